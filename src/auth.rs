@@ -0,0 +1,122 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use uuid::Uuid;
+
+use crate::AppState;
+
+/// Create a fresh management token for a webhook
+pub fn generate_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Tokens are only ever stored hashed
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Management token presented with a request, either as
+/// `Authorization: Bearer <token>` or as a `?token=` query parameter
+/// (the form used by dashboard links and WebSocket URLs).
+#[derive(Debug, Clone, Default)]
+pub struct Credentials(pub Option<String>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Credentials {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let bearer = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|t| t.trim().to_string());
+
+        let query = parts.uri.query().and_then(|q| {
+            serde_urlencoded::from_str::<Vec<(String, String)>>(q)
+                .ok()?
+                .into_iter()
+                .find(|(k, _)| k == "token")
+                .map(|(_, v)| v)
+        });
+
+        Ok(Credentials(bearer.or(query)))
+    }
+}
+
+/// Why a management request was turned away
+#[derive(Debug)]
+pub enum AuthError {
+    UnknownWebhook,
+    AdminDisabled,
+    MissingToken,
+    InvalidToken,
+    Database(sqlx::Error),
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            AuthError::UnknownWebhook => (StatusCode::NOT_FOUND, "Webhook not found".to_string()),
+            AuthError::AdminDisabled => (StatusCode::FORBIDDEN, "Set ADMIN_TOKEN to use this endpoint".to_string()),
+            AuthError::MissingToken => (StatusCode::UNAUTHORIZED, "Missing webhook token".to_string()),
+            AuthError::InvalidToken => (StatusCode::FORBIDDEN, "Invalid webhook token".to_string()),
+            AuthError::Database(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+        };
+        (status, Json(json!({"error": message}))).into_response()
+    }
+}
+
+impl AppState {
+    /// Check that the caller holds the management token for `webhook_id`.
+    ///
+    /// Unknown webhooks are reported as such, and a webhook without a token only
+    /// answers to the admin token.
+    pub async fn authorize(&self, webhook_id: &str, credentials: &Credentials) -> Result<(), AuthError> {
+        if self.is_admin(credentials) {
+            return Ok(());
+        }
+
+        let Some(expected) = self
+            .db
+            .get_token_hash(webhook_id)
+            .await
+            .map_err(AuthError::Database)?
+        else {
+            return Err(AuthError::UnknownWebhook);
+        };
+
+        match (expected, &credentials.0) {
+            (_, None) => Err(AuthError::MissingToken),
+            (Some(expected), Some(token)) if hash_token(token) == expected => Ok(()),
+            _ => Err(AuthError::InvalidToken),
+        }
+    }
+
+    /// Check access to operations spanning every webhook, such as listing.
+    ///
+    /// Without an `ADMIN_TOKEN` configured nobody may use them.
+    pub fn authorize_admin(&self, credentials: &Credentials) -> Result<(), AuthError> {
+        match (&self.settings.admin_token, &credentials.0) {
+            (None, _) => Err(AuthError::AdminDisabled),
+            (Some(_), None) => Err(AuthError::MissingToken),
+            (Some(_), Some(_)) if self.is_admin(credentials) => Ok(()),
+            (Some(_), Some(_)) => Err(AuthError::InvalidToken),
+        }
+    }
+
+    fn is_admin(&self, credentials: &Credentials) -> bool {
+        match (&self.settings.admin_token, &credentials.0) {
+            (Some(admin), Some(token)) => hash_token(admin) == hash_token(token),
+            _ => false,
+        }
+    }
+}
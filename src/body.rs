@@ -0,0 +1,222 @@
+use axum::body::Bytes;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use quick_xml::{events::Event, Reader, Writer};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Read;
+
+/// Largest body decompression may produce, so a small bomb can't fill memory
+const MAX_DECODED_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Most text shown for one multipart field
+const MAX_PART_TEXT: usize = 64 * 1024;
+
+/// A captured body, decompressed according to its `Content-Encoding`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedBody {
+    pub bytes: Vec<u8>,
+    /// The encodings that were removed, as sent (e.g. `gzip`), when there were any
+    pub encoding: Option<String>,
+}
+
+/// Undo `Content-Encoding`, applied in the order the sender listed it. `identity`
+/// and a missing header leave the body untouched.
+pub fn decode_content(content_encoding: Option<&str>, raw: &[u8]) -> Result<DecodedBody, String> {
+    let codings: Vec<String> = content_encoding
+        .unwrap_or_default()
+        .split(',')
+        .map(|c| c.trim().to_ascii_lowercase())
+        .filter(|c| !c.is_empty() && c != "identity")
+        .collect();
+    if codings.is_empty() {
+        return Ok(DecodedBody { bytes: raw.to_vec(), encoding: None });
+    }
+
+    let mut bytes = raw.to_vec();
+    for coding in codings.iter().rev() {
+        bytes = match coding.as_str() {
+            "gzip" | "x-gzip" => read_limited(GzDecoder::new(bytes.as_slice())),
+            // Meant to be zlib-wrapped, but raw deflate is common enough to accept
+            "deflate" => read_limited(ZlibDecoder::new(bytes.as_slice()))
+                .or_else(|_| read_limited(DeflateDecoder::new(bytes.as_slice()))),
+            "br" => read_limited(brotli_decompressor::Decompressor::new(bytes.as_slice(), 4096)),
+            other => return Err(format!("Unsupported content encoding {other}")),
+        }
+        .map_err(|e| format!("Could not decode {coding} body: {e}"))?;
+    }
+    Ok(DecodedBody { bytes, encoding: Some(codings.join(", ")) })
+}
+
+/// [`decode_content`] on the blocking pool, as inflating up to 32 MiB would hold up
+/// the async workers. Bodies without an encoding are passed through in place.
+pub async fn decode_content_async(content_encoding: Option<&str>, raw: Bytes) -> Result<DecodedBody, String> {
+    let Some(content_encoding) = content_encoding else {
+        return decode_content(None, &raw);
+    };
+    let content_encoding = content_encoding.to_string();
+    tokio::task::spawn_blocking(move || decode_content(Some(&content_encoding), &raw))
+        .await
+        .map_err(|e| format!("Decoding failed: {e}"))?
+}
+
+fn read_limited(reader: impl Read) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    reader.take(MAX_DECODED_SIZE + 1).read_to_end(&mut out)?;
+    if out.len() as u64 > MAX_DECODED_SIZE {
+        return Err(std::io::Error::other(format!("larger than {} MiB once decoded", MAX_DECODED_SIZE / 1024 / 1024)));
+    }
+    Ok(out)
+}
+
+/// A body broken down by its content type, for display
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ParsedBody {
+    Empty,
+    Json { value: Value },
+    /// URL-encoded form fields, in the order they were sent
+    Form { fields: Vec<FormField> },
+    Multipart { parts: Vec<MultipartPart> },
+    /// Indented XML
+    Xml { text: String },
+    Text { text: String },
+    Binary { size: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FormField {
+    pub name: String,
+    pub value: String,
+}
+
+/// One part of a `multipart/form-data` body
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MultipartPart {
+    pub name: Option<String>,
+    /// Set for file uploads
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub size: usize,
+    /// The part's content when it is text, cut to 64 KiB
+    pub text: Option<String>,
+    /// Stored file to download the part from, for file uploads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
+}
+
+impl MultipartPart {
+    pub fn is_file(&self) -> bool {
+        self.filename.is_some()
+    }
+}
+
+/// Parse a decoded body according to its `Content-Type`. Bodies that don't parse as
+/// their declared type fall back to text, or to their size when they aren't UTF-8.
+pub async fn parse(content_type: Option<&str>, body: &[u8]) -> ParsedBody {
+    if body.is_empty() {
+        return ParsedBody::Empty;
+    }
+
+    let mime = content_type.unwrap_or_default().to_ascii_lowercase();
+    let essence = mime.split(';').next().unwrap_or_default().trim();
+
+    let parsed = if essence == "application/json" || essence.ends_with("+json") {
+        serde_json::from_slice(body).ok().map(|value| ParsedBody::Json { value })
+    } else if essence == "application/x-www-form-urlencoded" {
+        parse_form(body)
+    } else if essence == "multipart/form-data" {
+        multipart_parts(content_type.unwrap_or_default(), body)
+            .await
+            .map(|parts| ParsedBody::Multipart { parts: parts.into_iter().map(|(part, _)| part).collect() })
+    } else if essence.ends_with("/xml") || essence.ends_with("+xml") {
+        indent_xml(body).map(|text| ParsedBody::Xml { text })
+    } else if essence.is_empty() {
+        // Undeclared bodies are often JSON anyway
+        serde_json::from_slice(body).ok().map(|value| ParsedBody::Json { value })
+    } else {
+        None
+    };
+
+    parsed.unwrap_or_else(|| match std::str::from_utf8(body) {
+        Ok(text) => ParsedBody::Text { text: text.to_string() },
+        Err(_) => ParsedBody::Binary { size: body.len() },
+    })
+}
+
+fn parse_form(body: &[u8]) -> Option<ParsedBody> {
+    let fields: Vec<(String, String)> = serde_urlencoded::from_bytes(body).ok()?;
+    Some(ParsedBody::Form {
+        fields: fields.into_iter().map(|(name, value)| FormField { name, value }).collect(),
+    })
+}
+
+/// Split a `multipart/form-data` body into its parts, each with its contents, or
+/// `None` if the body doesn't follow the boundary in `content_type`.
+pub async fn multipart_parts(content_type: &str, body: &[u8]) -> Option<Vec<(MultipartPart, Bytes)>> {
+    let boundary = multer::parse_boundary(content_type).ok()?;
+    let chunk = Bytes::copy_from_slice(body);
+    let stream = futures_util::stream::once(async move { Ok::<_, std::convert::Infallible>(chunk) });
+    let mut multipart = multer::Multipart::new(stream, boundary);
+
+    let mut parts = Vec::new();
+    while let Some(field) = multipart.next_field().await.ok()? {
+        let name = field.name().map(String::from);
+        let filename = field.file_name().map(String::from);
+        let content_type = field.content_type().map(|mime| mime.to_string());
+        let bytes = field.bytes().await.ok()?;
+
+        let textual = content_type.as_deref().is_none_or(|mime| mime.starts_with("text/") || mime.contains("json") || mime.contains("xml"));
+        let text = if textual { std::str::from_utf8(&bytes).ok().map(|text| truncate(text, MAX_PART_TEXT)) } else { None };
+        parts.push((MultipartPart { name, filename, content_type, size: bytes.len(), text, file_id: None }, bytes));
+    }
+    Some(parts)
+}
+
+/// The file uploads of a body, with their position among all its parts; empty
+/// unless the body is `multipart/form-data`
+pub async fn file_parts(content_type: Option<&str>, body: &[u8]) -> Vec<(usize, MultipartPart, Bytes)> {
+    let content_type = content_type.unwrap_or_default();
+    if !content_type.to_ascii_lowercase().starts_with("multipart/form-data") {
+        return Vec::new();
+    }
+    multipart_parts(content_type, body)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (part, _))| part.is_file())
+        .map(|(index, (part, bytes))| (index, part, bytes))
+        .collect()
+}
+
+/// Re-indent an XML document two spaces per level, or `None` if it isn't well-formed.
+pub fn indent_xml(body: &[u8]) -> Option<String> {
+    let mut reader = Reader::from_reader(body);
+    reader.config_mut().trim_text(true);
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    // The reader doesn't notice elements left open at the end
+    let mut depth = 0usize;
+    loop {
+        let event = reader.read_event().ok()?;
+        match &event {
+            Event::Eof if depth == 0 => break,
+            Event::Eof => return None,
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.checked_sub(1)?,
+            _ => {}
+        }
+        writer.write_event(event).ok()?;
+    }
+    String::from_utf8(writer.into_inner()).ok()
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &text[..end])
+}
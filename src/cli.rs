@@ -0,0 +1,730 @@
+use anstyle::{AnsiColor, Style};
+use anyhow::{bail, Context};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    future::Future,
+    io::IsTerminal,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::{
+    export::{replayable_headers, request_path},
+    models::{DeliveryReport, StoredRequest},
+    routes::webhook::NewWebhookResponse,
+    search::StatusFilter,
+};
+
+/// Limit on one local delivery
+const RELAY_TIMEOUT: Duration = Duration::from_secs(30);
+/// Most undelivered requests a relay catches up on when it (re)connects
+const RELAY_CATCH_UP: u32 = 1000;
+/// Reconnect delays after the server goes away, doubling from the first to the second
+const RELAY_BACKOFF: (Duration, Duration) = (Duration::from_secs(1), Duration::from_secs(30));
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Command line of the `webhook_tester` binary
+#[derive(Parser)]
+#[command(version, about = "Capture, inspect and replay webhooks")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the server (the default)
+    Serve,
+    /// Create a webhook and print its URLs and management token
+    New {
+        #[command(flatten)]
+        server: Server,
+    },
+    /// Print a webhook's requests as they arrive
+    Tail {
+        webhook_id: String,
+        #[command(flatten)]
+        filter: RequestFilter,
+        /// Print each request's body under its summary
+        #[arg(long)]
+        body: bool,
+        #[command(flatten)]
+        server: Server,
+    },
+    /// List a webhook's captured requests, newest first
+    Ls {
+        webhook_id: String,
+        /// Search expression, as in the dashboard's search box
+        #[arg(long, short)]
+        query: Option<String>,
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: u32,
+        #[command(flatten)]
+        server: Server,
+    },
+    /// Print a captured request in full, with the response it got
+    Show {
+        request_id: String,
+        #[command(flatten)]
+        server: Server,
+    },
+    /// Send a captured request again, to another URL
+    Replay {
+        request_id: String,
+        #[arg(long)]
+        to: String,
+        #[command(flatten)]
+        server: Server,
+    },
+    /// Change how a webhook answers; options left out stay as they are
+    Config {
+        webhook_id: String,
+        #[arg(long)]
+        status: Option<u16>,
+        #[arg(long)]
+        body: Option<String>,
+        #[arg(long)]
+        content_type: Option<String>,
+        /// Also send every request on to this URL
+        #[arg(long)]
+        forward_url: Option<String>,
+        #[command(flatten)]
+        server: Server,
+    },
+    /// Download a webhook's requests as HAR, NDJSON or a `curl` script
+    Export {
+        webhook_id: String,
+        #[arg(long, value_enum, default_value = "har")]
+        format: FileFormat,
+        /// Comma-separated request IDs, instead of the whole history
+        #[arg(long)]
+        ids: Option<String>,
+        /// Written to standard output when omitted
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        server: Server,
+    },
+    /// Load requests from a HAR, NDJSON or curl file into a webhook on a running server
+    Import {
+        webhook_id: String,
+        file: PathBuf,
+        /// Detected from the contents when omitted
+        #[arg(long, value_enum)]
+        format: Option<FileFormat>,
+        #[command(flatten)]
+        server: Server,
+    },
+    /// Re-send a webhook's requests to a local URL as they arrive, reporting each response back
+    Relay {
+        webhook_id: String,
+        /// Where to deliver, e.g. http://localhost:8080/hooks
+        target: String,
+        #[command(flatten)]
+        server: Server,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum FileFormat {
+    Har,
+    Ndjson,
+    Curl,
+}
+
+impl FileFormat {
+    fn as_str(self) -> &'static str {
+        match self {
+            FileFormat::Har => "har",
+            FileFormat::Ndjson => "ndjson",
+            FileFormat::Curl => "curl",
+        }
+    }
+}
+
+/// Where the server runs and how to authenticate with it
+#[derive(clap::Args, Clone)]
+pub struct Server {
+    #[arg(long, env = "WEBHOOK_TESTER_URL", default_value = "http://localhost:3000")]
+    pub url: String,
+    /// Management token of the webhook, or the admin token
+    #[arg(long, env = "WEBHOOK_TESTER_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+}
+
+impl Server {
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}{path}", self.url.trim_end_matches('/'))
+    }
+
+    /// WebSocket URL of a webhook's live feed
+    fn socket(&self, webhook_id: &str) -> anyhow::Result<String> {
+        let url = self.endpoint(&format!("/ws/{webhook_id}"));
+        if let Some(rest) = url.strip_prefix("https://") {
+            Ok(format!("wss://{rest}"))
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            Ok(format!("ws://{rest}"))
+        } else {
+            bail!("Server URL must start with http:// or https://: {}", self.url)
+        }
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn get(&self, path: &str) -> anyhow::Result<reqwest::Response> {
+        Ok(self.authorize(reqwest::Client::new().get(self.endpoint(path))).send().await?)
+    }
+
+    async fn post(&self, path: &str, payload: &Value) -> anyhow::Result<Value> {
+        let request = reqwest::Client::new().post(self.endpoint(path)).json(payload);
+        check(self.authorize(request).send().await?).await
+    }
+
+    async fn patch(&self, path: &str, payload: &Value) -> anyhow::Result<Value> {
+        let request = reqwest::Client::new().patch(self.endpoint(path)).json(payload);
+        check(self.authorize(request).send().await?).await
+    }
+}
+
+/// Which requests `tail` prints
+#[derive(clap::Args, Clone, Default)]
+pub struct RequestFilter {
+    #[arg(long)]
+    pub method: Option<String>,
+    /// Status code or class of the response sent, e.g. 500 or 4xx
+    #[arg(long)]
+    pub status: Option<StatusFilter>,
+    /// Text the body or a header value contains, ignoring case
+    #[arg(long)]
+    pub grep: Option<String>,
+}
+
+impl RequestFilter {
+    pub fn matches(&self, req: &StoredRequest) -> bool {
+        if self.method.as_ref().is_some_and(|method| !method.eq_ignore_ascii_case(&req.method)) {
+            return false;
+        }
+        if let Some(status) = self.status {
+            let (low, high) = status.range();
+            if !req.response_status.is_some_and(|code| (low..=high).contains(&code)) {
+                return false;
+            }
+        }
+        if let Some(text) = &self.grep {
+            let text = text.to_lowercase();
+            let in_headers = req.header_map().values().any(|value| value.to_lowercase().contains(&text));
+            if !in_headers && !req.body.to_lowercase().contains(&text) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Run a client subcommand against a server
+pub async fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Serve => bail!("`serve` runs the server, not a client command"),
+        Command::New { server } => new_webhook(&server).await,
+        Command::Tail { webhook_id, filter, body, server } => tail(&server, &webhook_id, filter, body).await,
+        Command::Ls { webhook_id, query, limit, server } => list(&server, &webhook_id, query.as_deref(), limit).await,
+        Command::Show { request_id, server } => show(&server, &request_id).await,
+        Command::Replay { request_id, to, server } => replay(&server, &request_id, &to).await,
+        Command::Config { webhook_id, status, body, content_type, forward_url, server } => {
+            let mut payload = Map::new();
+            for (key, value) in [
+                ("status_code", status.map(Value::from)),
+                ("response_body", body.map(Value::from)),
+                ("content_type", content_type.map(Value::from)),
+                ("forward_url", forward_url.map(Value::from)),
+            ] {
+                if let Some(value) = value {
+                    payload.insert(key.to_string(), value);
+                }
+            }
+            server.patch(&format!("/webhook/{webhook_id}/config"), &Value::Object(payload)).await?;
+            println!("Updated the response of {webhook_id}");
+            Ok(())
+        }
+        Command::Export { webhook_id, format, ids, output, server } => {
+            export(&server, &webhook_id, format, ids.as_deref(), output.as_ref()).await
+        }
+        Command::Import { webhook_id, file, format, server } => {
+            import(&server, &webhook_id, &file, format).await
+        }
+        Command::Relay { webhook_id, target, server } => relay(&server, &webhook_id, &target).await,
+    }
+}
+
+async fn new_webhook(server: &Server) -> anyhow::Result<()> {
+    let response = reqwest::Client::new().get(server.endpoint("/new")).send().await?;
+    let webhook: NewWebhookResponse = serde_json::from_value(check(response).await?)?;
+    println!("id:        {}", webhook.id);
+    println!("webhook:   {}", server.endpoint(&webhook.webhook_url));
+    println!("dashboard: {}", server.endpoint(&webhook.dashboard_url));
+    println!("token:     {}", webhook.token);
+    Ok(())
+}
+
+async fn tail(server: &Server, webhook_id: &str, filter: RequestFilter, body: bool) -> anyhow::Result<()> {
+    let paint = Paint::detect();
+    let socket = connect(server, webhook_id).await?;
+    eprintln!("Waiting for requests to {webhook_id}");
+
+    follow(server, webhook_id, socket, None, |req| {
+        if filter.matches(&req) {
+            println!("{}", summary(&req, paint));
+            if body && !req.body.is_empty() {
+                println!("{}\n", pretty_body(&req.body, req.header_map().get("content-type")));
+            }
+        }
+        async { None }
+    })
+    .await
+}
+
+async fn list(server: &Server, webhook_id: &str, query: Option<&str>, limit: u32) -> anyhow::Result<()> {
+    // An empty search is the newest requests, so the server applies the limit either way
+    let query = serde_urlencoded::to_string([("q", query.unwrap_or_default()), ("limit", &limit.to_string())])?;
+    let response = server.get(&format!("/api/webhooks/{webhook_id}/search?{query}")).await?;
+    let requests: Vec<StoredRequest> = serde_json::from_value(check(response).await?)?;
+
+    let paint = Paint::detect();
+    for req in &requests {
+        println!("{}", summary(req, paint));
+    }
+    Ok(())
+}
+
+async fn show(server: &Server, request_id: &str) -> anyhow::Result<()> {
+    let response = server.get(&format!("/api/requests/{request_id}")).await?;
+    let req: StoredRequest = serde_json::from_value(check(response).await?)?;
+    let paint = Paint::detect();
+    let heading = AnsiColor::Cyan.on_default().bold();
+
+    println!("{} {}", paint.method(&req.method), request_path(&req));
+    println!("{}  {}", paint.apply(Style::new().dimmed(), &req.id), local_time(&req.created_at));
+    if let Some(client_ip) = &req.client_ip {
+        // The peer is only worth showing when it was a proxy
+        let via = req
+            .remote_addr
+            .as_deref()
+            .filter(|peer| !peer.parse::<std::net::SocketAddr>().is_ok_and(|addr| addr.ip().to_string() == *client_ip));
+        println!("from: {client_ip}{}", via.map(|peer| format!(" via {peer}")).unwrap_or_default());
+    }
+    if let (Some(version), Some(uri)) = (&req.http_version, &req.request_uri) {
+        println!("{version} {uri}");
+    }
+    if let Some(received_at) = &req.received_at {
+        let size = req.body_size.map(|size| format!(", {size} B")).unwrap_or_default();
+        let latency = req.latency_ms.map(|ms| format!(", answered in {ms:.3} ms")).unwrap_or_default();
+        println!("received: {received_at}{size}{latency}");
+    }
+    if let Some(status) = &req.signature_status {
+        println!("signature: {status}{}", req.signature_reason.as_deref().map(|r| format!(" ({r})")).unwrap_or_default());
+    }
+    if let Some(status) = &req.schema_status {
+        println!("schema: {status}");
+        for error in req.schema_error_list() {
+            let path = if error.path.is_empty() { "(body)" } else { &error.path };
+            println!("  {} {}", paint.apply(AnsiColor::Red.on_default(), path), error.message);
+        }
+    }
+
+    println!("\n{}", paint.apply(heading, "Headers"));
+    for (name, value) in req.header_map() {
+        println!("  {name}: {value}");
+    }
+    if !req.body.is_empty() {
+        let decoded = req.body_encoding.as_deref().map(|encoding| format!(" (decoded from {encoding})")).unwrap_or_default();
+        println!("\n{}{decoded}", paint.apply(heading, "Body"));
+        println!("{}", pretty_body(&req.body, req.header_map().get("content-type")));
+    }
+
+    if let Some(status) = req.response_status {
+        println!("\n{} {}", paint.apply(heading, "Response"), paint.status(Some(status)));
+        for (name, value) in req.response_header_map() {
+            println!("  {name}: {value}");
+        }
+        if let Some(body) = req.response_body.as_deref().filter(|body| !body.is_empty()) {
+            println!("{}", pretty_body(body, req.response_header_map().get("content-type")));
+        }
+    }
+    Ok(())
+}
+
+async fn replay(server: &Server, request_id: &str, to: &str) -> anyhow::Result<()> {
+    let result = server.post(&format!("/replay/{request_id}"), &json!({"target": to})).await?;
+    match result["status_code"].as_u64() {
+        Some(status) => println!("Replayed {request_id} to {to}: {status}"),
+        None => println!("Replayed {request_id} to {to}"),
+    }
+    Ok(())
+}
+
+async fn export(
+    server: &Server,
+    webhook_id: &str,
+    format: FileFormat,
+    ids: Option<&str>,
+    output: Option<&PathBuf>,
+) -> anyhow::Result<()> {
+    let mut query = vec![("format", format.as_str())];
+    if let Some(ids) = ids {
+        query.push(("ids", ids));
+    }
+    let query = serde_urlencoded::to_string(query)?;
+    let response = server.get(&format!("/api/webhooks/{webhook_id}/export?{query}")).await?;
+    let contents = ensure_success(response).await?.bytes().await?;
+    match output {
+        Some(path) => {
+            tokio::fs::write(path, &contents)
+                .await
+                .with_context(|| format!("Writing {}", path.display()))?;
+            eprintln!("Exported {webhook_id} to {}", path.display());
+        }
+        None => {
+            use std::io::Write;
+            std::io::stdout().write_all(&contents)?;
+        }
+    }
+    Ok(())
+}
+
+async fn import(server: &Server, webhook_id: &str, file: &PathBuf, format: Option<FileFormat>) -> anyhow::Result<()> {
+    let contents = tokio::fs::read_to_string(file)
+        .await
+        .with_context(|| format!("Reading {}", file.display()))?;
+
+    let mut request = reqwest::Client::new()
+        .post(server.endpoint(&format!("/api/webhooks/{webhook_id}/import")))
+        .body(contents);
+    if let Some(format) = format {
+        request = request.query(&[("format", format.as_str())]);
+    }
+
+    let result = check(server.authorize(request).send().await?).await?;
+    println!("Imported {} requests into {webhook_id}", result["imported"]);
+    Ok(())
+}
+
+/// The JSON body of a successful response, or the server's error message
+async fn check(response: reqwest::Response) -> anyhow::Result<Value> {
+    Ok(ensure_success(response).await?.json().await.unwrap_or(Value::Null))
+}
+
+/// A successful response as it is, or the server's error message
+async fn ensure_success(response: reqwest::Response) -> anyhow::Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    let message = body["error"].as_str().unwrap_or("no details");
+    bail!("Server answered {status}: {message}");
+}
+
+/// ANSI styling, used only when standard output is a terminal and `NO_COLOR` isn't set
+#[derive(Clone, Copy)]
+struct Paint(bool);
+
+impl Paint {
+    fn detect() -> Self {
+        Self(std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal())
+    }
+
+    fn apply(self, style: Style, text: impl Display) -> String {
+        if self.0 {
+            format!("{style}{text}{style:#}")
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn method(self, method: &str) -> String {
+        let color = match method {
+            "GET" => AnsiColor::Green,
+            "POST" => AnsiColor::Yellow,
+            "PUT" | "PATCH" => AnsiColor::Blue,
+            "DELETE" => AnsiColor::Red,
+            _ => AnsiColor::Magenta,
+        };
+        self.apply(color.on_default().bold(), format!("{method:<7}"))
+    }
+
+    fn status(self, status: Option<u16>) -> String {
+        let Some(status) = status else {
+            return self.apply(Style::new().dimmed(), "---");
+        };
+        let color = match status {
+            200..=299 => AnsiColor::Green,
+            300..=399 => AnsiColor::Cyan,
+            400..=499 => AnsiColor::Yellow,
+            _ => AnsiColor::Red,
+        };
+        self.apply(color.on_default(), status)
+    }
+}
+
+/// One line per request: when, method, response status, body size, ID and path
+fn summary(req: &StoredRequest, paint: Paint) -> String {
+    let size = format!("{} B", req.body.len());
+    format!(
+        "{}  {} {}  {size:>8}  {}  {}",
+        local_time(&req.created_at),
+        paint.method(&req.method),
+        paint.status(req.response_status),
+        paint.apply(Style::new().dimmed(), &req.id),
+        request_path(req),
+    )
+}
+
+fn local_time(timestamp: &str) -> String {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|at| at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+/// JSON pretty-printed, XML indented and forms one field per line; anything else as is
+fn pretty_body(body: &str, content_type: Option<&String>) -> String {
+    let content_type = content_type.map(|c| c.to_ascii_lowercase()).unwrap_or_default();
+    let pretty = if content_type.contains("xml") {
+        crate::body::indent_xml(body.as_bytes())
+    } else if content_type.starts_with("application/x-www-form-urlencoded") {
+        serde_urlencoded::from_str::<Vec<(String, String)>>(body)
+            .ok()
+            .map(|fields| fields.iter().map(|(name, value)| format!("{name} = {value}")).collect::<Vec<_>>().join("\n"))
+    } else {
+        serde_json::from_str::<Value>(body).ok().and_then(|value| serde_json::to_string_pretty(&value).ok())
+    };
+    pretty.unwrap_or_else(|| body.to_string())
+}
+
+async fn relay(server: &Server, webhook_id: &str, target: &str) -> anyhow::Result<()> {
+    let target = reqwest::Url::parse(target).with_context(|| format!("Invalid target URL {target}"))?;
+    let client = reqwest::Client::builder()
+        .timeout(RELAY_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+
+    // Anything captured from here on that no relay reported is sent after a reconnect
+    let since = Utc::now();
+    let socket = connect(server, webhook_id).await?;
+    println!("Relaying {webhook_id} to {target}");
+
+    follow(server, webhook_id, socket, Some(since), |req| {
+        let client = client.clone();
+        let target = target.clone();
+        async move {
+            let report = deliver(server, &client, &target, &req).await;
+            match (report.status_code, &report.error) {
+                (Some(status), _) => println!("{} {} -> {status} ({:.0} ms)", req.method, req.id, report.duration_ms.unwrap_or_default()),
+                (None, error) => println!("{} {} -> failed: {}", req.method, req.id, error.as_deref().unwrap_or("no response")),
+            }
+            serde_json::to_string(&report).ok()
+        }
+    })
+    .await
+}
+
+async fn connect(server: &Server, webhook_id: &str) -> anyhow::Result<Socket> {
+    let url = server.socket(webhook_id)?;
+    let mut request = url.as_str().into_client_request()?;
+    if let Some(token) = &server.token {
+        request
+            .headers_mut()
+            .insert("authorization", HeaderValue::from_str(&format!("Bearer {token}"))?);
+    }
+    let (socket, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .with_context(|| format!("Connecting to {url}"))?;
+    Ok(socket)
+}
+
+/// Hand each request on a webhook's live feed to `handle`, one at a time and in the
+/// order they were captured, sending back whatever it answers. Callers connect first,
+/// so a bad URL or token fails straight away; a server that goes away later is waited for.
+///
+/// With `catch_up`, each connection starts with the requests captured since then that
+/// no relay has reported delivering, so none are lost while the server was away.
+async fn follow<F, Fut>(
+    server: &Server,
+    webhook_id: &str,
+    mut socket: Socket,
+    catch_up: Option<DateTime<Utc>>,
+    mut handle: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(StoredRequest) -> Fut,
+    Fut: Future<Output = Option<String>>,
+{
+    loop {
+        if let Err(err) = follow_session(server, webhook_id, socket, catch_up, &mut handle).await {
+            eprintln!("Connection lost: {err}");
+        } else {
+            eprintln!("Connection closed by the server");
+        }
+
+        let mut backoff = RELAY_BACKOFF.0;
+        socket = loop {
+            tokio::time::sleep(backoff).await;
+            match connect(server, webhook_id).await {
+                Ok(socket) => break socket,
+                Err(err) => {
+                    eprintln!("Reconnecting failed: {err:#}");
+                    backoff = (backoff * 2).min(RELAY_BACKOFF.1);
+                }
+            }
+        };
+        eprintln!("Reconnected");
+    }
+}
+
+async fn follow_session<F, Fut>(
+    server: &Server,
+    webhook_id: &str,
+    socket: Socket,
+    catch_up: Option<DateTime<Utc>>,
+    handle: &mut F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(StoredRequest) -> Fut,
+    Fut: Future<Output = Option<String>>,
+{
+    let (mut sink, mut stream) = socket.split();
+
+    // Requests captured after connecting can be both caught up on and on the feed
+    let mut caught_up = HashSet::new();
+    if let Some(since) = catch_up {
+        for req in undelivered(server, webhook_id, since).await? {
+            caught_up.insert(req.id.clone());
+            if let Some(reply) = handle(req).await {
+                sink.send(Message::Text(reply)).await?;
+            }
+        }
+    }
+
+    while let Some(message) = stream.next().await {
+        let Message::Text(text) = message? else { continue };
+        let Ok(value) = serde_json::from_str::<Value>(&text) else { continue };
+        // The greeting and dashboard events aren't captured requests
+        if value.get("status").is_some() || value.get("event").is_some() {
+            continue;
+        }
+        let Ok(req) = serde_json::from_value::<StoredRequest>(value) else { continue };
+        if caught_up.remove(&req.id) {
+            continue;
+        }
+
+        if let Some(reply) = handle(req).await {
+            sink.send(Message::Text(reply)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Requests captured since `since` that no relay has reported, oldest first
+async fn undelivered(server: &Server, webhook_id: &str, since: DateTime<Utc>) -> anyhow::Result<Vec<StoredRequest>> {
+    let query = format!("delivered:no after:{}", since.to_rfc3339_opts(SecondsFormat::Micros, true));
+    let query = serde_urlencoded::to_string([("q", query.as_str()), ("limit", &RELAY_CATCH_UP.to_string())])?;
+    let response = server.get(&format!("/api/webhooks/{webhook_id}/search?{query}")).await?;
+    let mut requests: Vec<StoredRequest> = serde_json::from_value(check(response).await?)?;
+    if requests.len() as u32 == RELAY_CATCH_UP {
+        eprintln!("Catching up on the newest {RELAY_CATCH_UP} undelivered requests only");
+    }
+    requests.reverse();
+    Ok(requests)
+}
+
+/// The body as it was received, for requests whose stored text isn't that:
+/// compressed bodies and ones that aren't text at all
+async fn raw_body(server: &Server, req: &StoredRequest) -> anyhow::Result<Option<Vec<u8>>> {
+    let not_text = req.body.is_empty() && req.body_size.unwrap_or_default() > 0;
+    if req.body_encoding.is_none() && !not_text {
+        return Ok(None);
+    }
+    let response = ensure_success(server.get(&format!("/api/requests/{}/raw", req.id)).await?).await?;
+    Ok(Some(response.bytes().await?.to_vec()))
+}
+
+/// Send one captured request to the local target, with its original query string
+async fn deliver(server: &Server, client: &reqwest::Client, target: &reqwest::Url, req: &StoredRequest) -> DeliveryReport {
+    let mut url = target.clone();
+    let query = req.query_map();
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query.iter());
+    }
+
+    let mut report = DeliveryReport {
+        request_id: req.id.clone(),
+        target: url.to_string(),
+        status_code: None,
+        response_headers: None,
+        response_body: None,
+        error: None,
+        duration_ms: None,
+    };
+
+    let method = match reqwest::Method::from_bytes(req.method.as_bytes()) {
+        Ok(method) => method,
+        Err(err) => {
+            report.error = Some(format!("Invalid method {}: {err}", req.method));
+            return report;
+        }
+    };
+    let mut headers = replayable_headers(req);
+    let body = match raw_body(server, req).await {
+        // The bytes as received still carry their content encoding
+        Ok(Some(raw)) => {
+            if let Some(encoding) = req.header_map().remove("content-encoding") {
+                headers.insert("content-encoding".into(), encoding);
+            }
+            raw
+        }
+        Ok(None) => req.body.clone().into_bytes(),
+        Err(err) => {
+            report.error = Some(format!("Fetching the body: {err:#}"));
+            return report;
+        }
+    };
+    let mut request = client.request(method, url).body(body);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+
+    let started = Instant::now();
+    match request.send().await {
+        Ok(response) => {
+            report.status_code = Some(response.status().as_u16());
+            let headers: BTreeMap<String, String> = response
+                .headers()
+                .iter()
+                .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+                .collect();
+            report.response_headers = serde_json::to_string(&headers).ok();
+            match response.text().await {
+                Ok(body) => report.response_body = Some(body),
+                Err(err) => report.error = Some(format!("Reading the response: {err}")),
+            }
+        }
+        Err(err) => report.error = Some(err.to_string()),
+    }
+    report.duration_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
+    report
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    export::{curl_command, replayable_headers, request_url, shell_method, shell_quote},
+    models::StoredRequest,
+};
+
+/// Languages and tools a captured request can be rendered for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeTarget {
+    Curl,
+    Httpie,
+    /// Rust, with `reqwest`
+    Reqwest,
+    /// Python, with `requests`
+    Python,
+    /// JavaScript `fetch`
+    Fetch,
+}
+
+impl CodeTarget {
+    pub const ALL: [CodeTarget; 5] = [
+        CodeTarget::Curl,
+        CodeTarget::Httpie,
+        CodeTarget::Reqwest,
+        CodeTarget::Python,
+        CodeTarget::Fetch,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CodeTarget::Curl => "curl",
+            CodeTarget::Httpie => "httpie",
+            CodeTarget::Reqwest => "reqwest",
+            CodeTarget::Python => "python",
+            CodeTarget::Fetch => "fetch",
+        }
+    }
+}
+
+/// Render `req` as a ready-to-run snippet that resends it to the URL it was captured on
+pub fn render(req: &StoredRequest, target: CodeTarget) -> String {
+    let url = request_url(req);
+    let headers = replayable_headers(req);
+
+    match target {
+        CodeTarget::Curl => curl_command(req, &shell_quote(&url)),
+        CodeTarget::Httpie => httpie(req, &url, &headers),
+        CodeTarget::Reqwest => rust_reqwest(req, &url, &headers),
+        CodeTarget::Python => python_requests(req, &url, &headers),
+        CodeTarget::Fetch => js_fetch(req, &url, &headers),
+    }
+}
+
+fn httpie(req: &StoredRequest, url: &str, headers: &BTreeMap<String, String>) -> String {
+    let mut lines = vec![format!("http --ignore-stdin {} {}", shell_method(&req.method), shell_quote(url))];
+    for (name, value) in headers {
+        // `Name;` is HTTPie's spelling of a header with an empty value
+        let item = if value.is_empty() { format!("{name};") } else { format!("{name}:{value}") };
+        lines.push(shell_quote(&item));
+    }
+    if !req.body.is_empty() {
+        lines.push(format!("--raw {}", shell_quote(&req.body)));
+    }
+    lines.join(" \\\n  ")
+}
+
+/// A Rust string literal; `Debug` output for `str` is valid Rust
+fn rust_str(value: &str) -> String {
+    format!("{value:?}")
+}
+
+fn rust_reqwest(req: &StoredRequest, url: &str, headers: &BTreeMap<String, String>) -> String {
+    let method = match req.method.as_str() {
+        "GET" | "POST" | "PUT" | "DELETE" | "PATCH" | "HEAD" | "OPTIONS" => format!("reqwest::Method::{}", req.method),
+        other => format!("reqwest::Method::from_bytes({}.as_bytes())?", rust_str(other)),
+    };
+
+    let mut code = format!("let response = reqwest::Client::new()\n    .request({method}, {})\n", rust_str(url));
+    for (name, value) in headers {
+        code.push_str(&format!("    .header({}, {})\n", rust_str(name), rust_str(value)));
+    }
+    if !req.body.is_empty() {
+        code.push_str(&format!("    .body({})\n", rust_str(&req.body)));
+    }
+    code.push_str("    .send()\n    .await?;\nprintln!(\"{} {}\", response.status(), response.text().await?);");
+    code
+}
+
+/// A double-quoted string literal valid in both Python and JavaScript
+fn json_str(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn python_requests(req: &StoredRequest, url: &str, headers: &BTreeMap<String, String>) -> String {
+    let mut code = format!(
+        "import requests\n\nresponse = requests.request(\n    {},\n    {},\n",
+        json_str(&req.method),
+        json_str(url)
+    );
+    code.push_str("    headers={\n");
+    for (name, value) in headers {
+        code.push_str(&format!("        {}: {},\n", json_str(name), json_str(value)));
+    }
+    code.push_str("    },\n");
+    if !req.body.is_empty() {
+        code.push_str(&format!("    data={}.encode(\"utf-8\"),\n", json_str(&req.body)));
+    }
+    code.push_str(")\nprint(response.status_code, response.text)");
+    code
+}
+
+fn js_fetch(req: &StoredRequest, url: &str, headers: &BTreeMap<String, String>) -> String {
+    let mut code = format!("const response = await fetch({}, {{\n  method: {},\n", json_str(url), json_str(&req.method));
+    code.push_str("  headers: {\n");
+    for (name, value) in headers {
+        code.push_str(&format!("    {}: {},\n", json_str(name), json_str(value)));
+    }
+    code.push_str("  },\n");
+    // fetch refuses a body on GET and HEAD
+    if !req.body.is_empty() && !matches!(req.method.as_str(), "GET" | "HEAD") {
+        code.push_str(&format!("  body: {},\n", json_str(&req.body)));
+    }
+    code.push_str("});\nconsole.log(response.status, await response.text());");
+    code
+}
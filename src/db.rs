@@ -1,8 +1,26 @@
-use sqlx::SqlitePool;
+use sqlx::{sqlite::SqlitePoolOptions, QueryBuilder, Sqlite, SqlitePool};
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
 use std::{fs::OpenOptions, path::PathBuf};
-use crate::models::{StoredRequest, WebhookConfig};
+use tokio::sync::mpsc;
+use crate::auth::{generate_token, hash_token};
+use crate::models::{Delivery, ReplaySchedule, RequestFile, StoredRequest, Webhook, WebhookConfig};
+use crate::search::SearchQuery;
+
+const WEBHOOK_COLUMNS: &str =
+    "id, created_at, name, description, tags, expires_at, paused, paused_status, archived_at";
+
+const SCHEDULE_COLUMNS: &str =
+    "id, webhook_id, request_id, target, cron, signing, next_run_at,
+     last_run_at, last_status_code, last_error, runs, created_at";
+
+const REQUEST_COLUMNS: &str =
+    "id, webhook_id, method, headers, body, query, created_at,
+     response_status, response_headers, response_body, response_source, config_version, latency_ms,
+     signature_status, signature_reason, schema_status, schema_errors, body_encoding, raw_body,
+     remote_addr, client_ip, http_version, request_uri, body_size, received_at,
+     pinned, derived_from";
 
 /// Represents the database connection layer.
 #[derive(Clone)]
@@ -20,19 +38,65 @@ pub async fn init_db() -> Result<Database> {
         OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(false)
             .open(&db_path)?;
     }
 
     let db_url = format!("sqlite://{}", db_path.to_string_lossy());
     let pool = SqlitePool::connect(&db_url).await?;
 
-    sqlx::query("PRAGMA foreign_keys = ON;").execute(&pool).await?;
+    run_migrations(&pool).await?;
+
+    Ok(Database { pool })
+}
+
+/// Open a private in-memory database with the full schema.
+///
+/// The pool is capped at one connection because every `:memory:`
+/// connection would otherwise get its own empty database.
+pub async fn init_in_memory() -> Result<Database> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?;
+
+    run_migrations(&pool).await?;
+
+    Ok(Database { pool })
+}
+
+/// Create every table the service needs and bring older databases up to date.
+pub async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query("PRAGMA foreign_keys = ON;").execute(pool).await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS webhooks (
+            id TEXT PRIMARY KEY,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    ).execute(pool).await?;
+
+    add_column_if_missing(pool, "webhooks", "token_hash", "TEXT").await?;
+    add_column_if_missing(pool, "webhooks", "name", "TEXT").await?;
+    add_column_if_missing(pool, "webhooks", "description", "TEXT").await?;
+    add_column_if_missing(pool, "webhooks", "tags", "TEXT NOT NULL DEFAULT '[]'").await?;
+    add_column_if_missing(pool, "webhooks", "expires_at", "TEXT").await?;
+    add_column_if_missing(pool, "webhooks", "paused", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "webhooks", "paused_status", "INTEGER").await?;
+    add_column_if_missing(pool, "webhooks", "archived_at", "TEXT").await?;
+
+    // Tables created before webhook ownership was enforced lack their foreign
+    // key; move them aside so they can be recreated and copied back below.
+    let requests_legacy = set_aside_without_foreign_key(pool, "requests").await?;
+    let configs_legacy = set_aside_without_foreign_key(pool, "webhook_configs").await?;
 
     sqlx::query(
         r#"
         CREATE TABLE IF NOT EXISTS requests (
             id TEXT PRIMARY KEY,
-            webhook_id TEXT NOT NULL,
+            webhook_id TEXT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
             method TEXT NOT NULL,
             headers TEXT,
             body TEXT,
@@ -40,88 +104,763 @@ pub async fn init_db() -> Result<Database> {
             created_at TEXT DEFAULT CURRENT_TIMESTAMP
         )
         "#
-    ).execute(&pool).await?;
+    ).execute(pool).await?;
 
     sqlx::query(
         r#"
-        CREATE TABLE IF NOT EXISTS webhooks (
+        CREATE TABLE IF NOT EXISTS webhook_configs (
+            webhook_id TEXT PRIMARY KEY REFERENCES webhooks(id) ON DELETE CASCADE,
+            status_code INTEGER,
+            response_body TEXT,
+            content_type TEXT,
+            forward_url TEXT
+        )
+        "#
+    ).execute(pool).await?;
+
+    add_column_if_missing(pool, "requests", "response_status", "INTEGER").await?;
+    add_column_if_missing(pool, "requests", "response_headers", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "response_body", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "response_source", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "latency_ms", "REAL").await?;
+    add_column_if_missing(pool, "requests", "config_version", "INTEGER").await?;
+
+    add_column_if_missing(pool, "requests", "signature_status", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "signature_reason", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "schema_status", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "schema_errors", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "body_encoding", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "raw_body", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "remote_addr", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "client_ip", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "http_version", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "request_uri", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "body_size", "INTEGER").await?;
+    add_column_if_missing(pool, "requests", "received_at", "TEXT").await?;
+    add_column_if_missing(pool, "requests", "pinned", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "requests", "derived_from", "TEXT REFERENCES requests(id) ON DELETE SET NULL").await?;
+
+    add_column_if_missing(pool, "webhook_configs", "response_sequence", "TEXT").await?;
+    add_column_if_missing(pool, "webhook_configs", "sequence_mode", "TEXT").await?;
+    add_column_if_missing(pool, "webhook_configs", "signing_provider", "TEXT").await?;
+    add_column_if_missing(pool, "webhook_configs", "signing_secret", "TEXT").await?;
+    add_column_if_missing(pool, "webhook_configs", "reject_invalid_signatures", "INTEGER").await?;
+    add_column_if_missing(pool, "webhook_configs", "forward_signing", "TEXT").await?;
+    add_column_if_missing(pool, "webhook_configs", "payload_schema", "TEXT").await?;
+    add_column_if_missing(pool, "webhook_configs", "reject_invalid_payloads", "INTEGER").await?;
+    add_column_if_missing(pool, "webhook_configs", "version", "INTEGER NOT NULL DEFAULT 1").await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS sequence_counters (
+            webhook_id TEXT PRIMARY KEY REFERENCES webhooks(id) ON DELETE CASCADE,
+            position INTEGER NOT NULL DEFAULT 0
+        )
+        "#
+    ).execute(pool).await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS replay_schedules (
             id TEXT PRIMARY KEY,
-            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            webhook_id TEXT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+            request_id TEXT NOT NULL REFERENCES requests(id) ON DELETE CASCADE,
+            target TEXT NOT NULL,
+            cron TEXT,
+            signing TEXT,
+            next_run_at TEXT,
+            last_run_at TEXT,
+            last_status_code INTEGER,
+            last_error TEXT,
+            runs INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL
         )
         "#
-    ).execute(&pool).await?;
+    ).execute(pool).await?;
 
     sqlx::query(
         r#"
-        CREATE TABLE IF NOT EXISTS webhook_configs (
-            webhook_id TEXT PRIMARY KEY,
+        CREATE TABLE IF NOT EXISTS deliveries (
+            id TEXT PRIMARY KEY,
+            request_id TEXT NOT NULL REFERENCES requests(id) ON DELETE CASCADE,
+            target TEXT NOT NULL,
             status_code INTEGER,
+            response_headers TEXT,
             response_body TEXT,
+            error TEXT,
+            duration_ms REAL,
+            created_at TEXT NOT NULL
+        )
+        "#
+    ).execute(pool).await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS request_files (
+            id TEXT PRIMARY KEY,
+            request_id TEXT NOT NULL REFERENCES requests(id) ON DELETE CASCADE,
+            part_index INTEGER NOT NULL,
+            field_name TEXT,
+            filename TEXT NOT NULL,
             content_type TEXT,
-            forward_url TEXT
+            size INTEGER NOT NULL,
+            created_at TEXT NOT NULL
         )
         "#
-    ).execute(&pool).await?;
+    ).execute(pool).await?;
+    // File contents used to be copied out of the body, which already holds them
+    drop_column_if_present(pool, "request_files", "data").await?;
 
-    Ok(Database { pool })
+    if let Some(legacy) = requests_legacy {
+        copy_back(pool, &legacy, "requests").await?;
+    }
+    if let Some(legacy) = configs_legacy {
+        copy_back(pool, &legacy, "webhook_configs").await?;
+    }
+
+    create_search_index(pool).await?;
+    issue_missing_tokens(pool).await?;
+
+    Ok(())
+}
+
+/// Give every webhook created before tokens existed a management token of its own.
+///
+/// Tokens are only stored hashed, so this is the one time they are printed.
+async fn issue_missing_tokens(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let tokenless: Vec<String> = sqlx::query_scalar("SELECT id FROM webhooks WHERE token_hash IS NULL")
+        .fetch_all(pool)
+        .await?;
+
+    for id in tokenless {
+        let token = generate_token();
+        sqlx::query("UPDATE webhooks SET token_hash = ? WHERE id = ? AND token_hash IS NULL")
+            .bind(hash_token(&token))
+            .bind(&id)
+            .execute(pool)
+            .await?;
+        println!("Issued management token for webhook {id}: {token}");
+    }
+    Ok(())
+}
+
+/// Full-text index over request bodies and headers, kept in step by triggers.
+///
+/// Index rows carry the ID of the request they describe. The first run indexes
+/// whatever was captured before the index existed, and an index from before the
+/// ID column, keyed on the rowid that VACUUM may renumber, is rebuilt.
+async fn create_search_index(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let existing: Option<String> = sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE name = 'requests_fts'")
+        .fetch_optional(pool)
+        .await?;
+    let keyed_on_rowid = existing.as_deref().is_some_and(|sql| !sql.contains("id UNINDEXED"));
+
+    if keyed_on_rowid {
+        for statement in [
+            "DROP TRIGGER IF EXISTS requests_fts_insert",
+            "DROP TRIGGER IF EXISTS requests_fts_delete",
+            "DROP TRIGGER IF EXISTS requests_fts_update",
+            "DROP TABLE requests_fts",
+        ] {
+            sqlx::query(statement).execute(pool).await?;
+        }
+    }
+
+    sqlx::query("CREATE VIRTUAL TABLE IF NOT EXISTS requests_fts USING fts5(id UNINDEXED, body, headers)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TRIGGER IF NOT EXISTS requests_fts_insert AFTER INSERT ON requests BEGIN
+            INSERT INTO requests_fts (id, body, headers) VALUES (new.id, new.body, new.headers);
+        END
+        "#
+    ).execute(pool).await?;
+
+    sqlx::query(
+        r#"
+        CREATE TRIGGER IF NOT EXISTS requests_fts_delete AFTER DELETE ON requests BEGIN
+            DELETE FROM requests_fts WHERE id = old.id;
+        END
+        "#
+    ).execute(pool).await?;
+
+    sqlx::query(
+        r#"
+        CREATE TRIGGER IF NOT EXISTS requests_fts_update AFTER UPDATE OF body, headers ON requests BEGIN
+            UPDATE requests_fts SET body = new.body, headers = new.headers WHERE id = old.id;
+        END
+        "#
+    ).execute(pool).await?;
+
+    if existing.is_none() || keyed_on_rowid {
+        sqlx::query("INSERT INTO requests_fts (id, body, headers) SELECT id, body, headers FROM requests")
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Rename `table` to `<table>_legacy` if it exists but has no foreign key yet.
+async fn set_aside_without_foreign_key(pool: &SqlitePool, table: &str) -> Result<Option<String>, sqlx::Error> {
+    let exists: Option<String> = sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?")
+        .bind(table)
+        .fetch_optional(pool)
+        .await?;
+    if exists.is_none() {
+        return Ok(None);
+    }
+
+    let foreign_keys: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM pragma_foreign_key_list('{table}')"))
+        .fetch_one(pool)
+        .await?;
+    if foreign_keys > 0 {
+        return Ok(None);
+    }
+
+    let legacy = format!("{table}_legacy");
+    sqlx::query(&format!("ALTER TABLE {table} RENAME TO {legacy}"))
+        .execute(pool)
+        .await?;
+    Ok(Some(legacy))
+}
+
+/// Copy rows from a set-aside legacy table into its recreated counterpart.
+///
+/// Webhook IDs that only ever appeared in captured traffic are registered
+/// (without a token) so the rows satisfy the new foreign key.
+async fn copy_back(pool: &SqlitePool, legacy: &str, table: &str) -> Result<(), sqlx::Error> {
+    let columns: Vec<String> = sqlx::query_scalar(&format!("SELECT name FROM pragma_table_info('{legacy}')"))
+        .fetch_all(pool)
+        .await?;
+    let columns = columns.join(", ");
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(&format!("INSERT OR IGNORE INTO webhooks (id) SELECT DISTINCT webhook_id FROM {legacy}"))
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(&format!("INSERT INTO {table} ({columns}) SELECT {columns} FROM {legacy}"))
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(&format!("DROP TABLE {legacy}"))
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// Add a column to an existing table unless a previous run already did.
+async fn add_column_if_missing(
+    pool: &SqlitePool,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), sqlx::Error> {
+    let columns: Vec<String> = sqlx::query_scalar(&format!("SELECT name FROM pragma_table_info('{table}')"))
+        .fetch_all(pool)
+        .await?;
+
+    if !columns.iter().any(|c| c == column) {
+        sqlx::query(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"))
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+async fn drop_column_if_present(pool: &SqlitePool, table: &str, column: &str) -> Result<(), sqlx::Error> {
+    let columns: Vec<String> = sqlx::query_scalar(&format!("SELECT name FROM pragma_table_info('{table}')"))
+        .fetch_all(pool)
+        .await?;
+
+    if columns.iter().any(|c| c == column) {
+        sqlx::query(&format!("ALTER TABLE {table} DROP COLUMN {column}"))
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
 }
 
 impl Database {
-    /// Insert a new webhook UUID.
-    pub async fn create_webhook(&self, id: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("INSERT INTO webhooks (id, created_at) VALUES (?, ?)")
+    /// Insert a new webhook UUID together with the hash of its management token.
+    pub async fn create_webhook(&self, id: &str, token_hash: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO webhooks (id, created_at, token_hash) VALUES (?, ?, ?)")
             .bind(id)
             .bind(Utc::now().to_rfc3339())
+            .bind(token_hash)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
-    /// Store a webhook request.
-    pub async fn store_request(&self, req: &StoredRequest) -> Result<(), sqlx::Error> {
+    /// Insert a webhook unless one with this ID already exists; true if this call created it.
+    pub async fn create_webhook_if_missing(&self, id: &str, token_hash: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("INSERT OR IGNORE INTO webhooks (id, created_at, token_hash) VALUES (?, ?, ?)")
+            .bind(id)
+            .bind(Utc::now().to_rfc3339())
+            .bind(token_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Whether a webhook with this ID was ever created.
+    pub async fn webhook_exists(&self, webhook_id: &str) -> Result<bool, sqlx::Error> {
+        let found: Option<i64> = sqlx::query_scalar("SELECT 1 FROM webhooks WHERE id = ?")
+            .bind(webhook_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(found.is_some())
+    }
+
+    /// List webhooks, newest first, optionally including archived ones.
+    pub async fn list_webhooks(&self, include_archived: bool) -> Result<Vec<Webhook>, sqlx::Error> {
+        sqlx::query_as::<_, Webhook>(&format!(
+            "SELECT {WEBHOOK_COLUMNS} FROM webhooks
+             WHERE ?1 OR archived_at IS NULL
+             ORDER BY created_at DESC"
+        ))
+            .bind(include_archived)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Retrieve a webhook's metadata, if it exists.
+    pub async fn get_webhook(&self, webhook_id: &str) -> Result<Option<Webhook>, sqlx::Error> {
+        sqlx::query_as::<_, Webhook>(&format!("SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE id = ?"))
+            .bind(webhook_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Overwrite a webhook's name, description, tags and expiry.
+    pub async fn update_webhook(&self, webhook: &Webhook) -> Result<(), sqlx::Error> {
         sqlx::query(
-            "INSERT INTO requests (id, webhook_id, method, headers, body, query, created_at)
-             VALUES (?, ?, ?, ?, ?, ?, ?)"
-        )
-            .bind(&req.id)
-            .bind(&req.webhook_id)
-            .bind(&req.method)
-            .bind(&req.headers)
-            .bind(&req.body)
-            .bind(&req.query)
-            .bind(&req.created_at)
+            "UPDATE webhooks SET name = ?, description = ?, tags = ?, expires_at = ?,
+                                 paused = ?, paused_status = ?, archived_at = ?
+             WHERE id = ?"
+        )
+            .bind(&webhook.name)
+            .bind(&webhook.description)
+            .bind(serde_json::to_string(&webhook.tags).unwrap_or_else(|_| "[]".to_string()))
+            .bind(&webhook.expires_at)
+            .bind(webhook.paused)
+            .bind(webhook.paused_status)
+            .bind(&webhook.archived_at)
+            .bind(&webhook.id)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
+    /// Delete a webhook; its requests and configuration go with it.
+    pub async fn delete_webhook(&self, webhook_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = ?")
+            .bind(webhook_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Hash of a webhook's management token: `None` if the webhook doesn't exist,
+    /// `Some(None)` if it has no token.
+    pub async fn get_token_hash(&self, webhook_id: &str) -> Result<Option<Option<String>>, sqlx::Error> {
+        sqlx::query_scalar("SELECT token_hash FROM webhooks WHERE id = ?")
+            .bind(webhook_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Store a webhook request.
+    pub async fn store_request(&self, req: &StoredRequest) -> Result<(), sqlx::Error> {
+        insert_request(&self.pool, req).await
+    }
+
+    /// Store a batch of requests, all or nothing.
+    pub async fn import_requests(&self, requests: &[StoredRequest]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for req in requests {
+            insert_request(&mut *tx, req).await?;
+        }
+        tx.commit().await
+    }
+
     /// Retrieve a stored request by ID.
     pub async fn get_request(&self, req_id: &str) -> Result<StoredRequest, sqlx::Error> {
-        sqlx::query_as::<_, StoredRequest>(
-            "SELECT id, webhook_id, method, headers, body, query, created_at
-             FROM requests WHERE id = ?1"
-        )
+        sqlx::query_as::<_, StoredRequest>(&format!("SELECT {REQUEST_COLUMNS} FROM requests WHERE id = ?1"))
             .bind(req_id)
             .fetch_one(&self.pool)
             .await
     }
 
+    /// Send a webhook's requests, oldest first, into `tx` one row at a time.
+    ///
+    /// An empty `ids` means every request; otherwise only those listed.
+    /// Stops early, without error, once the receiver goes away.
+    pub async fn stream_requests(
+        &self,
+        webhook_id: &str,
+        ids: &[String],
+        tx: mpsc::Sender<StoredRequest>,
+    ) -> Result<(), sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!("SELECT {REQUEST_COLUMNS} FROM requests WHERE webhook_id = "));
+        query.push_bind(webhook_id);
+        if !ids.is_empty() {
+            query.push(" AND id IN (");
+            let mut list = query.separated(", ");
+            for id in ids {
+                list.push_bind(id);
+            }
+            query.push(")");
+        }
+        query.push(" ORDER BY created_at ASC");
+
+        let mut rows = query.build_query_as::<StoredRequest>().fetch(&self.pool);
+        while let Some(req) = rows.try_next().await? {
+            if tx.send(req).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Up to `limit` of a webhook's captured requests, optionally only those in `ids`, oldest first.
+    pub async fn oldest_requests(&self, webhook_id: &str, ids: &[String], limit: u32) -> Result<Vec<StoredRequest>, sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!("SELECT {REQUEST_COLUMNS} FROM requests WHERE webhook_id = "));
+        query.push_bind(webhook_id);
+        if !ids.is_empty() {
+            query.push(" AND id IN (");
+            let mut list = query.separated(", ");
+            for id in ids {
+                list.push_bind(id);
+            }
+            query.push(")");
+        }
+        query.push(" ORDER BY created_at ASC LIMIT ").push_bind(limit);
+
+        query.build_query_as::<StoredRequest>().fetch_all(&self.pool).await
+    }
+
+    /// Find a webhook's captured requests matching a search, newest first.
+    pub async fn search_requests(
+        &self,
+        webhook_id: &str,
+        search: &SearchQuery,
+        limit: u32,
+    ) -> Result<Vec<StoredRequest>, sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!("SELECT {REQUEST_COLUMNS} FROM requests"));
+        push_search_filters(&mut query, webhook_id, search);
+        query.push(" ORDER BY created_at DESC LIMIT ").push_bind(limit);
+
+        query.build_query_as::<StoredRequest>().fetch_all(&self.pool).await
+    }
+
+    /// The oldest of a webhook's captured requests matching `search`.
+    pub async fn first_matching(&self, webhook_id: &str, search: &SearchQuery) -> Result<Option<StoredRequest>, sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!("SELECT {REQUEST_COLUMNS} FROM requests"));
+        push_search_filters(&mut query, webhook_id, search);
+        query.push(" ORDER BY created_at ASC LIMIT 1");
+
+        query.build_query_as::<StoredRequest>().fetch_optional(&self.pool).await
+    }
+
+    /// How many of a webhook's captured requests match `search`.
+    pub async fn count_requests(&self, webhook_id: &str, search: &SearchQuery) -> Result<u64, sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM requests");
+        push_search_filters(&mut query, webhook_id, search);
+        let count: i64 = query.build_query_scalar().fetch_one(&self.pool).await?;
+        Ok(count as u64)
+    }
+
+    /// Send every request matching `search` to `tx`, oldest first, until the receiver is dropped.
+    pub async fn stream_matching(
+        &self,
+        webhook_id: &str,
+        search: &SearchQuery,
+        tx: mpsc::Sender<StoredRequest>,
+    ) -> Result<(), sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!("SELECT {REQUEST_COLUMNS} FROM requests"));
+        push_search_filters(&mut query, webhook_id, search);
+        query.push(" ORDER BY created_at ASC");
+
+        let mut rows = query.build_query_as::<StoredRequest>().fetch(&self.pool);
+        while let Some(req) = rows.try_next().await? {
+            if tx.send(req).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete a single captured request.
+    pub async fn delete_request(&self, req_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM requests WHERE id = ?")
+            .bind(req_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Delete every captured request of a webhook, sparing pinned ones unless asked.
+    pub async fn clear_requests(&self, webhook_id: &str, include_pinned: bool) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM requests WHERE webhook_id = ? AND (? OR pinned = 0)")
+            .bind(webhook_id)
+            .bind(include_pinned)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Pin or unpin a captured request.
+    pub async fn set_pinned(&self, req_id: &str, pinned: bool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE requests SET pinned = ? WHERE id = ?")
+            .bind(pinned)
+            .bind(req_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record how long the handler took to answer a request, once it has been stored.
+    pub async fn set_latency_ms(&self, req_id: &str, latency_ms: f64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE requests SET latency_ms = ? WHERE id = ?")
+            .bind(latency_ms)
+            .bind(req_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete unpinned requests captured before `cutoff`, sparing those with a replay schedule.
+    pub async fn purge_requests_before(&self, cutoff: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "DELETE FROM requests WHERE pinned = 0 AND datetime(created_at) < datetime(?)
+               AND id NOT IN (SELECT request_id FROM replay_schedules)",
+        )
+            .bind(cutoff.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Record what a relay got back for a captured request.
+    pub async fn store_delivery(&self, delivery: &Delivery) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO deliveries (id, request_id, target, status_code, response_headers, response_body, error, duration_ms, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&delivery.id)
+        .bind(&delivery.request_id)
+        .bind(&delivery.target)
+        .bind(delivery.status_code)
+        .bind(&delivery.response_headers)
+        .bind(&delivery.response_body)
+        .bind(&delivery.error)
+        .bind(delivery.duration_ms)
+        .bind(&delivery.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Record a file uploaded with a captured request; its contents stay in the request body.
+    pub async fn store_request_file(&self, file: &RequestFile) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO request_files (id, request_id, part_index, field_name, filename, content_type, size, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&file.id)
+        .bind(&file.request_id)
+        .bind(file.part_index)
+        .bind(&file.field_name)
+        .bind(&file.filename)
+        .bind(&file.content_type)
+        .bind(file.size)
+        .bind(&file.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Files uploaded with a captured request, in body order, without their contents.
+    pub async fn list_request_files(&self, request_id: &str) -> Result<Vec<RequestFile>, sqlx::Error> {
+        sqlx::query_as::<_, RequestFile>(
+            "SELECT id, request_id, part_index, field_name, filename, content_type, size, created_at
+             FROM request_files WHERE request_id = ? ORDER BY part_index",
+        )
+        .bind(request_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// One file uploaded with a captured request.
+    pub async fn get_request_file(&self, request_id: &str, file_id: &str) -> Result<Option<RequestFile>, sqlx::Error> {
+        sqlx::query_as::<_, RequestFile>(
+            "SELECT id, request_id, part_index, field_name, filename, content_type, size, created_at
+             FROM request_files WHERE request_id = ? AND id = ?",
+        )
+        .bind(request_id)
+        .bind(file_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Deliveries of a captured request, newest first.
+    pub async fn list_deliveries(&self, request_id: &str) -> Result<Vec<Delivery>, sqlx::Error> {
+        sqlx::query_as::<_, Delivery>(
+            "SELECT id, request_id, target, status_code, response_headers, response_body, error, duration_ms, created_at
+             FROM deliveries WHERE request_id = ? ORDER BY created_at DESC",
+        )
+        .bind(request_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Save a new replay schedule.
+    pub async fn create_schedule(&self, schedule: &ReplaySchedule) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!("INSERT INTO replay_schedules ({SCHEDULE_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"))
+            .bind(&schedule.id)
+            .bind(&schedule.webhook_id)
+            .bind(&schedule.request_id)
+            .bind(&schedule.target)
+            .bind(&schedule.cron)
+            .bind(&schedule.signing)
+            .bind(&schedule.next_run_at)
+            .bind(&schedule.last_run_at)
+            .bind(schedule.last_status_code)
+            .bind(&schedule.last_error)
+            .bind(schedule.runs)
+            .bind(&schedule.created_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// A webhook's replay schedules, newest first.
+    pub async fn list_schedules(&self, webhook_id: &str) -> Result<Vec<ReplaySchedule>, sqlx::Error> {
+        sqlx::query_as::<_, ReplaySchedule>(&format!(
+            "SELECT {SCHEDULE_COLUMNS} FROM replay_schedules WHERE webhook_id = ? ORDER BY created_at DESC"
+        ))
+        .bind(webhook_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_schedule(&self, schedule_id: &str) -> Result<Option<ReplaySchedule>, sqlx::Error> {
+        sqlx::query_as::<_, ReplaySchedule>(&format!("SELECT {SCHEDULE_COLUMNS} FROM replay_schedules WHERE id = ?"))
+            .bind(schedule_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn delete_schedule(&self, schedule_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM replay_schedules WHERE id = ?")
+            .bind(schedule_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Schedules whose next run is at or before `now` (both in the scheduler's timestamp format).
+    pub async fn due_schedules(&self, now: &str) -> Result<Vec<ReplaySchedule>, sqlx::Error> {
+        sqlx::query_as::<_, ReplaySchedule>(&format!(
+            "SELECT {SCHEDULE_COLUMNS} FROM replay_schedules WHERE next_run_at IS NOT NULL AND next_run_at <= ? ORDER BY next_run_at"
+        ))
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Move a schedule from the run it was due for to the next one.
+    ///
+    /// Returns false if the schedule is gone or another sweep already claimed that run.
+    pub async fn advance_schedule(&self, schedule_id: &str, due: &str, next: Option<&str>) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE replay_schedules SET next_run_at = ? WHERE id = ? AND next_run_at = ?")
+            .bind(next)
+            .bind(schedule_id)
+            .bind(due)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record how a scheduled replay went.
+    pub async fn record_schedule_run(
+        &self,
+        schedule_id: &str,
+        ran_at: &str,
+        status_code: Option<u16>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE replay_schedules SET last_run_at = ?, last_status_code = ?, last_error = ?, runs = runs + 1 WHERE id = ?",
+        )
+        .bind(ran_at)
+        .bind(status_code)
+        .bind(error)
+        .bind(schedule_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
     /// Save or update a custom response configuration.
     pub async fn set_response_config(&self, config: &WebhookConfig) -> Result<(), sqlx::Error> {
         sqlx::query(
-            "INSERT INTO webhook_configs (webhook_id, status_code, response_body, content_type, forward_url)
-             VALUES (?, ?, ?, ?, ?)
+            "INSERT INTO webhook_configs (webhook_id, status_code, response_body, content_type, forward_url, response_sequence, sequence_mode,
+                                          signing_provider, signing_secret, reject_invalid_signatures, forward_signing,
+                                          payload_schema, reject_invalid_payloads)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
              ON CONFLICT(webhook_id) DO UPDATE SET
                  status_code = excluded.status_code,
                  response_body = excluded.response_body,
                  content_type = excluded.content_type,
-                 forward_url = excluded.forward_url"
+                 forward_url = excluded.forward_url,
+                 response_sequence = excluded.response_sequence,
+                 sequence_mode = excluded.sequence_mode,
+                 signing_provider = excluded.signing_provider,
+                 signing_secret = excluded.signing_secret,
+                 reject_invalid_signatures = excluded.reject_invalid_signatures,
+                 forward_signing = excluded.forward_signing,
+                 payload_schema = excluded.payload_schema,
+                 reject_invalid_payloads = excluded.reject_invalid_payloads,
+                 version = webhook_configs.version + 1"
         )
             .bind(&config.webhook_id)
             .bind(config.status_code)
             .bind(&config.response_body)
             .bind(&config.content_type)
             .bind(&config.forward_url)
+            .bind(&config.response_sequence)
+            .bind(&config.sequence_mode)
+            .bind(&config.signing_provider)
+            .bind(&config.signing_secret)
+            .bind(config.reject_invalid_signatures)
+            .bind(&config.forward_signing)
+            .bind(&config.payload_schema)
+            .bind(config.reject_invalid_payloads)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Atomically bump a webhook's sequence counter and return the new call number (1-based).
+    pub async fn next_sequence_call(&self, webhook_id: &str) -> Result<u64, sqlx::Error> {
+        let position: i64 = sqlx::query_scalar(
+            "INSERT INTO sequence_counters (webhook_id, position) VALUES (?, 1)
+             ON CONFLICT(webhook_id) DO UPDATE SET position = position + 1
+             RETURNING position"
+        )
+            .bind(webhook_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(position.max(0) as u64)
+    }
+
+    /// Rewind a webhook's response sequence to the first step.
+    pub async fn reset_sequence(&self, webhook_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM sequence_counters WHERE webhook_id = ?")
+            .bind(webhook_id)
             .execute(&self.pool)
             .await?;
         Ok(())
@@ -130,7 +869,9 @@ impl Database {
     /// Retrieve a webhook's custom response configuration.
     pub async fn get_response_config(&self, webhook_id: &str) -> Result<WebhookConfig, sqlx::Error> {
         let config = sqlx::query_as::<_, WebhookConfig>(
-            "SELECT webhook_id, status_code, response_body, content_type, forward_url
+            "SELECT webhook_id, status_code, response_body, content_type, forward_url, response_sequence, sequence_mode,
+                    signing_provider, signing_secret, reject_invalid_signatures, forward_signing,
+                    payload_schema, reject_invalid_payloads, version
              FROM webhook_configs WHERE webhook_id = ?"
         )
             .bind(webhook_id)
@@ -141,6 +882,96 @@ impl Database {
     }
 }
 
+async fn insert_request<'e, E>(executor: E, req: &StoredRequest) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = Sqlite>,
+{
+    sqlx::query(
+        "INSERT INTO requests (id, webhook_id, method, headers, body, query, created_at,
+                               response_status, response_headers, response_body, response_source, config_version, latency_ms,
+                               signature_status, signature_reason, schema_status, schema_errors, body_encoding, raw_body,
+                               remote_addr, client_ip, http_version, request_uri, body_size, received_at,
+                               pinned, derived_from)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+        .bind(&req.id)
+        .bind(&req.webhook_id)
+        .bind(&req.method)
+        .bind(&req.headers)
+        .bind(&req.body)
+        .bind(&req.query)
+        .bind(&req.created_at)
+        .bind(req.response_status)
+        .bind(&req.response_headers)
+        .bind(&req.response_body)
+        .bind(&req.response_source)
+        .bind(req.config_version)
+        .bind(req.latency_ms)
+        .bind(&req.signature_status)
+        .bind(&req.signature_reason)
+        .bind(&req.schema_status)
+        .bind(&req.schema_errors)
+        .bind(&req.body_encoding)
+        .bind(&req.raw_body)
+        .bind(&req.remote_addr)
+        .bind(&req.client_ip)
+        .bind(&req.http_version)
+        .bind(&req.request_uri)
+        .bind(req.body_size)
+        .bind(&req.received_at)
+        .bind(req.pinned)
+        .bind(&req.derived_from)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// `WHERE` clause selecting a webhook's requests that match `search`; times compare to the millisecond
+fn push_search_filters(query: &mut QueryBuilder<'_, Sqlite>, webhook_id: &str, search: &SearchQuery) {
+    query.push(" WHERE webhook_id = ").push_bind(webhook_id.to_string());
+
+    if let Some(expression) = search.fts_expression() {
+        query.push(" AND id IN (SELECT id FROM requests_fts WHERE requests_fts MATCH ");
+        query.push_bind(expression);
+        query.push(")");
+    }
+    if let Some(method) = &search.method {
+        query.push(" AND method = ").push_bind(method.clone());
+    }
+    for (name, value) in &search.headers {
+        // Header names are lowercase when captured; quote them for the JSON path
+        query.push(" AND CASE WHEN json_valid(headers) THEN json_extract(headers, ");
+        query.push_bind(format!("$.\"{name}\""));
+        query.push(") END = ").push_bind(value.clone());
+    }
+    for (path, value) in &search.json {
+        // Compare scalars by their JSON spelling so `12345` matches both 12345 and "12345"
+        query.push(" AND CASE WHEN json_valid(body) THEN CASE json_type(body, ");
+        query.push_bind(path.clone());
+        query.push(") WHEN 'true' THEN 'true' WHEN 'false' THEN 'false' WHEN 'null' THEN 'null' ELSE CAST(json_extract(body, ");
+        query.push_bind(path.clone());
+        query.push(") AS TEXT) END END = ").push_bind(value.clone());
+    }
+    if let Some(status) = search.status {
+        let (low, high) = status.range();
+        query.push(" AND response_status BETWEEN ").push_bind(low);
+        query.push(" AND ").push_bind(high);
+    }
+    if let Some(schema) = &search.schema {
+        query.push(" AND schema_status = ").push_bind(schema.clone());
+    }
+    if let Some(delivered) = search.delivered {
+        query.push(if delivered { " AND id IN" } else { " AND id NOT IN" });
+        query.push(" (SELECT request_id FROM deliveries)");
+    }
+    if let Some(after) = search.after {
+        query.push(" AND julianday(created_at) >= julianday(").push_bind(after.to_rfc3339()).push(")");
+    }
+    if let Some(before) = search.before {
+        query.push(" AND julianday(created_at) < julianday(").push_bind(before.to_rfc3339()).push(")");
+    }
+}
+
 impl Default for WebhookConfig {
     fn default() -> Self {
         Self {
@@ -149,6 +980,15 @@ impl Default for WebhookConfig {
             response_body: Some("OK".to_string()),
             content_type: Some("text/plain".to_string()),
             forward_url: None,
+            response_sequence: None,
+            sequence_mode: None,
+            signing_provider: None,
+            signing_secret: None,
+            reject_invalid_signatures: None,
+            forward_signing: None,
+            payload_schema: None,
+            reject_invalid_payloads: None,
+            version: None,
         }
     }
-}
\ No newline at end of file
+}
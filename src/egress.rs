@@ -0,0 +1,333 @@
+use axum::{http::StatusCode, Json};
+use ipnet::IpNet;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    Url,
+};
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+
+/// Ranges no target may reach unless allowed explicitly: cloud metadata, link-local,
+/// unspecified, multicast and broadcast addresses
+const ALWAYS_BLOCKED: &[&str] = &[
+    "0.0.0.0/8",
+    "169.254.0.0/16",
+    "100.100.100.200/32",
+    "224.0.0.0/4",
+    "255.255.255.255/32",
+    "::/128",
+    "fe80::/10",
+    "fd00:ec2::254/128",
+    "ff00::/8",
+];
+
+/// Loopback and private ranges, blocked unless `EGRESS_BLOCK_PRIVATE=false`
+const PRIVATE: &[&str] = &[
+    "127.0.0.0/8",
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "100.64.0.0/10",
+    "::1/128",
+    "fc00::/7",
+];
+
+/// Why an outbound request was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EgressError(pub String);
+
+impl fmt::Display for EgressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Blocked by egress policy: {}", self.0)
+    }
+}
+
+impl std::error::Error for EgressError {}
+
+/// Where replayed and forwarded requests may go
+#[derive(Debug, Clone)]
+pub struct EgressPolicy {
+    /// `EGRESS_SCHEMES`: `http,https` by default
+    pub schemes: Vec<String>,
+    /// `EGRESS_ALLOWED_HOSTS`: when set, only these hosts; `*.example.com` matches subdomains
+    pub allowed_hosts: Vec<String>,
+    /// `EGRESS_BLOCKED_HOSTS`: hosts never reached, same patterns
+    pub blocked_hosts: Vec<String>,
+    /// `EGRESS_ALLOWED_CIDRS`: exceptions to the blocked ranges, such as `127.0.0.1/32`
+    /// for a consumer on the same machine
+    pub allowed_cidrs: Vec<IpNet>,
+    /// `EGRESS_BLOCKED_CIDRS`: blocked on top of the built-in ranges, even if also allowed
+    pub blocked_cidrs: Vec<IpNet>,
+    /// `EGRESS_BLOCK_PRIVATE`: also block loopback and private networks (on by default;
+    /// allow single ranges with `allowed_cidrs` rather than turning this off)
+    pub block_private: bool,
+    /// Refuse every address outside the loopback and private ranges, on top of the
+    /// rest of the policy; set for the client that skips certificate checks
+    pub local_only: bool,
+}
+
+impl Default for EgressPolicy {
+    fn default() -> Self {
+        Self {
+            schemes: vec!["http".into(), "https".into()],
+            allowed_hosts: Vec::new(),
+            blocked_hosts: Vec::new(),
+            allowed_cidrs: Vec::new(),
+            blocked_cidrs: Vec::new(),
+            block_private: true,
+            local_only: false,
+        }
+    }
+}
+
+impl EgressPolicy {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut policy = Self::default();
+
+        if let Ok(schemes) = std::env::var("EGRESS_SCHEMES") {
+            policy.schemes = list(&schemes).map(|s| s.to_ascii_lowercase()).collect();
+        }
+        if let Ok(hosts) = std::env::var("EGRESS_ALLOWED_HOSTS") {
+            policy.allowed_hosts = list(&hosts).map(|h| normalize_host(&h)).collect();
+        }
+        if let Ok(hosts) = std::env::var("EGRESS_BLOCKED_HOSTS") {
+            policy.blocked_hosts = list(&hosts).map(|h| normalize_host(&h)).collect();
+        }
+        if let Ok(cidrs) = std::env::var("EGRESS_ALLOWED_CIDRS") {
+            policy.allowed_cidrs = parse_cidrs(&cidrs)?;
+        }
+        if let Ok(cidrs) = std::env::var("EGRESS_BLOCKED_CIDRS") {
+            policy.blocked_cidrs = parse_cidrs(&cidrs)?;
+        }
+        if let Ok(block) = std::env::var("EGRESS_BLOCK_PRIVATE") {
+            policy.block_private = block.parse()?;
+        }
+
+        Ok(policy)
+    }
+
+    /// Check everything about `url` that doesn't need DNS: scheme, host lists and IP literals.
+    ///
+    /// Hostnames are checked again once resolved, by the client from [`EgressPolicy::client_builder`].
+    pub fn check_url(&self, url: &Url) -> Result<(), EgressError> {
+        if !self.schemes.iter().any(|scheme| scheme == url.scheme()) {
+            return Err(EgressError(format!("scheme {} is not allowed", url.scheme())));
+        }
+
+        let host = match url.host_str() {
+            Some(host) => normalize_host(host.trim_start_matches('[').trim_end_matches(']')),
+            None => return Err(EgressError("the URL has no host".into())),
+        };
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return self.check_host(&host).and(self.check_ip(ip));
+        }
+        self.check_host(&host)?;
+
+        // Refuse loopback names up front rather than at connect time
+        if host == "localhost" || host.ends_with(".localhost") {
+            self.check_ip(IpAddr::from([127, 0, 0, 1]))?;
+        }
+        Ok(())
+    }
+
+    /// [`EgressPolicy::check_url`] for a URL that hasn't been parsed yet
+    pub fn check_target(&self, target: &str) -> Result<(), String> {
+        let url = Url::parse(target).map_err(|e| format!("Invalid target URL: {e}"))?;
+        self.check_url(&url).map_err(|e| e.to_string())
+    }
+
+    fn check_host(&self, host: &str) -> Result<(), EgressError> {
+        if self.blocked_hosts.iter().any(|pattern| host_matches(pattern, host)) {
+            return Err(EgressError(format!("host {host} is blocked")));
+        }
+        if !self.allowed_hosts.is_empty() && !self.allowed_hosts.iter().any(|pattern| host_matches(pattern, host)) {
+            return Err(EgressError(format!("host {host} is not in the allowed hosts")));
+        }
+        Ok(())
+    }
+
+    /// Check one address a target resolved to
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), EgressError> {
+        // `::ffff:169.254.169.254` is the same machine as `169.254.169.254`
+        let ip = match ip {
+            IpAddr::V6(v6) => embedded_ipv4(v6).map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+
+        if self.local_only && !is_local(ip) {
+            return Err(EgressError(format!("{ip} is not a loopback or private address")));
+        }
+        if self.blocked_cidrs.iter().any(|net| net.contains(&ip)) {
+            return Err(EgressError(format!("{ip} is in a blocked range")));
+        }
+        if self.allowed_cidrs.iter().any(|net| net.contains(&ip)) {
+            return Ok(());
+        }
+
+        let builtin = ALWAYS_BLOCKED
+            .iter()
+            .chain(if self.block_private { PRIVATE } else { &[] })
+            .filter_map(|net| net.parse::<IpNet>().ok());
+        for net in builtin {
+            if net.contains(&ip) {
+                return Err(EgressError(format!("{ip} is in the reserved range {net}")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuse a target before any work starts: 400 if it isn't a URL, 403 if the policy blocks it
+    pub fn screen_target(&self, target: &str) -> Result<(), (StatusCode, Json<Value>)> {
+        let url = Url::parse(target)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid target URL: {e}")}))))?;
+        self.check_url(&url)
+            .map_err(|e| (StatusCode::FORBIDDEN, Json(json!({"error": e.to_string()}))))
+    }
+
+    /// This policy, restricted to loopback and private addresses
+    pub fn local_only(&self) -> Self {
+        Self { local_only: true, ..self.clone() }
+    }
+
+    /// A client builder that enforces this policy on every connection and on up to
+    /// `max_redirects` redirects (with none, the redirect response itself is returned).
+    ///
+    /// Only an explicitly configured proxy is used, not the environment's: a proxy
+    /// resolves targets itself, so resolved addresses are then out of the policy's reach.
+    pub fn client_builder(&self, max_redirects: usize) -> reqwest::ClientBuilder {
+        let policy = Arc::new(self.clone());
+        let redirects = policy.clone();
+        reqwest::Client::builder()
+            .no_proxy()
+            .dns_resolver(Arc::new(GuardedResolver(policy)))
+            .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                // `previous` starts with the original URL
+                if max_redirects == 0 {
+                    attempt.stop()
+                } else if attempt.previous().len() > max_redirects {
+                    attempt.error("too many redirects")
+                } else if let Err(e) = redirects.check_url(attempt.url()) {
+                    attempt.error(e)
+                } else {
+                    attempt.follow()
+                }
+            }))
+    }
+}
+
+/// Resolves hostnames and keeps only the addresses the policy allows, so the
+/// address that gets checked is the one connected to (no DNS rebinding)
+struct GuardedResolver(Arc<EgressPolicy>);
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.0.clone();
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+
+            let mut refused = None;
+            let allowed: Vec<SocketAddr> = resolved
+                .into_iter()
+                .filter(|addr| match policy.check_ip(addr.ip()) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        refused.get_or_insert(e);
+                        false
+                    }
+                })
+                .collect();
+
+            match (allowed.is_empty(), refused) {
+                (true, Some(e)) => Err(Box::new(EgressError(format!("{host} resolves to {}", e.0))) as _),
+                _ => Ok(Box::new(allowed.into_iter()) as Addrs),
+            }
+        })
+    }
+}
+
+/// Whether `ip` is a loopback or private address
+pub fn is_local(ip: IpAddr) -> bool {
+    let ip = match ip {
+        IpAddr::V6(v6) => embedded_ipv4(v6).map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    };
+    PRIVATE
+        .iter()
+        .filter_map(|net| net.parse::<IpNet>().ok())
+        .any(|net| net.contains(&ip))
+}
+
+/// Whether every address `url`'s host resolves to is loopback or private
+pub async fn is_local_target(url: &Url) -> bool {
+    let Some(host) = url.host_str().map(|host| host.trim_start_matches('[').trim_end_matches(']')) else {
+        return false;
+    };
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return is_local(ip);
+    }
+    let port = url.port_or_known_default().unwrap_or(0);
+    match tokio::net::lookup_host((host, port)).await {
+        Ok(addrs) => {
+            let addrs: Vec<SocketAddr> = addrs.collect();
+            !addrs.is_empty() && addrs.iter().all(|addr| is_local(addr.ip()))
+        }
+        Err(_) => false,
+    }
+}
+
+/// Describe a failed outbound request with its causes, surfacing a policy refusal buried among them
+pub fn describe_error(err: &reqwest::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        if let Some(refused) = cause.downcast_ref::<EgressError>() {
+            return refused.to_string();
+        }
+        message = format!("{message}: {cause}");
+        source = cause.source();
+    }
+    message
+}
+
+fn list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(String::from)
+}
+
+pub(crate) fn parse_cidrs(value: &str) -> anyhow::Result<Vec<IpNet>> {
+    list(value)
+        .map(|item| {
+            // A bare address is a range of one
+            item.parse::<IpNet>()
+                .or_else(|_| item.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| anyhow::anyhow!("Invalid CIDR: {item}"))
+        })
+        .collect()
+}
+
+fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+/// The IPv4 address inside an IPv4-mapped (`::ffff:a.b.c.d`), NAT64 (`64:ff9b::a.b.c.d`)
+/// or IPv4-compatible (`::a.b.c.d`) address, each of which reaches that IPv4 host
+fn embedded_ipv4(v6: Ipv6Addr) -> Option<Ipv4Addr> {
+    let [a, b, c, d] = v6.octets()[12..] else { return None };
+    match v6.segments()[..6] {
+        [0, 0, 0, 0, 0, 0xffff] | [0x64, 0xff9b, 0, 0, 0, 0] => Some(Ipv4Addr::new(a, b, c, d)),
+        // `::` and `::1` are IPv6's own unspecified and loopback addresses
+        [0, 0, 0, 0, 0, 0] if !v6.is_unspecified() && !v6.is_loopback() => Some(Ipv4Addr::new(a, b, c, d)),
+        _ => None,
+    }
+}
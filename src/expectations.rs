@@ -0,0 +1,528 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::Instant;
+
+use crate::{
+    auth::Credentials,
+    models::{StoredRequest, WebhookEvent},
+    search::SearchQuery,
+    utils::parse_timestamp,
+    AppState,
+};
+
+/// How long a wait blocks when no timeout is given
+const DEFAULT_WAIT_SECS: f64 = 30.0;
+/// Longest a wait may block
+const MAX_WAIT_SECS: f64 = 300.0;
+/// Most matching requests listed per expectation; the count covers them all
+const MAX_LISTED_MATCHES: usize = 1000;
+/// Expectation sets kept at once; the oldest are forgotten
+const MAX_EXPECTATION_SETS: usize = 100;
+
+#[derive(Deserialize)]
+pub struct WaitQuery {
+    /// Search query the request must match, see [`SearchQuery`]
+    #[serde(rename = "match", default)]
+    matches: String,
+    /// Seconds to wait, 30 by default
+    timeout: Option<f64>,
+    /// RFC 3339 time to look back to; by default only requests captured after the call count
+    since: Option<String>,
+}
+
+/// Answer with the first request matching `match` captured since the call (or since
+/// `since`) as soon as the webhook has one, or 408 once `timeout` runs out.
+pub async fn wait_for_request(
+    Path(webhook_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Query(query): Query<WaitQuery>,
+) -> Response {
+    if let Err(err) = state.authorize(&webhook_id, &credentials).await {
+        return err.into_response();
+    }
+
+    let mut search: SearchQuery = match query.matches.parse() {
+        Ok(search) => search,
+        Err(err) => return bad_request(err.to_string()),
+    };
+    // A request left over from an earlier run must not satisfy this wait
+    let since = match query.since.as_deref().map(parse_timestamp) {
+        Some(Some(since)) => since,
+        Some(None) => return bad_request("since must be an RFC 3339 timestamp".to_string()),
+        None => Utc::now(),
+    };
+    search.after = search.after.max(Some(since));
+    let timeout = match wait_timeout(query.timeout.unwrap_or(DEFAULT_WAIT_SECS)) {
+        Ok(timeout) => timeout,
+        Err(message) => return bad_request(message),
+    };
+    let deadline = Instant::now() + timeout;
+
+    // Subscribed before looking, so a request stored in between still wakes us
+    let mut events = state.tx.subscribe();
+    loop {
+        match state.db.first_matching(&webhook_id, &search).await {
+            Ok(Some(req)) => return Json(req).into_response(),
+            Ok(None) => {}
+            Err(err) => return db_error(err),
+        }
+        if !next_event(&mut events, &webhook_id, deadline).await {
+            return (
+                StatusCode::REQUEST_TIMEOUT,
+                Json(json!({"error": format!("No matching request within {}s", timeout.as_secs_f64())})),
+            )
+                .into_response();
+        }
+    }
+}
+
+/// What a webhook should receive after an expectation set is registered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Expectation {
+    /// Search query the requests must match, see [`SearchQuery`]
+    #[serde(rename = "match", default)]
+    pub matches: String,
+    /// JSON the body must contain: every field given, nested the same way, with the same value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    /// Exactly this many matching requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    /// At least this many; with no count, min or max, at least one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<usize>,
+}
+
+impl Expectation {
+    /// Describe what went wrong with `matched` requests, if anything
+    fn count_problem(&self, matched: usize) -> Option<String> {
+        let expected = match (self.count, self.min, self.max) {
+            (Some(count), _, _) if matched != count => format!("exactly {}", requests(count)),
+            (Some(_), _, _) => return None,
+            (None, None, None) if matched == 0 => "at least 1 request".to_string(),
+            (None, Some(min), _) if matched < min => format!("at least {}", requests(min)),
+            (None, _, Some(max)) if matched > max => format!("at most {}", requests(max)),
+            _ => return None,
+        };
+        Some(format!("- expected {expected}\n+ got {}", requests(matched)))
+    }
+
+    fn label(&self) -> String {
+        let mut label = if self.matches.trim().is_empty() { "any request".to_string() } else { self.matches.clone() };
+        if let Some(body) = &self.body {
+            let _ = write!(label, " with body {body}");
+        }
+        label
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExpectationsPayload {
+    expect: Vec<Expectation>,
+    /// Each expectation's first match must arrive after the previous one's
+    #[serde(default)]
+    ordered: bool,
+}
+
+/// Expectations registered up front and verified once the code under test has run.
+/// Only requests captured after `created_at` count.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpectationSet {
+    pub id: String,
+    pub webhook_id: String,
+    pub ordered: bool,
+    pub expect: Vec<Expectation>,
+    pub created_at: String,
+}
+
+/// Outcome of one expectation
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpectationResult {
+    pub passed: bool,
+    /// How many requests matched
+    pub count: usize,
+    /// IDs of the matching requests, oldest first (at most 1000)
+    pub matched: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+/// Outcome of verifying a set, with a diff-style report of what differed
+#[derive(Debug, Clone, Serialize)]
+pub struct Verification {
+    pub id: String,
+    pub passed: bool,
+    pub results: Vec<ExpectationResult>,
+    pub report: String,
+}
+
+/// Expectation sets of this process
+#[derive(Clone, Default)]
+pub struct Expectations {
+    sets: Arc<Mutex<HashMap<String, ExpectationSet>>>,
+}
+
+impl Expectations {
+    fn get(&self, id: &str) -> Option<ExpectationSet> {
+        self.sets.lock().unwrap().get(id).cloned()
+    }
+
+    fn insert(&self, set: ExpectationSet) {
+        let mut sets = self.sets.lock().unwrap();
+        sets.insert(set.id.clone(), set);
+
+        if sets.len() > MAX_EXPECTATION_SETS {
+            let mut ages: Vec<(String, String)> =
+                sets.values().map(|set| (set.created_at.clone(), set.id.clone())).collect();
+            ages.sort();
+            for (_, id) in &ages[..ages.len() - MAX_EXPECTATION_SETS] {
+                sets.remove(id);
+            }
+        }
+    }
+
+    fn remove(&self, id: &str) -> bool {
+        self.sets.lock().unwrap().remove(id).is_some()
+    }
+}
+
+/// Register expectations for a webhook, to verify later
+pub async fn create_expectations(
+    Path(webhook_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Json(payload): Json<ExpectationsPayload>,
+) -> Response {
+    if let Err(err) = state.authorize(&webhook_id, &credentials).await {
+        return err.into_response();
+    }
+
+    if payload.expect.is_empty() {
+        return bad_request("Give at least one expectation".to_string());
+    }
+    for (index, expectation) in payload.expect.iter().enumerate() {
+        let number = index + 1;
+        if let Err(err) = expectation.matches.parse::<SearchQuery>() {
+            return bad_request(format!("Expectation {number}: {err}"));
+        }
+        if expectation.count.is_some() && (expectation.min.is_some() || expectation.max.is_some()) {
+            return bad_request(format!("Expectation {number}: give either count or min/max"));
+        }
+        if let (Some(min), Some(max)) = (expectation.min, expectation.max) {
+            if min > max {
+                return bad_request(format!("Expectation {number}: min is greater than max"));
+            }
+        }
+    }
+
+    let set = ExpectationSet {
+        id: uuid::Uuid::new_v4().to_string(),
+        webhook_id,
+        ordered: payload.ordered,
+        expect: payload.expect,
+        created_at: Utc::now().to_rfc3339(),
+    };
+    state.expectations.insert(set.clone());
+    (StatusCode::CREATED, Json(set)).into_response()
+}
+
+pub async fn get_expectations(
+    Path(set_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    match load_set(&state, &set_id, &credentials).await {
+        Ok(set) => Json(set).into_response(),
+        Err(response) => response,
+    }
+}
+
+pub async fn delete_expectations(
+    Path(set_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    if let Err(response) = load_set(&state, &set_id, &credentials).await {
+        return response;
+    }
+    state.expectations.remove(&set_id);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[derive(Deserialize)]
+pub struct VerifyQuery {
+    /// Seconds to keep checking until the expectations are met; checked once by default
+    timeout: Option<f64>,
+}
+
+/// Check a set against the requests captured since it was registered: 200 when every
+/// expectation is met, 417 with the report otherwise
+pub async fn verify_expectations(
+    Path(set_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Query(query): Query<VerifyQuery>,
+) -> Response {
+    let set = match load_set(&state, &set_id, &credentials).await {
+        Ok(set) => set,
+        Err(response) => return response,
+    };
+    let timeout = match wait_timeout(query.timeout.unwrap_or_default()) {
+        Ok(timeout) => timeout,
+        Err(message) => return bad_request(message),
+    };
+    let deadline = Instant::now() + timeout;
+
+    let mut events = state.tx.subscribe();
+    loop {
+        let verification = match verify(&state, &set).await {
+            Ok(verification) => verification,
+            Err(err) => return db_error(err),
+        };
+        if verification.passed {
+            return Json(verification).into_response();
+        }
+        if !next_event(&mut events, &set.webhook_id, deadline).await {
+            return (StatusCode::EXPECTATION_FAILED, Json(verification)).into_response();
+        }
+    }
+}
+
+async fn load_set(state: &AppState, set_id: &str, credentials: &Credentials) -> Result<ExpectationSet, Response> {
+    let Some(set) = state.expectations.get(set_id) else {
+        return Err((StatusCode::NOT_FOUND, Json(json!({"error": "Expectations not found"}))).into_response());
+    };
+    state
+        .authorize(&set.webhook_id, credentials)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    Ok(set)
+}
+
+async fn verify(state: &AppState, set: &ExpectationSet) -> Result<Verification, sqlx::Error> {
+    let registered = parse_timestamp(&set.created_at).unwrap_or_default();
+    let mut results = Vec::new();
+    // Per expectation: the requests matching its query, and those also matching its body
+    let mut candidates = Vec::new();
+
+    for expectation in &set.expect {
+        let mut search: SearchQuery = expectation.matches.parse().unwrap_or_default();
+        search.after = search.after.max(Some(registered));
+        let matches = find_matches(state, &set.webhook_id, &search, expectation.body.as_ref()).await?;
+
+        let mut problems = Vec::new();
+        if let Some(problem) = expectation.count_problem(matches.count) {
+            problems.push(problem);
+            if matches.count == 0 {
+                if let Some(diff) = expectation.body.as_ref().and_then(|body| closest_body_diff(&matches.misses, body)) {
+                    problems.push(diff);
+                }
+            }
+        }
+
+        results.push(ExpectationResult {
+            passed: problems.is_empty(),
+            count: matches.count,
+            matched: matches.listed.iter().map(|(_, id)| id.clone()).collect(),
+            problems,
+        });
+        candidates.push(matches.listed.into_iter().next());
+    }
+
+    if set.ordered {
+        let mut previous: Option<(usize, DateTime<Utc>)> = None;
+        for (index, first) in candidates.iter().enumerate() {
+            let Some((at, id)) = first else { continue };
+            if let Some((before, previous_at)) = previous {
+                if *at < previous_at {
+                    results[index].passed = false;
+                    results[index].problems.push(format!(
+                        "- first match after that of [{}] ({})\n+ first match {id} arrived earlier ({})",
+                        before + 1,
+                        previous_at.to_rfc3339(),
+                        at.to_rfc3339(),
+                    ));
+                }
+            }
+            previous = Some((index, *at));
+        }
+    }
+
+    let passed = results.iter().all(|result| result.passed);
+    let report = report(set, &results);
+    Ok(Verification { id: set.id.clone(), passed, results, report })
+}
+
+/// Requests matching one expectation
+struct Matches {
+    count: usize,
+    /// The oldest matches, with when they arrived
+    listed: Vec<(DateTime<Utc>, String)>,
+    /// The oldest requests that matched the query but not the body
+    misses: Vec<(DateTime<Utc>, StoredRequest)>,
+}
+
+/// Count the requests matching `search` and, if given, containing `body`.
+///
+/// Query matches are counted by the database. Body containment isn't something SQL
+/// can check, so with a body every query match is read, oldest first.
+async fn find_matches(
+    state: &AppState,
+    webhook_id: &str,
+    search: &SearchQuery,
+    body: Option<&Value>,
+) -> Result<Matches, sqlx::Error> {
+    let (rows, mut row_rx) = mpsc::channel(64);
+    let producer = state.db.stream_matching(webhook_id, search, rows);
+
+    let reader = async move {
+        let mut matches = Matches { count: 0, listed: Vec::new(), misses: Vec::new() };
+        while let Some(req) = row_rx.recv().await {
+            let Some(at) = parse_timestamp(&req.created_at) else { continue };
+            if body.is_none_or(|body| body_contains(&req.body, body)) {
+                matches.count += 1;
+                if matches.listed.len() < MAX_LISTED_MATCHES {
+                    matches.listed.push((at, req.id));
+                }
+            } else if matches.misses.len() < MAX_LISTED_MATCHES {
+                matches.misses.push((at, req));
+            }
+            // Without a body to check, the rest only add to the count
+            if body.is_none() && matches.listed.len() == MAX_LISTED_MATCHES {
+                break;
+            }
+        }
+        matches
+    };
+
+    let (result, mut matches) = tokio::join!(producer, reader);
+    result?;
+    if body.is_none() {
+        matches.count = state.db.count_requests(webhook_id, search).await? as usize;
+    }
+    Ok(matches)
+}
+
+fn report(set: &ExpectationSet, results: &[ExpectationResult]) -> String {
+    let failed = results.iter().filter(|result| !result.passed).count();
+    let mut report = format!(
+        "Expectations {} for webhook {}: {} of {} met\n",
+        set.id,
+        set.webhook_id,
+        results.len() - failed,
+        results.len(),
+    );
+    for (index, (expectation, result)) in set.expect.iter().zip(results).enumerate() {
+        let verdict = if result.passed { "ok  " } else { "FAIL" };
+        let _ = writeln!(report, "\n{verdict} [{}] {} ({} matched)", index + 1, expectation.label(), result.count);
+        for problem in &result.problems {
+            for line in problem.lines() {
+                let _ = writeln!(report, "       {line}");
+            }
+        }
+    }
+    report
+}
+
+fn requests(count: usize) -> String {
+    if count == 1 {
+        "1 request".to_string()
+    } else {
+        format!("{count} requests")
+    }
+}
+
+/// Whether `body` parses as JSON containing everything in `expected`
+fn body_contains(body: &str, expected: &Value) -> bool {
+    serde_json::from_str::<Value>(body).is_ok_and(|actual| contains(&actual, expected))
+}
+
+fn contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => expected
+            .iter()
+            .all(|(key, value)| actual.get(key).is_some_and(|actual| contains(actual, value))),
+        _ => actual == expected,
+    }
+}
+
+/// Where `actual` departs from `expected`, as (path, expected, actual) triples
+fn differences<'a>(actual: Option<&'a Value>, expected: &'a Value, path: String, out: &mut Vec<(String, &'a Value, Option<&'a Value>)>) {
+    match (actual, expected) {
+        (Some(Value::Object(actual)), Value::Object(expected)) => {
+            for (key, value) in expected {
+                let path = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+                differences(actual.get(key), value, path, out);
+            }
+        }
+        (Some(actual), expected) if actual == expected => {}
+        (actual, expected) => out.push((if path.is_empty() { "$".to_string() } else { path }, expected, actual)),
+    }
+}
+
+/// The body diff of the request closest to matching, among those that matched the query
+fn closest_body_diff(found: &[(DateTime<Utc>, StoredRequest)], expected: &Value) -> Option<String> {
+    let parsed: Vec<(&StoredRequest, Value)> = found
+        .iter()
+        .map(|(_, req)| (req, serde_json::from_str(&req.body).unwrap_or(Value::Null)))
+        .collect();
+
+    let (req, diffs) = parsed
+        .iter()
+        .map(|(req, body)| {
+            let mut diffs = Vec::new();
+            differences(Some(body), expected, String::new(), &mut diffs);
+            (req, diffs)
+        })
+        .min_by_key(|(_, diffs)| diffs.len())?;
+
+    let mut diff = format!("closest request {} differs in its body:", req.id);
+    for (path, expected, actual) in diffs {
+        let actual = actual.map(Value::to_string).unwrap_or_else(|| "(missing)".to_string());
+        let _ = write!(diff, "\n-   {path}: {expected}\n+   {path}: {actual}");
+    }
+    Some(diff)
+}
+
+/// Wait for the next request to `webhook_id`; false once `deadline` passes
+async fn next_event(events: &mut broadcast::Receiver<WebhookEvent>, webhook_id: &str, deadline: Instant) -> bool {
+    loop {
+        match tokio::time::timeout_at(deadline, events.recv()).await {
+            Ok(Ok(event)) if event.webhook_id == webhook_id => return true,
+            Ok(Ok(_)) => continue,
+            // Missed some; looking again covers whatever they were
+            Ok(Err(broadcast::error::RecvError::Lagged(_))) => return true,
+            Ok(Err(broadcast::error::RecvError::Closed)) | Err(_) => return false,
+        }
+    }
+}
+
+fn wait_timeout(seconds: f64) -> Result<Duration, String> {
+    if !(0.0..=MAX_WAIT_SECS).contains(&seconds) {
+        return Err(format!("timeout must be between 0 and {MAX_WAIT_SECS} seconds"));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+fn bad_request(message: String) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({"error": message}))).into_response()
+}
+
+fn db_error(err: sqlx::Error) -> Response {
+    eprintln!("DB error: {err}");
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": "Database error"}))).into_response()
+}
@@ -0,0 +1,268 @@
+use axum::body::{Body, Bytes};
+use axum::http::StatusCode;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::{db::Database, forwarded, models::StoredRequest, utils};
+
+/// File formats captured requests can be exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// HAR 1.2, as read by browser devtools and most HTTP tooling
+    #[default]
+    Har,
+    /// One `StoredRequest` JSON object per line
+    Ndjson,
+    /// A shell script of `curl` commands that resend the requests
+    Curl,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Har => "application/json",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Curl => "text/x-shellscript; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Har => "har",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Curl => "sh",
+        }
+    }
+
+    /// Text written before the first request
+    fn header(self, webhook_id: &str) -> String {
+        match self {
+            ExportFormat::Har => {
+                let creator = json!({"name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION")});
+                format!(r#"{{"log":{{"version":"1.2","creator":{creator},"entries":["#)
+            }
+            ExportFormat::Ndjson => String::new(),
+            ExportFormat::Curl => format!(
+                "#!/bin/sh\n\
+                 # Requests captured by webhook {webhook_id}\n\
+                 # Set BASE_URL to send them somewhere else, e.g. BASE_URL=http://localhost:8080 sh this-file.sh\n"
+            ),
+        }
+    }
+
+    /// One request, `first` telling whether it needs a separator
+    fn entry(self, req: &StoredRequest, first: bool) -> String {
+        match self {
+            ExportFormat::Har => {
+                let separator = if first { "" } else { "," };
+                format!("{separator}{}", har_entry(req))
+            }
+            ExportFormat::Ndjson => format!("{}\n", ndjson_line(req)),
+            ExportFormat::Curl => {
+                let url = format!("\"${{BASE_URL:-{}}}\"{}", request_origin(req), shell_quote(&request_path(req)));
+                format!("\n# {} captured {}\n{}\n", req.id, req.created_at, curl_command(req, &url))
+            }
+        }
+    }
+
+    /// Text written after the last request
+    fn footer(self) -> &'static str {
+        match self {
+            ExportFormat::Har => "]}}",
+            ExportFormat::Ndjson | ExportFormat::Curl => "",
+        }
+    }
+}
+
+/// Stream a webhook's requests (all of them, or just `ids`) in `format`.
+///
+/// Rows are formatted as they come off the database, so exports of any size
+/// are never held in memory. A database error mid-way aborts the response
+/// rather than ending it with a truncated but well-formed file.
+pub fn export_body(db: Arc<Database>, webhook_id: String, ids: Vec<String>, format: ExportFormat) -> Body {
+    let (chunks, mut chunk_rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(16);
+
+    tokio::spawn(async move {
+        let (rows, mut row_rx) = mpsc::channel(64);
+        let producer = db.stream_requests(&webhook_id, &ids, rows);
+
+        let writer = async {
+            if chunks.send(Ok(format.header(&webhook_id).into())).await.is_err() {
+                return;
+            }
+            let mut first = true;
+            while let Some(req) = row_rx.recv().await {
+                if chunks.send(Ok(format.entry(&req, first).into())).await.is_err() {
+                    return;
+                }
+                first = false;
+            }
+        };
+
+        let (result, ()) = tokio::join!(producer, writer);
+        let last = match result {
+            Ok(()) => Ok(Bytes::from_static(format.footer().as_bytes())),
+            Err(err) => {
+                eprintln!("Export error: {err}");
+                Err(std::io::Error::other(err))
+            }
+        };
+        let _ = chunks.send(last).await;
+    });
+
+    Body::from_stream(futures_util::stream::poll_fn(move |cx| chunk_rx.poll_recv(cx)))
+}
+
+/// Scheme and host the request was sent to, from the URL recorded when it was
+/// captured, which only believes trusted proxies
+pub fn request_origin(req: &StoredRequest) -> String {
+    if let Some(url) = req.request_uri.as_deref().and_then(|uri| Url::parse(uri).ok()) {
+        return url.origin().ascii_serialization();
+    }
+    // Captured before URLs were recorded, or imported without one
+    let headers = req.header_map();
+    let host = headers
+        .get("host")
+        .map(String::as_str)
+        .filter(|host| forwarded::valid_host(host))
+        .unwrap_or("localhost:3000");
+    format!("http://{host}")
+}
+
+/// Path and query string the request was sent to
+pub fn request_path(req: &StoredRequest) -> String {
+    let query = req.query_map();
+    let path = format!("/webhook/{}", req.webhook_id);
+    if query.is_empty() {
+        path
+    } else {
+        format!("{path}?{}", serde_urlencoded::to_string(&query).unwrap_or_default())
+    }
+}
+
+/// Full URL the request was sent to
+pub fn request_url(req: &StoredRequest) -> String {
+    format!("{}{}", request_origin(req), request_path(req))
+}
+
+/// Headers worth resending with the stored body: the client recomputes framing and
+/// `Host` itself, and a decompressed body no longer has its `Content-Encoding`
+pub fn replayable_headers(req: &StoredRequest) -> BTreeMap<String, String> {
+    let mut headers = req.header_map();
+    for name in ["host", "content-length", "transfer-encoding", "connection"] {
+        headers.remove(name);
+    }
+    if req.body_encoding.is_some() {
+        headers.remove("content-encoding");
+    }
+    headers
+}
+
+/// Quote a string for POSIX shells
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// A method as a shell word, quoted only if it needs to be
+pub fn shell_method(method: &str) -> String {
+    if method.chars().all(|c| c.is_ascii_alphanumeric()) {
+        method.to_string()
+    } else {
+        shell_quote(method)
+    }
+}
+
+/// A multi-line `curl` command resending `req` to `url`, which must already be shell-quoted
+pub fn curl_command(req: &StoredRequest, url: &str) -> String {
+    let mut lines = vec![format!("curl -sS -X {} {url}", shell_method(&req.method))];
+    for (name, value) in replayable_headers(req) {
+        lines.push(format!("-H {}", shell_quote(&format!("{name}: {value}"))));
+    }
+    if !req.body.is_empty() {
+        lines.push(format!("--data-binary {}", shell_quote(&req.body)));
+    }
+    lines.join(" \\\n  ")
+}
+
+/// A request as an NDJSON line, carrying the bytes as received so that a
+/// compressed or binary body survives a re-import
+pub fn ndjson_line(req: &StoredRequest) -> String {
+    let mut line = serde_json::to_value(req).unwrap_or_default();
+    if let Some(raw_body) = &req.raw_body {
+        line["raw_body"] = json!(raw_body);
+    }
+    line.to_string()
+}
+
+fn name_values(map: &BTreeMap<String, String>) -> Vec<Value> {
+    map.iter().map(|(name, value)| json!({"name": name, "value": value})).collect()
+}
+
+/// A request and the response we sent as a HAR 1.2 entry.
+///
+/// Fields HAR has no place for are kept under `_`-prefixed keys, which the
+/// spec reserves for custom data.
+pub fn har_entry(req: &StoredRequest) -> Value {
+    let headers = req.header_map();
+    let response_headers = req.response_header_map();
+    let latency = req.latency_ms.unwrap_or(0.0);
+
+    let mut request = json!({
+        "method": req.method,
+        "url": request_url(req),
+        "httpVersion": req.http_version.as_deref().unwrap_or("HTTP/1.1"),
+        "cookies": [],
+        "headers": name_values(&headers),
+        "queryString": name_values(&req.query_map()),
+        "headersSize": -1,
+        "bodySize": req.body.len(),
+    });
+    if !req.body.is_empty() {
+        request["postData"] = json!({
+            "mimeType": headers.get("content-type").cloned().unwrap_or_default(),
+            "text": req.body,
+        });
+    }
+
+    let status = req.response_status.unwrap_or(0);
+    let response_body = req.response_body.clone().unwrap_or_default();
+    let response = json!({
+        "status": status,
+        "statusText": StatusCode::from_u16(status).ok().and_then(|s| s.canonical_reason()).unwrap_or(""),
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": name_values(&response_headers),
+        "content": {
+            "size": response_body.len(),
+            "mimeType": response_headers.get("content-type").cloned().unwrap_or_default(),
+            "text": response_body,
+        },
+        "redirectURL": "",
+        "headersSize": -1,
+        "bodySize": response_body.len(),
+    });
+
+    json!({
+        "startedDateTime": utils::parse_timestamp(&req.created_at)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_else(|| req.created_at.clone()),
+        "time": latency,
+        "request": request,
+        "response": response,
+        "cache": {},
+        "timings": {"send": 0, "wait": latency, "receive": 0},
+        "_id": req.id,
+        "_webhookId": req.webhook_id,
+        "_pinned": req.pinned,
+        "_derivedFrom": req.derived_from,
+        "_responseSource": req.response_source,
+        "_signatureStatus": req.signature_status,
+        "_bodyEncoding": req.body_encoding,
+        "_rawBody": req.raw_body,
+    })
+}
@@ -0,0 +1,183 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, uri::Authority, HeaderMap, Version},
+};
+use ipnet::IpNet;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::egress::parse_cidrs;
+
+/// Peers whose `Forwarded` and `X-Forwarded-*` headers are believed, such as the
+/// reverse proxy or load balancer in front of the tester. Nobody is trusted by default,
+/// so a sender can't make up its own address.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    /// `TRUSTED_PROXIES`: CIDRs or bare addresses
+    pub cidrs: Vec<IpNet>,
+}
+
+/// How a request reached the server
+pub struct Connection {
+    /// The peer that connected, when the server was started with connect info
+    pub peer: Option<SocketAddr>,
+    pub version: Version,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Connection {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| *addr);
+        Ok(Self { peer, version: parts.version })
+    }
+}
+
+/// Where a request came from, looking through trusted proxies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The client's address: the peer itself, or the last hop before a trusted proxy
+    pub client_ip: Option<IpAddr>,
+    /// `https` when a trusted proxy says the client used TLS
+    pub scheme: String,
+    /// The host the client asked for, when a trusted proxy passed it on
+    pub host: Option<String>,
+}
+
+/// What one proxy recorded about the connection it received
+#[derive(Default)]
+struct Hop {
+    node: Option<String>,
+    proto: Option<String>,
+    host: Option<String>,
+}
+
+impl TrustedProxies {
+    pub fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("TRUSTED_PROXIES") {
+            Ok(cidrs) => Ok(Self { cidrs: parse_cidrs(&cidrs)? }),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn trusts(&self, ip: IpAddr) -> bool {
+        self.cidrs.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Resolve the client behind `peer`. Hops are read right to left, from `Forwarded`
+    /// if present and `X-Forwarded-For` otherwise, stopping at the first one that isn't
+    /// a trusted proxy. The scheme and host come from the same hop as the client.
+    pub fn resolve(&self, peer: Option<SocketAddr>, headers: &HeaderMap) -> Origin {
+        let peer_ip = peer.map(|peer| peer.ip());
+        let untrusted = Origin { client_ip: peer_ip, scheme: "http".to_string(), host: None };
+        let Some(mut client) = peer_ip.filter(|ip| self.trusts(*ip)) else {
+            return untrusted;
+        };
+
+        let hops = forwarding_hops(headers);
+        // Each hop was written by a trusted proxy until the walk stops
+        let mut last = None;
+        for hop in hops.iter().rev() {
+            last = Some(hop);
+            // `unknown` and obfuscated identifiers end the chain at the last known hop
+            let Some(ip) = hop.node.as_deref().and_then(parse_node) else { break };
+            client = ip;
+            if !self.trusts(ip) {
+                break;
+            }
+        }
+
+        let scheme = last
+            .and_then(|hop| hop.proto.as_deref())
+            .map(|proto| proto.to_ascii_lowercase())
+            .filter(|proto| proto == "http" || proto == "https")
+            .unwrap_or_else(|| "http".to_string());
+        let host = last.and_then(|hop| hop.host.clone()).filter(|host| valid_host(host));
+        Origin { client_ip: Some(client), scheme, host }
+    }
+}
+
+/// A `Host` value that is a bare authority: a name or address and an optional port
+pub fn valid_host(host: &str) -> bool {
+    !host.contains('@') && host.parse::<Authority>().is_ok()
+}
+
+/// Every hop a request passed through, oldest first, from `Forwarded` if present
+/// and `X-Forwarded-For` otherwise
+fn forwarding_hops(headers: &HeaderMap) -> Vec<Hop> {
+    let forwarded = forwarded_elements(headers);
+    if !forwarded.is_empty() {
+        return forwarded
+            .into_iter()
+            .map(|mut element| Hop {
+                node: element.remove("for"),
+                proto: element.remove("proto"),
+                host: element.remove("host"),
+            })
+            .collect();
+    }
+
+    // Proxies that don't append to X-Forwarded-Proto or -Host leave them shorter than
+    // X-Forwarded-For; the rightmost value is then the one the nearest proxy set
+    let nodes = header_list(headers, "x-forwarded-for");
+    let protos = header_list(headers, "x-forwarded-proto");
+    let hosts = header_list(headers, "x-forwarded-host");
+    // A proxy that only passes on the scheme or host still describes the peer's hop
+    if nodes.is_empty() && !(protos.is_empty() && hosts.is_empty()) {
+        return vec![Hop { node: None, proto: protos.last().cloned(), host: hosts.last().cloned() }];
+    }
+    let aligned = |values: &[String], index: usize| {
+        let from_right = nodes.len() - index;
+        values
+            .len()
+            .checked_sub(from_right)
+            .and_then(|i| values.get(i))
+            .or(values.last())
+            .cloned()
+    };
+    nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| Hop {
+            node: Some(node.clone()),
+            proto: aligned(&protos, index),
+            host: aligned(&hosts, index),
+        })
+        .collect()
+}
+
+/// Comma-separated values of every `name` header, in order
+fn header_list(headers: &HeaderMap, name: &str) -> Vec<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// RFC 7239 `Forwarded` elements, each as lowercase parameter names and unquoted values
+fn forwarded_elements(headers: &HeaderMap) -> Vec<std::collections::HashMap<String, String>> {
+    header_list(headers, "forwarded")
+        .iter()
+        .map(|element| {
+            element
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().trim_matches('"').to_string()))
+                .collect()
+        })
+        .collect()
+}
+
+/// An address from a forwarding header: `192.0.2.1`, `192.0.2.1:4711`, `[2001:db8::1]:4711` or `2001:db8::1`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| node.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')).and_then(|ip| ip.parse().ok()))
+}
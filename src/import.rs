@@ -0,0 +1,396 @@
+use anyhow::{anyhow, bail, Context};
+use axum::http::Method;
+use chrono::{Duration, Utc};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::{models::StoredRequest, utils};
+
+/// File formats requests can be imported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    /// A HAR 1.2 log, e.g. saved from browser devtools or our own export
+    Har,
+    /// One JSON object per line: either a `StoredRequest`, as exported, or any
+    /// other object, which becomes the JSON body of a POST
+    Ndjson,
+    /// A shell script of `curl` commands
+    Curl,
+}
+
+impl ImportFormat {
+    /// Guess the format from the file contents
+    pub fn detect(input: &str) -> Self {
+        let start = input.trim_start();
+        if start.starts_with('#') || start.starts_with("curl") {
+            ImportFormat::Curl
+        } else if serde_json::from_str::<Value>(input).is_ok_and(|v| v.get("log").is_some()) {
+            ImportFormat::Har
+        } else {
+            ImportFormat::Ndjson
+        }
+    }
+}
+
+/// Parse a file of requests for `webhook_id`.
+///
+/// Every request gets a fresh ID but keeps its original timestamp; ones
+/// without a timestamp are stamped with the import time, in file order.
+pub fn parse(input: &str, format: Option<ImportFormat>, webhook_id: &str) -> anyhow::Result<Vec<StoredRequest>> {
+    let mut requests = match format.unwrap_or_else(|| ImportFormat::detect(input)) {
+        ImportFormat::Har => parse_har(input)?,
+        ImportFormat::Ndjson => parse_ndjson(input)?,
+        ImportFormat::Curl => parse_curl(input)?,
+    };
+
+    let now = Utc::now();
+    for (index, req) in requests.iter_mut().enumerate() {
+        req.id = uuid::Uuid::new_v4().to_string();
+        req.webhook_id = webhook_id.to_string();
+        // The original is not part of the import
+        req.derived_from = None;
+        let created_at = utils::parse_timestamp(&req.created_at)
+            .unwrap_or_else(|| now + Duration::microseconds(index as i64));
+        req.created_at = created_at.to_rfc3339();
+        without_missing_encoding(req);
+    }
+    Ok(requests)
+}
+
+/// Without the bytes as received, a stored body is the decoded payload: it must
+/// not be replayed or relayed under the `Content-Encoding` it arrived with
+fn without_missing_encoding(req: &mut StoredRequest) {
+    if req.raw_body.is_some() {
+        return;
+    }
+    req.body_encoding = None;
+    let mut headers = req.header_map();
+    if headers.remove("content-encoding").is_some() {
+        req.headers = to_json(&headers);
+    }
+}
+
+fn parse_har(input: &str) -> anyhow::Result<Vec<StoredRequest>> {
+    let har: Value = serde_json::from_str(input).context("Invalid HAR file")?;
+    let entries = har["log"]["entries"]
+        .as_array()
+        .ok_or_else(|| anyhow!("HAR file has no log.entries"))?;
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| har_request(entry).with_context(|| format!("HAR entry {index}")))
+        .collect()
+}
+
+fn har_request(entry: &Value) -> anyhow::Result<StoredRequest> {
+    let request = &entry["request"];
+    let method = checked_method(request["method"].as_str().unwrap_or("GET"))?;
+    let url = request["url"].as_str().ok_or_else(|| anyhow!("missing request.url"))?;
+
+    let mut req = StoredRequest {
+        method,
+        headers: to_json(&name_values(&request["headers"])),
+        body: request["postData"]["text"].as_str().unwrap_or_default().to_string(),
+        query: to_json(&query_of(url)?),
+        request_uri: Some(url.to_string()),
+        created_at: entry["startedDateTime"].as_str().unwrap_or_default().to_string(),
+        latency_ms: entry["time"].as_f64(),
+        pinned: entry["_pinned"].as_bool().unwrap_or(false),
+        body_encoding: entry["_bodyEncoding"].as_str().map(String::from),
+        raw_body: entry["_rawBody"].as_str().map(String::from),
+        ..Default::default()
+    };
+
+    // Status 0 is how HAR (and our export) spells "no response"
+    let response = &entry["response"];
+    if let Some(status) = response["status"].as_u64().filter(|s| (100..=599).contains(s)) {
+        req.response_status = Some(status as u16);
+        req.response_headers = Some(to_json(&name_values(&response["headers"])));
+        req.response_body = response["content"]["text"].as_str().map(String::from);
+        req.response_source = entry["_responseSource"].as_str().map(String::from);
+    }
+    Ok(req)
+}
+
+fn parse_ndjson(input: &str) -> anyhow::Result<Vec<StoredRequest>> {
+    let mut requests = Vec::new();
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(line).with_context(|| format!("Line {}: invalid JSON", index + 1))?;
+
+        let looks_captured = value.get("method").is_some() && value.get("headers").is_some();
+        let req = match serde_json::from_value::<StoredRequest>(value) {
+            Ok(mut req) if looks_captured => {
+                req.method = checked_method(&req.method).with_context(|| format!("Line {}", index + 1))?;
+                req
+            }
+            // Anything else is a payload fixture
+            _ => StoredRequest {
+                method: "POST".to_string(),
+                headers: to_json(&BTreeMap::from([("content-type".to_string(), "application/json".to_string())])),
+                body: line.to_string(),
+                query: "{}".to_string(),
+                ..Default::default()
+            },
+        };
+        requests.push(req);
+    }
+    Ok(requests)
+}
+
+/// Parse every `curl` command in a shell script.
+///
+/// A `# <id> captured <timestamp>` comment, as written by our export, dates
+/// the command after it.
+fn parse_curl(input: &str) -> anyhow::Result<Vec<StoredRequest>> {
+    let mut requests = Vec::new();
+    let mut captured_at = String::new();
+    let mut command = String::new();
+
+    for line in input.lines() {
+        if command.is_empty() {
+            let trimmed = line.trim();
+            if let Some(comment) = trimmed.strip_prefix('#') {
+                if let Some((_, at)) = comment.split_once(" captured ") {
+                    captured_at = at.trim().to_string();
+                }
+                continue;
+            }
+            if !trimmed.starts_with("curl ") {
+                continue;
+            }
+        }
+
+        command.push_str(line);
+        command.push('\n');
+        if !continues(&command) {
+            let mut req = curl_request(&command).with_context(|| format!("Invalid curl command: {}", command.trim()))?;
+            req.created_at = std::mem::take(&mut captured_at);
+            requests.push(req);
+            command.clear();
+        }
+    }
+
+    if !command.is_empty() {
+        bail!("Unterminated curl command: {}", command.trim());
+    }
+    Ok(requests)
+}
+
+/// Whether a command goes on past the end of `command`: inside quotes, or
+/// after a backslash-newline
+fn continues(command: &str) -> bool {
+    let mut single = false;
+    let mut double = false;
+    let mut escaped = false;
+    let mut last_escaped_newline = false;
+
+    for c in command.chars() {
+        last_escaped_newline = escaped && c == '\n';
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if !single => escaped = true,
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            _ => {}
+        }
+    }
+    single || double || last_escaped_newline
+}
+
+fn curl_request(command: &str) -> anyhow::Result<StoredRequest> {
+    let words = shell_words(command)?;
+    let mut args = words.into_iter().skip(1);
+
+    let mut method = None;
+    let mut url = None;
+    let mut headers = BTreeMap::new();
+    let mut data: Vec<String> = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("{arg} needs a value"));
+        match arg.as_str() {
+            "-X" | "--request" => method = Some(value()?),
+            "-H" | "--header" => {
+                let header = value()?;
+                let (name, value) = header.split_once(':').ok_or_else(|| anyhow!("Invalid header: {header}"))?;
+                headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+            "-d" | "--data" | "--data-raw" | "--data-binary" | "--data-ascii" => {
+                let body = value()?;
+                if arg != "--data-raw" && body.starts_with('@') {
+                    bail!("Reading the body from a file ({body}) is not supported");
+                }
+                data.push(body);
+            }
+            "--json" => {
+                data.push(value()?);
+                headers.entry("content-type".to_string()).or_insert_with(|| "application/json".to_string());
+                headers.entry("accept".to_string()).or_insert_with(|| "application/json".to_string());
+            }
+            "-A" | "--user-agent" => {
+                headers.insert("user-agent".to_string(), value()?);
+            }
+            "-b" | "--cookie" => {
+                headers.insert("cookie".to_string(), value()?);
+            }
+            "--url" => url = Some(value()?),
+            "-o" | "--output" | "-m" | "--max-time" | "--connect-timeout" | "-w" | "--write-out" | "-e" | "--referer"
+            | "--retry" => {
+                value()?;
+            }
+            flag if flag.starts_with('-') => {}
+            _ => url = Some(arg),
+        }
+    }
+
+    let url = url.ok_or_else(|| anyhow!("no URL"))?;
+    if !data.is_empty() {
+        headers
+            .entry("content-type".to_string())
+            .or_insert_with(|| "application/x-www-form-urlencoded".to_string());
+    }
+    let method = method.unwrap_or_else(|| if data.is_empty() { "GET" } else { "POST" }.to_string());
+
+    Ok(StoredRequest {
+        method: checked_method(&method)?,
+        headers: to_json(&headers),
+        body: data.join("&"),
+        query: to_json(&query_of(&url)?),
+        ..Default::default()
+    })
+}
+
+/// Split a command line into words the way a POSIX shell would, as far as
+/// quoting goes. `${NAME:-default}` expands to its default and other
+/// variables to nothing, since the environment is not ours to read.
+fn shell_words(command: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => bail!("Unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => bail!("Unterminated double quote"),
+                        },
+                        Some('$') => word.push_str(&expand_variable(&mut chars)?),
+                        Some(c) => word.push(c),
+                        None => bail!("Unterminated double quote"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                // Line continuation
+                Some('\n') => {}
+                Some(c) => {
+                    in_word = true;
+                    word.push(c);
+                }
+                None => {}
+            },
+            '$' => {
+                in_word = true;
+                word.push_str(&expand_variable(&mut chars)?);
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Expand the variable after a `$`
+fn expand_variable(chars: &mut std::iter::Peekable<std::str::Chars>) -> anyhow::Result<String> {
+    if chars.peek() != Some(&'{') {
+        while chars.peek().is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            chars.next();
+        }
+        return Ok(String::new());
+    }
+
+    chars.next();
+    let mut inner = String::new();
+    loop {
+        match chars.next() {
+            Some('}') => break,
+            Some(c) => inner.push(c),
+            None => bail!("Unterminated ${{...}}"),
+        }
+    }
+    Ok(inner.split_once(":-").map(|(_, default)| default.to_string()).unwrap_or_default())
+}
+
+fn checked_method(method: &str) -> anyhow::Result<String> {
+    let method = method.to_ascii_uppercase();
+    Method::from_bytes(method.as_bytes()).map_err(|_| anyhow!("Invalid method: {method}"))?;
+    Ok(method)
+}
+
+/// `[{"name": ..., "value": ...}]` as a map, joining repeated names like HTTP does
+fn name_values(list: &Value) -> BTreeMap<String, String> {
+    let mut map: BTreeMap<String, String> = BTreeMap::new();
+    for item in list.as_array().into_iter().flatten() {
+        let (Some(name), Some(value)) = (item["name"].as_str(), item["value"].as_str()) else {
+            continue;
+        };
+        map.entry(name.to_ascii_lowercase())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    map
+}
+
+fn query_of(url: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let url = Url::parse(url).with_context(|| format!("Invalid URL: {url}"))?;
+    Ok(url.query_pairs().into_owned().collect())
+}
+
+fn to_json(map: &BTreeMap<String, String>) -> String {
+    serde_json::to_string(map).unwrap_or_else(|_| "{}".to_string())
+}
@@ -1,7 +1,25 @@
+pub mod auth;
+pub mod body;
+pub mod cli;
+pub mod codegen;
 pub mod routes;
 pub mod db;
+pub mod egress;
+pub mod expectations;
+pub mod export;
+pub mod forwarded;
+pub mod import;
 pub mod models;
+pub mod outbound;
 pub mod replay;
+pub mod replay_jobs;
+pub mod retention;
+pub mod schedules;
+pub mod schema;
+pub mod search;
+pub mod settings;
+pub mod signature;
+pub mod testing;
 pub mod utils;
 
 use std::sync::Arc;
@@ -11,12 +29,71 @@ use tokio::sync::broadcast;
 pub struct AppState {
     pub db: Arc<db::Database>,
     pub tx: broadcast::Sender<models::WebhookEvent>,
+    pub settings: settings::Settings,
+    pub replay_jobs: replay_jobs::ReplayJobs,
+    pub expectations: expectations::Expectations,
+    pub schemas: schema::SchemaCache,
+    /// Client for every replayed and forwarded request, built from the settings
+    pub http: reqwest::Client,
+    /// Client that skips certificate checks, for local targets; only with `OUTBOUND_INSECURE_TLS`
+    pub local_http: Option<reqwest::Client>,
 }
 
 impl AppState {
-    pub async fn create_webhook(&self, id: &str) -> anyhow::Result<()> {
-        self.db.create_webhook(id).await?;
-        Ok(())
+    /// Build a state with default settings.
+    pub fn new(db: Arc<db::Database>, tx: broadcast::Sender<models::WebhookEvent>) -> anyhow::Result<Self> {
+        Self::with_settings(db, tx, settings::Settings::default())
+    }
+
+    /// Build a state, failing if the outbound client can't be set up from `settings`.
+    pub fn with_settings(
+        db: Arc<db::Database>,
+        tx: broadcast::Sender<models::WebhookEvent>,
+        settings: settings::Settings,
+    ) -> anyhow::Result<Self> {
+        let http = settings.outbound.build_client(&settings.egress)?;
+        let local_http = settings.outbound.build_local_client(&settings.egress)?;
+        Ok(Self {
+            db,
+            tx,
+            settings,
+            replay_jobs: replay_jobs::ReplayJobs::default(),
+            expectations: expectations::Expectations::default(),
+            schemas: schema::SchemaCache::default(),
+            http,
+            local_http,
+        })
+    }
+
+    /// The client to send to `target` with: the one that skips certificate checks
+    /// if there is one and the target resolves to loopback or private addresses
+    pub async fn client_for(&self, target: &str) -> &reqwest::Client {
+        if let (Some(local), Ok(url)) = (&self.local_http, reqwest::Url::parse(target)) {
+            if egress::is_local_target(&url).await {
+                return local;
+            }
+        }
+        &self.http
+    }
+
+    /// Create a webhook and return the management token that guards it.
+    pub async fn create_webhook(&self, id: &str) -> anyhow::Result<String> {
+        let token = auth::generate_token();
+        self.db.create_webhook(id, Some(&auth::hash_token(&token))).await?;
+        Ok(token)
+    }
+
+    /// Create `id` on first use, as `UNKNOWN_WEBHOOK_POLICY=create` allows. Concurrent
+    /// first requests race safely: only the one that inserted the row gets the token.
+    pub async fn claim_webhook(&self, id: &str) -> anyhow::Result<(models::Webhook, Option<String>)> {
+        let token = auth::generate_token();
+        let created = self.db.create_webhook_if_missing(id, &auth::hash_token(&token)).await?;
+        let webhook = self
+            .db
+            .get_webhook(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Webhook {id} disappeared right after being created"))?;
+        Ok((webhook, created.then_some(token)))
     }
 
     pub async fn store_request(&self, req: &models::StoredRequest) -> anyhow::Result<()> {
@@ -24,6 +101,15 @@ impl AppState {
         Ok(())
     }
 
+    /// Tell everyone watching a webhook about a request just stored in it: in-process
+    /// subscribers such as waits, expectations and the embedded test server, and
+    /// WebSocket clients
+    pub async fn announce(&self, req: &models::StoredRequest) {
+        // Having no subscribers is the usual case, not an error
+        let _ = self.tx.send(req.clone().into());
+        routes::ws::broadcast_to_clients(&req.webhook_id, req).await;
+    }
+
     pub async fn get_response_config(
         &self,
         webhook_id: &str,
@@ -1,8 +1,14 @@
+use base64::Engine;
 use serde::{Serialize, Deserialize};
 use sqlx::FromRow;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::schema::{PayloadSchema, SchemaError};
+use crate::signature::OutboundSigning;
 
 /// Represents a stored webhook request
-#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone, Default)]
 pub struct StoredRequest {
     pub id: String,
     pub webhook_id: String,
@@ -11,6 +17,114 @@ pub struct StoredRequest {
     pub body: String,
     pub query: String,
     pub created_at: String,
+    /// Status code we answered the sender with
+    #[sqlx(default)]
+    pub response_status: Option<u16>,
+    /// JSON-encoded headers we answered the sender with
+    #[sqlx(default)]
+    pub response_headers: Option<String>,
+    #[sqlx(default)]
+    pub response_body: Option<String>,
+    /// What produced the response: `default`, `config`, `sequence:<step>`, `signature` or `schema`
+    #[sqlx(default)]
+    pub response_source: Option<String>,
+    /// Version of the webhook config in force when the response was chosen
+    #[sqlx(default)]
+    #[serde(default)]
+    pub config_version: Option<i64>,
+    /// Time spent in the handler before the response was ready, storing and forwarding included
+    #[sqlx(default)]
+    pub latency_ms: Option<f64>,
+    /// Signature verdict: `valid`, `invalid` or `missing` (unset when not configured)
+    #[sqlx(default)]
+    pub signature_status: Option<String>,
+    #[sqlx(default)]
+    pub signature_reason: Option<String>,
+    /// Payload schema verdict: `valid`, `invalid` or `unmatched` (unset when not configured)
+    #[sqlx(default)]
+    #[serde(default)]
+    pub schema_status: Option<String>,
+    /// JSON-encoded list of `SchemaError`s, each with the JSON Pointer it applies to
+    #[sqlx(default)]
+    #[serde(default)]
+    pub schema_errors: Option<String>,
+    /// `Content-Encoding` removed from the body before it was stored, such as `gzip`
+    #[sqlx(default)]
+    #[serde(default)]
+    pub body_encoding: Option<String>,
+    /// The body exactly as received, base64-encoded, when `body` differs from it:
+    /// decompressed, or not UTF-8. Served by `/api/requests/:req_id/raw`
+    #[sqlx(default)]
+    #[serde(default, skip_serializing)]
+    pub raw_body: Option<String>,
+    /// Socket address of the peer that connected, which may be a proxy
+    #[sqlx(default)]
+    #[serde(default)]
+    pub remote_addr: Option<String>,
+    /// The sender's address, read through `TRUSTED_PROXIES` when the peer is one
+    #[sqlx(default)]
+    #[serde(default)]
+    pub client_ip: Option<String>,
+    /// Such as `HTTP/1.1` or `HTTP/2.0`
+    #[sqlx(default)]
+    #[serde(default)]
+    pub http_version: Option<String>,
+    /// Absolute URL the request was sent to; `https` when a trusted proxy terminated TLS
+    #[sqlx(default)]
+    #[serde(default)]
+    pub request_uri: Option<String>,
+    /// Bytes received, before any decompression
+    #[sqlx(default)]
+    #[serde(default)]
+    pub body_size: Option<i64>,
+    /// When the handler got the request, to the microsecond
+    #[sqlx(default)]
+    #[serde(default)]
+    pub received_at: Option<String>,
+    /// Pinned requests are never purged by retention
+    #[sqlx(default)]
+    #[serde(default)]
+    pub pinned: bool,
+    /// The request this one was edited from, for requests created by edit-and-replay
+    #[sqlx(default)]
+    #[serde(default)]
+    pub derived_from: Option<String>,
+}
+
+impl StoredRequest {
+    /// Captured headers, sorted by name
+    pub fn header_map(&self) -> BTreeMap<String, String> {
+        serde_json::from_str(&self.headers).unwrap_or_default()
+    }
+
+    /// The body as it was received, before any decompression
+    pub fn raw_bytes(&self) -> Vec<u8> {
+        self.raw_body
+            .as_deref()
+            .and_then(|raw| base64::engine::general_purpose::STANDARD.decode(raw).ok())
+            .unwrap_or_else(|| self.body.as_bytes().to_vec())
+    }
+
+    /// Why the body broke its schema, if it did
+    pub fn schema_error_list(&self) -> Vec<SchemaError> {
+        self.schema_errors
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Captured query parameters, sorted by name
+    pub fn query_map(&self) -> BTreeMap<String, String> {
+        serde_json::from_str(&self.query).unwrap_or_default()
+    }
+
+    /// Headers of the response we sent, sorted by name
+    pub fn response_header_map(&self) -> BTreeMap<String, String> {
+        self.response_headers
+            .as_deref()
+            .and_then(|h| serde_json::from_str(h).ok())
+            .unwrap_or_default()
+    }
 }
 
 /// Webhook configuration / custom response
@@ -21,6 +135,25 @@ pub struct WebhookConfig {
     pub response_body: Option<String>,
     pub content_type: Option<String>,
     pub forward_url: Option<String>,
+    /// JSON-encoded list of `ResponseStep`s served in order, one per call
+    pub response_sequence: Option<String>,
+    /// What to do once the sequence is exhausted: `loop` or `stick` (default)
+    pub sequence_mode: Option<String>,
+    /// Provider whose signing scheme incoming requests are checked against
+    pub signing_provider: Option<String>,
+    #[serde(skip_serializing)]
+    pub signing_secret: Option<String>,
+    /// Answer 401 instead of the configured response when the signature isn't valid
+    pub reject_invalid_signatures: Option<bool>,
+    /// JSON-encoded `OutboundSigning` used to re-sign forwarded requests
+    #[serde(skip_serializing)]
+    pub forward_signing: Option<String>,
+    /// JSON-encoded `PayloadSchema` captured bodies are validated against
+    pub payload_schema: Option<String>,
+    /// Answer 400 instead of the configured response when the body breaks its schema
+    pub reject_invalid_payloads: Option<bool>,
+    /// Bumped every time the config is saved; unset until it first is
+    pub version: Option<i64>,
 }
 
 impl WebhookConfig {
@@ -31,10 +164,188 @@ impl WebhookConfig {
             response_body: Some("OK".to_string()),
             content_type: Some("text/plain".to_string()),
             forward_url: None,
+            response_sequence: None,
+            sequence_mode: None,
+            signing_provider: None,
+            signing_secret: None,
+            reject_invalid_signatures: None,
+            forward_signing: None,
+            payload_schema: None,
+            reject_invalid_payloads: None,
+            version: None,
+        }
+    }
+
+    /// Decode the scripted response sequence, if any.
+    pub fn sequence_steps(&self) -> Vec<ResponseStep> {
+        self.response_sequence
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Decode the signing settings applied to forwarded requests, if any.
+    pub fn forward_signing(&self) -> Option<OutboundSigning> {
+        self.forward_signing
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+    }
+
+    /// Decode the schemas captured bodies are validated against, if any.
+    pub fn payload_schema(&self) -> Option<PayloadSchema> {
+        self.payload_schema
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+    }
+
+    pub fn sequence_mode(&self) -> SequenceMode {
+        self.sequence_mode
+            .as_deref()
+            .and_then(|mode| mode.parse().ok())
+            .unwrap_or(SequenceMode::Stick)
+    }
+}
+
+/// A single scripted response; unset fields fall back to the webhook config
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponseStep {
+    pub status_code: Option<u16>,
+    pub response_body: Option<String>,
+    pub content_type: Option<String>,
+}
+
+/// Policy applied once every step of a response sequence has been served
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceMode {
+    /// Start again from the first step
+    Loop,
+    /// Keep serving the last step
+    Stick,
+}
+
+impl FromStr for SequenceMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "loop" => Ok(Self::Loop),
+            "stick" => Ok(Self::Stick),
+            other => Err(anyhow::anyhow!("Unknown sequence mode: {other} (expected loop or stick)")),
         }
     }
 }
 
+impl SequenceMode {
+    /// Map a 1-based call number onto an index into a sequence of `len` steps.
+    pub fn step_index(self, call: u64, len: usize) -> usize {
+        let zero_based = call.saturating_sub(1) as usize;
+        match self {
+            SequenceMode::Loop => zero_based % len,
+            SequenceMode::Stick => zero_based.min(len - 1),
+        }
+    }
+}
+
+/// A file uploaded in a `multipart/form-data` body. Only its details are stored;
+/// the contents are read from the request body when downloaded
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone, Default)]
+pub struct RequestFile {
+    pub id: String,
+    pub request_id: String,
+    /// Position of the file's part among all the parts of the body
+    pub part_index: i64,
+    /// Form field the file was sent as
+    pub field_name: Option<String>,
+    pub filename: String,
+    pub content_type: Option<String>,
+    pub size: i64,
+    pub created_at: String,
+}
+
+/// What a relay got back when it re-sent a captured request to a local target
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct Delivery {
+    pub id: String,
+    pub request_id: String,
+    pub target: String,
+    pub status_code: Option<u16>,
+    /// JSON object of the response headers
+    pub response_headers: Option<String>,
+    pub response_body: Option<String>,
+    /// Why no response came back
+    pub error: Option<String>,
+    pub duration_ms: Option<f64>,
+    pub created_at: String,
+}
+
+/// A [`Delivery`] as a relay reports it over the WebSocket
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryReport {
+    pub request_id: String,
+    pub target: String,
+    pub status_code: Option<u16>,
+    pub response_headers: Option<String>,
+    pub response_body: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: Option<f64>,
+}
+
+/// A captured request set to replay once, or repeatedly on a cron schedule
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct ReplaySchedule {
+    pub id: String,
+    pub webhook_id: String,
+    pub request_id: String,
+    pub target: String,
+    /// Cron expression of a recurring schedule; absent for a one-off replay
+    pub cron: Option<String>,
+    /// JSON-encoded [`OutboundSigning`]; never sent back since it holds the secret
+    #[serde(skip)]
+    pub signing: Option<String>,
+    /// Absent once a one-off replay has run
+    pub next_run_at: Option<String>,
+    pub last_run_at: Option<String>,
+    pub last_status_code: Option<u16>,
+    pub last_error: Option<String>,
+    pub runs: i64,
+    pub created_at: String,
+}
+
+impl ReplaySchedule {
+    /// Decode the signing settings applied to each replay, if any.
+    pub fn signing(&self) -> Option<OutboundSigning> {
+        self.signing
+            .as_deref()
+            .and_then(|signing| serde_json::from_str(signing).ok())
+    }
+}
+
+/// A webhook and its lifecycle metadata (the token hash is never exposed)
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct Webhook {
+    pub id: String,
+    pub created_at: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    #[sqlx(json)]
+    pub tags: Vec<String>,
+    /// After this RFC 3339 instant ingest answers 410 Gone
+    pub expires_at: Option<String>,
+    /// While paused, ingest answers `paused_status` without storing anything
+    pub paused: bool,
+    pub paused_status: Option<u16>,
+    pub archived_at: Option<String>,
+}
+
+impl Webhook {
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.expires_at
+            .as_deref()
+            .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+            .is_some_and(|at| at <= now)
+    }
+}
+
 /// Represents a newly created webhook
 #[derive(Serialize, Debug, Clone)]
 pub struct NewWebhook {
@@ -52,6 +363,24 @@ pub struct WebhookEvent {
     pub body: String,
     pub query: String,
     pub created_at: String,
+    pub response_status: Option<u16>,
+    /// JSON-encoded headers we answered the sender with
+    pub response_headers: Option<String>,
+    pub response_body: Option<String>,
+    pub response_source: Option<String>,
+    pub config_version: Option<i64>,
+    pub latency_ms: Option<f64>,
+    pub signature_status: Option<String>,
+}
+
+/// Change to already captured requests, pushed to live dashboards
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DashboardEvent {
+    RequestDeleted { request_id: String },
+    RequestsCleared { include_pinned: bool },
+    RequestPinned { request_id: String, pinned: bool },
+    RequestDelivered { request_id: String, target: String, status_code: Option<u16>, error: Option<String> },
 }
 
 impl From<StoredRequest> for WebhookEvent {
@@ -64,6 +393,13 @@ impl From<StoredRequest> for WebhookEvent {
             body: req.body,
             query: req.query,
             created_at: req.created_at,
+            response_status: req.response_status,
+            response_headers: req.response_headers,
+            response_body: req.response_body,
+            response_source: req.response_source,
+            config_version: req.config_version,
+            latency_ms: req.latency_ms,
+            signature_status: req.signature_status,
         }
     }
 }
@@ -79,6 +415,7 @@ impl StoredRequest {
             body: "test-body".to_string(),
             query: "".to_string(),
             created_at: "2025-01-01T00:00:00Z".to_string(),
+            ..Default::default()
         }
     }
 }
@@ -0,0 +1,119 @@
+use anyhow::Context;
+use std::{path::PathBuf, time::Duration};
+
+use crate::egress::EgressPolicy;
+
+/// How replayed and forwarded requests are sent
+#[derive(Debug, Clone)]
+pub struct OutboundSettings {
+    /// `OUTBOUND_CONNECT_TIMEOUT_SECS`: 10 by default
+    pub connect_timeout: Duration,
+    /// `OUTBOUND_TIMEOUT_SECS`: limit on a whole exchange, 30 by default
+    pub timeout: Duration,
+    /// `OUTBOUND_MAX_REDIRECTS`: 10 by default; 0 reports the redirect itself instead of following it
+    pub max_redirects: usize,
+    /// `OUTBOUND_PROXY`: HTTP(S) proxy for every outbound request but those to local targets
+    /// with `insecure_tls`. Refused while the egress policy blocks private or extra address
+    /// ranges, which only the proxy could enforce; the always-blocked ranges, cloud metadata
+    /// included, are then only checked for IP literals, not for hostnames
+    pub proxy: Option<String>,
+    /// `OUTBOUND_CA_BUNDLE`: PEM file of extra root certificates, e.g. a local development CA
+    pub ca_bundle: Option<PathBuf>,
+    /// `OUTBOUND_INSECURE_TLS`: skip certificate checks for targets that resolve to
+    /// loopback or private addresses, such as a self-signed local consumer
+    pub insecure_tls: bool,
+    /// `OUTBOUND_USER_AGENT`: sent when the request being replayed has no User-Agent of its own
+    pub user_agent: String,
+}
+
+impl Default for OutboundSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(30),
+            max_redirects: 10,
+            proxy: None,
+            ca_bundle: None,
+            insecure_tls: false,
+            user_agent: concat!("webhook-tester/", env!("CARGO_PKG_VERSION")).to_string(),
+        }
+    }
+}
+
+impl OutboundSettings {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut settings = Self::default();
+
+        if let Ok(secs) = std::env::var("OUTBOUND_CONNECT_TIMEOUT_SECS") {
+            settings.connect_timeout = Duration::from_secs(secs.parse()?);
+        }
+        if let Ok(secs) = std::env::var("OUTBOUND_TIMEOUT_SECS") {
+            settings.timeout = Duration::from_secs(secs.parse()?);
+        }
+        if let Ok(max) = std::env::var("OUTBOUND_MAX_REDIRECTS") {
+            settings.max_redirects = max.parse()?;
+        }
+        settings.proxy = std::env::var("OUTBOUND_PROXY").ok().filter(|p| !p.is_empty());
+        settings.ca_bundle = std::env::var("OUTBOUND_CA_BUNDLE").ok().filter(|p| !p.is_empty()).map(PathBuf::from);
+        if let Ok(insecure) = std::env::var("OUTBOUND_INSECURE_TLS") {
+            settings.insecure_tls = insecure.parse()?;
+        }
+        if let Ok(user_agent) = std::env::var("OUTBOUND_USER_AGENT") {
+            settings.user_agent = user_agent;
+        }
+
+        Ok(settings)
+    }
+
+    /// The client every replay and forward goes through, enforcing `egress`
+    pub fn build_client(&self, egress: &EgressPolicy) -> anyhow::Result<reqwest::Client> {
+        Ok(self.builder(egress, true)?.build()?)
+    }
+
+    /// With `insecure_tls`, the client for local targets: it skips certificate checks and,
+    /// so that neither DNS nor a redirect can lead it elsewhere, only connects to loopback
+    /// and private addresses. It never goes through the proxy, which would resolve names itself
+    pub fn build_local_client(&self, egress: &EgressPolicy) -> anyhow::Result<Option<reqwest::Client>> {
+        if !self.insecure_tls {
+            return Ok(None);
+        }
+        let builder = self.builder(&egress.local_only(), false)?.danger_accept_invalid_certs(true);
+        Ok(Some(builder.build()?))
+    }
+
+    fn builder(&self, egress: &EgressPolicy, use_proxy: bool) -> anyhow::Result<reqwest::ClientBuilder> {
+        let mut builder = egress
+            .client_builder(self.max_redirects)
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout)
+            .user_agent(&self.user_agent);
+
+        if let Some(proxy) = self.proxy.as_ref().filter(|_| use_proxy) {
+            // The proxy resolves target names itself, so the addresses they lead to are never seen here
+            if egress.block_private || !egress.blocked_cidrs.is_empty() {
+                anyhow::bail!(
+                    "OUTBOUND_PROXY can't enforce the egress policy's address ranges; \
+                     set EGRESS_BLOCK_PRIVATE=false and leave EGRESS_BLOCKED_CIDRS empty to use a proxy, \
+                     and block private networks on the proxy instead"
+                );
+            }
+            eprintln!(
+                "WARNING: outbound requests go through OUTBOUND_PROXY {proxy}; target names are resolved by the proxy, \
+                 so only URLs and IP literals are checked against the egress policy. A hostname that resolves to \
+                 cloud metadata (169.254.169.254) or another always-blocked range is NOT refused: block those on the proxy"
+            );
+            builder = builder.proxy(reqwest::Proxy::all(proxy).with_context(|| format!("Invalid OUTBOUND_PROXY {proxy}"))?);
+        }
+
+        if let Some(path) = &self.ca_bundle {
+            let pem = std::fs::read(path).with_context(|| format!("Reading OUTBOUND_CA_BUNDLE {}", path.display()))?;
+            for certificate in reqwest::Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Parsing OUTBOUND_CA_BUNDLE {}", path.display()))?
+            {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        Ok(builder)
+    }
+}
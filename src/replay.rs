@@ -1,14 +1,102 @@
-use axum::{extract::{Path, State}, Json};
-use serde_json::json;
-use reqwest::{Client, header::{HeaderMap, HeaderName, HeaderValue}};
+use axum::{extract::{Path, State}, http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use reqwest::{Method, Url, header::{HeaderMap, HeaderName, HeaderValue}};
 use std::sync::{Arc, OnceLock};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::{AppState, models::StoredRequest};
+use crate::{AppState, auth::Credentials, egress, models::StoredRequest, signature::{self, OutboundSigning}};
 
 #[derive(serde::Deserialize)]
 pub struct ReplayPayload {
     target: String,
+    /// Re-sign the payload for the target instead of sending the original signature
+    signing: Option<OutboundSigning>,
+    /// Change the request before sending it; the edited copy is stored as a new request
+    edit: Option<RequestEdit>,
+}
+
+/// Changes made to a captured request before it is replayed
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RequestEdit {
+    pub method: Option<String>,
+    /// Headers to add, replacing any already present
+    pub set_headers: BTreeMap<String, String>,
+    pub remove_headers: Vec<String>,
+    /// Replaces the whole query string
+    pub query: Option<BTreeMap<String, String>>,
+    /// Replaces the whole body
+    pub body: Option<String>,
+    /// RFC 7396 merge patch for a JSON body, applied after `body`
+    pub merge_patch: Option<Value>,
+    /// RFC 6902 patch for a JSON body, applied last
+    pub json_patch: Option<json_patch::Patch>,
+}
+
+impl RequestEdit {
+    /// A new request derived from `original` with the edits applied
+    pub fn apply(&self, original: &StoredRequest) -> Result<StoredRequest, String> {
+        let mut headers = original.header_map();
+        let mut body = self.body.clone().unwrap_or_else(|| original.body.clone());
+        let replaces_body = self.body.is_some() || self.merge_patch.is_some() || self.json_patch.is_some();
+
+        let method = match &self.method {
+            Some(method) => {
+                let method = method.to_ascii_uppercase();
+                Method::from_bytes(method.as_bytes()).map_err(|_| format!("Invalid method: {method}"))?;
+                method
+            }
+            None => original.method.clone(),
+        };
+
+        // A new body is sent as written, so the original's compression no longer applies
+        if replaces_body && original.body_encoding.is_some() {
+            headers.remove("content-encoding");
+        }
+        for name in &self.remove_headers {
+            headers.remove(&name.to_ascii_lowercase());
+        }
+        for (name, value) in &self.set_headers {
+            headers.insert(name.to_ascii_lowercase(), value.clone());
+        }
+
+        if self.merge_patch.is_some() || self.json_patch.is_some() {
+            let mut document: Value = serde_json::from_str(&body).map_err(|e| format!("Body is not JSON, so it can't be patched: {e}"))?;
+            if let Some(merge_patch) = &self.merge_patch {
+                json_patch::merge(&mut document, merge_patch);
+            }
+            if let Some(patch) = &self.json_patch {
+                json_patch::patch(&mut document, patch).map_err(|e| format!("JSON patch failed: {e}"))?;
+            }
+            body = document.to_string();
+        }
+
+        // The captured length no longer describes an edited body
+        if body != original.body {
+            headers.remove("content-length");
+        }
+
+        let query = match &self.query {
+            Some(query) => serde_json::to_string(query).unwrap_or_default(),
+            None => original.query.clone(),
+        };
+
+        Ok(StoredRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            webhook_id: original.webhook_id.clone(),
+            method,
+            headers: serde_json::to_string(&headers).unwrap_or_default(),
+            body,
+            query,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            // An untouched body is still sent byte for byte, even if it isn't UTF-8
+            body_encoding: original.body_encoding.clone().filter(|_| !replaces_body),
+            raw_body: original.raw_body.clone().filter(|_| !replaces_body),
+            derived_from: Some(original.id.clone()),
+            ..Default::default()
+        })
+    }
 }
 
 pub type ForwardRequestFn = dyn Fn(&str, &StoredRequest) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), anyhow::Error>> + Send>> + Send + Sync;
@@ -18,31 +106,90 @@ pub static MOCK_FORWARD_REQUEST: OnceLock<Box<ForwardRequestFn>> = OnceLock::new
 pub async fn replay_request(
     Path(req_id): Path<String>,
     State(state): State<Arc<AppState>>,
+    credentials: Credentials,
     Json(payload): Json<ReplayPayload>,
-) -> Json<serde_json::Value> {
+) -> Response {
     let target_url = &payload.target;
     if target_url.is_empty() {
-        return Json(json!({"error": "Missing target URL"}));
+        return (StatusCode::BAD_REQUEST, Json(json!({"error": "Missing target URL"}))).into_response();
+    }
+    if let Err(refused) = state.settings.egress.screen_target(target_url) {
+        return refused.into_response();
     }
 
     // Fetch the stored request
     let stored_req_result = state.db.get_request(&req_id).await;
-    let stored_req: StoredRequest = match stored_req_result {
+    let mut stored_req: StoredRequest = match stored_req_result {
         Ok(req) => req,
-        Err(_) => return Json(json!({"error": "Request not found"})),
+        Err(_) => return (StatusCode::NOT_FOUND, Json(json!({"error": "Request not found"}))).into_response(),
     };
 
+    // Only the owner of the request's webhook may replay it
+    if let Err(err) = state.authorize(&stored_req.webhook_id, &credentials).await {
+        return err.into_response();
+    }
+
+    // Edited replays are recorded once they went out, linked to the original
+    let mut derived = None;
+    if let Some(edit) = &payload.edit {
+        stored_req = match edit.apply(&stored_req) {
+            Ok(edited) => edited,
+            Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": e}))).into_response(),
+        };
+        derived = Some(stored_req.clone());
+    }
+
+    // An edit that sets the query string adds it to the target; otherwise the target is used as given
+    let target_url = match payload.edit.as_ref().and_then(|edit| edit.query.as_ref()) {
+        Some(query) => match with_query(target_url, query) {
+            Ok(url) => url,
+            Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": e}))).into_response(),
+        },
+        None => target_url.clone(),
+    };
+
+    if let Some(signing) = &payload.signing {
+        if let Err(e) = signature::resign_request(signing, &mut stored_req, chrono::Utc::now().timestamp()) {
+            return (StatusCode::BAD_REQUEST, Json(json!({"error": e}))).into_response();
+        }
+    }
+
+    // The target couldn't be reached, or the policy refused the address it resolved to
+    let status_code = match send(&state, &target_url, &stored_req).await {
+        Ok(status_code) => status_code,
+        Err(e) => return (StatusCode::BAD_GATEWAY, Json(json!({"error": e}))).into_response(),
+    };
+
+    if let Some(derived) = &derived {
+        if let Err(e) = state.store_request(derived).await {
+            eprintln!("DB store error: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": "Database error"}))).into_response();
+        }
+        state.announce(derived).await;
+    }
+
+    let mut result = json!({"status": "ok", "request_id": derived.map(|derived| derived.id)});
+    if let Some(status_code) = status_code {
+        result["status_code"] = json!(status_code);
+    }
+    Json(result).into_response()
+}
+
+/// Send a stored request to `target` with its original method, headers and body.
+///
+/// Returns the status the target answered with, or `None` when the mock forwarder took the request.
+/// Targets the egress policy refuses are never contacted.
+pub async fn send(state: &AppState, target: &str, req: &StoredRequest) -> Result<Option<u16>, String> {
+    state.settings.egress.check_target(target)?;
+
     // If the mock is set, call it
     if let Some(mock) = MOCK_FORWARD_REQUEST.get() {
-        if let Err(e) = mock(target_url, &stored_req).await {
-            return Json(json!({"error": e.to_string()}));
-        }
-        return Json(json!({"status": "ok"}));
+        return mock(target, req).await.map(|_| None).map_err(|e| e.to_string());
     }
 
     // Convert headers JSON string -> HashMap -> HeaderMap
     let mut headers = HeaderMap::new();
-    if let Ok(map) = serde_json::from_str::<HashMap<String, String>>(&stored_req.headers) {
+    if let Ok(map) = serde_json::from_str::<HashMap<String, String>>(&req.headers) {
         for (key, value) in map {
             if let Ok(name) = key.parse::<HeaderName>() {
                 if let Ok(header_value) = value.parse::<HeaderValue>() {
@@ -55,18 +202,25 @@ pub async fn replay_request(
     }
 
     // Send the request
-    let client = Client::new();
-    let res = client.post(target_url)
+    let method = Method::from_bytes(req.method.as_bytes()).unwrap_or(Method::POST);
+    state
+        .client_for(target)
+        .await
+        .request(method, target)
         .headers(headers)
-        .body(stored_req.body.clone())
+        .body(req.raw_bytes())
         .send()
-        .await;
-
-    match res {
-        Ok(resp) => Json(json!({
-            "status": "ok",
-            "status_code": resp.status().as_u16()
-        })),
-        Err(e) => Json(json!({"error": e.to_string()})),
+        .await
+        .map(|resp| Some(resp.status().as_u16()))
+        .map_err(|e| egress::describe_error(&e))
+}
+
+/// `target` with `query` parameters added
+fn with_query(target: &str, query: &BTreeMap<String, String>) -> Result<String, String> {
+    if query.is_empty() {
+        return Ok(target.to_string());
     }
+    let mut url = Url::parse(target).map_err(|e| format!("Invalid target URL: {e}"))?;
+    url.query_pairs_mut().extend_pairs(query);
+    Ok(url.to_string())
 }
@@ -0,0 +1,410 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinSet;
+
+use crate::{
+    auth::Credentials,
+    db::Database,
+    models::StoredRequest,
+    replay,
+    search::SearchQuery,
+    signature::{self, OutboundSigning},
+    utils::parse_timestamp,
+    AppState,
+};
+
+/// Most requests a single job may replay
+pub const MAX_JOB_REQUESTS: usize = 1000;
+/// Most requests a job may have in flight at once
+pub const MAX_CONCURRENCY: usize = 32;
+/// Finished jobs kept for inspection; older ones are forgotten
+const MAX_FINISHED_JOBS: usize = 100;
+
+/// How a job spaces out the requests it replays
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayTiming {
+    /// Send each request as soon as a slot is free
+    #[default]
+    None,
+    /// Keep the gaps between the original captures, divided by `speed`
+    Original,
+}
+
+#[derive(Deserialize)]
+pub struct ReplayJobPayload {
+    target: String,
+    /// Requests to replay; with `q`, only those that also match the search
+    #[serde(default)]
+    ids: Vec<String>,
+    /// Search query selecting the requests to replay, see [`SearchQuery`]
+    q: Option<String>,
+    /// Requests in flight at once; 1 replays strictly in order
+    #[serde(default = "default_concurrency")]
+    concurrency: usize,
+    #[serde(default)]
+    timing: ReplayTiming,
+    /// With original timing, 2.0 replays twice as fast
+    #[serde(default = "default_speed")]
+    speed: f64,
+    /// Re-sign each payload for the target, as a single replay can
+    signing: Option<OutboundSigning>,
+}
+
+fn default_concurrency() -> usize {
+    1
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutcomeStatus {
+    /// Waiting for its turn
+    Pending,
+    Sending,
+    /// The target answered, whatever the status
+    Sent,
+    /// The request never got an answer
+    Failed,
+    /// The job was cancelled before the request was sent
+    Skipped,
+}
+
+/// What happened to one request of a job
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayOutcome {
+    pub request_id: String,
+    pub status: OutcomeStatus,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub duration_ms: Option<u64>,
+}
+
+/// A bulk replay and the outcome of each of its requests, in replay order
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayJob {
+    pub id: String,
+    pub webhook_id: String,
+    pub target: String,
+    pub status: JobStatus,
+    pub concurrency: usize,
+    pub timing: ReplayTiming,
+    pub speed: f64,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+    pub outcomes: Vec<ReplayOutcome>,
+}
+
+struct JobHandle {
+    job: Mutex<ReplayJob>,
+    cancel: watch::Sender<bool>,
+}
+
+impl JobHandle {
+    fn snapshot(&self) -> ReplayJob {
+        self.job.lock().unwrap().clone()
+    }
+
+    fn update(&self, index: usize, change: impl FnOnce(&mut ReplayOutcome)) {
+        change(&mut self.job.lock().unwrap().outcomes[index]);
+    }
+}
+
+/// Bulk replay jobs of this process, running and recently finished
+#[derive(Clone, Default)]
+pub struct ReplayJobs {
+    jobs: Arc<Mutex<HashMap<String, Arc<JobHandle>>>>,
+}
+
+impl ReplayJobs {
+    fn get(&self, id: &str) -> Option<Arc<JobHandle>> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    /// Jobs of a webhook, newest first
+    pub fn list(&self, webhook_id: &str) -> Vec<ReplayJob> {
+        let mut jobs: Vec<ReplayJob> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|handle| handle.snapshot())
+            .filter(|job| job.webhook_id == webhook_id)
+            .collect();
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        jobs
+    }
+
+    fn insert(&self, handle: Arc<JobHandle>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = handle.snapshot().id;
+        jobs.insert(id, handle);
+
+        let mut finished: Vec<(String, String)> = jobs
+            .iter()
+            .map(|(id, handle)| (id, handle.snapshot()))
+            .filter(|(_, job)| job.status != JobStatus::Running)
+            .map(|(id, job)| (job.created_at, id.clone()))
+            .collect();
+        if finished.len() > MAX_FINISHED_JOBS {
+            finished.sort();
+            for (_, id) in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+                jobs.remove(id);
+            }
+        }
+    }
+}
+
+/// Start replaying a webhook's requests, oldest first, in the background
+pub async fn create_replay_job(
+    Path(webhook_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Json(payload): Json<ReplayJobPayload>,
+) -> Response {
+    if let Err(err) = state.authorize(&webhook_id, &credentials).await {
+        return err.into_response();
+    }
+
+    if let Err(refused) = state.settings.egress.screen_target(&payload.target) {
+        return refused.into_response();
+    }
+    if payload.concurrency == 0 || payload.concurrency > MAX_CONCURRENCY {
+        return bad_request(format!("concurrency must be between 1 and {MAX_CONCURRENCY}"));
+    }
+    if !payload.speed.is_finite() || payload.speed <= 0.0 {
+        return bad_request("speed must be a positive number".to_string());
+    }
+
+    let requests = match select_requests(&state.db, &webhook_id, &payload).await {
+        Ok(requests) => requests,
+        Err(response) => return response,
+    };
+    if requests.is_empty() {
+        return bad_request("No requests to replay".to_string());
+    }
+    if requests.len() > MAX_JOB_REQUESTS {
+        return bad_request(format!("A job replays at most {MAX_JOB_REQUESTS} requests, more were selected"));
+    }
+
+    let job = ReplayJob {
+        id: uuid::Uuid::new_v4().to_string(),
+        webhook_id,
+        target: payload.target.clone(),
+        status: JobStatus::Running,
+        concurrency: payload.concurrency,
+        timing: payload.timing,
+        speed: payload.speed,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        finished_at: None,
+        outcomes: requests
+            .iter()
+            .map(|req| ReplayOutcome {
+                request_id: req.id.clone(),
+                status: OutcomeStatus::Pending,
+                status_code: None,
+                error: None,
+                duration_ms: None,
+            })
+            .collect(),
+    };
+
+    let handle = Arc::new(JobHandle {
+        job: Mutex::new(job),
+        cancel: watch::channel(false).0,
+    });
+    state.replay_jobs.insert(handle.clone());
+    tokio::spawn(run(state.clone(), handle.clone(), requests, payload));
+
+    (StatusCode::ACCEPTED, Json(handle.snapshot())).into_response()
+}
+
+/// List a webhook's replay jobs, newest first
+pub async fn list_replay_jobs(
+    Path(webhook_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    if let Err(err) = state.authorize(&webhook_id, &credentials).await {
+        return err.into_response();
+    }
+
+    Json(state.replay_jobs.list(&webhook_id)).into_response()
+}
+
+/// Progress of a replay job, with the outcome of each request so far
+pub async fn get_replay_job(
+    Path(job_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    match load_job(&state, &job_id, &credentials).await {
+        Ok(handle) => Json(handle.snapshot()).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Stop a replay job; requests already on their way still finish
+pub async fn cancel_replay_job(
+    Path(job_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    match load_job(&state, &job_id, &credentials).await {
+        Ok(handle) => {
+            handle.cancel.send_replace(true);
+            Json(handle.snapshot()).into_response()
+        }
+        Err(response) => response,
+    }
+}
+
+async fn load_job(state: &AppState, job_id: &str, credentials: &Credentials) -> Result<Arc<JobHandle>, Response> {
+    let Some(handle) = state.replay_jobs.get(job_id) else {
+        return Err((StatusCode::NOT_FOUND, Json(json!({"error": "Replay job not found"}))).into_response());
+    };
+
+    let webhook_id = handle.snapshot().webhook_id;
+    state
+        .authorize(&webhook_id, credentials)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    Ok(handle)
+}
+
+fn bad_request(message: String) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({"error": message}))).into_response()
+}
+
+/// The requests a job replays, oldest first
+async fn select_requests(db: &Database, webhook_id: &str, payload: &ReplayJobPayload) -> Result<Vec<StoredRequest>, Response> {
+    let db_error = |e: sqlx::Error| {
+        eprintln!("DB error: {e}");
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": "Database error"}))).into_response()
+    };
+
+    // One more than allowed, so an oversized selection is reported rather than cut short
+    let limit = MAX_JOB_REQUESTS as u32 + 1;
+    let Some(q) = &payload.q else {
+        return db.oldest_requests(webhook_id, &payload.ids, limit).await.map_err(db_error);
+    };
+
+    let search: SearchQuery = q.parse().map_err(|e: anyhow::Error| bad_request(e.to_string()))?;
+    let mut requests = db
+        .search_requests(webhook_id, &search, limit)
+        .await
+        .map_err(db_error)?;
+    if !payload.ids.is_empty() {
+        requests.retain(|req| payload.ids.contains(&req.id));
+    }
+    requests.reverse();
+    Ok(requests)
+}
+
+/// When each request is due, measured from the start of the job
+fn schedule(requests: &[StoredRequest], timing: ReplayTiming, speed: f64) -> Vec<Duration> {
+    let captured: Vec<_> = requests.iter().map(|req| parse_timestamp(&req.created_at)).collect();
+    let first = captured.iter().flatten().next().copied();
+
+    let mut due = Duration::ZERO;
+    captured
+        .iter()
+        .map(|at| {
+            if let (ReplayTiming::Original, Some(first), Some(at)) = (timing, first, at) {
+                let gap = (*at - first).to_std().unwrap_or_default();
+                // A request without a readable capture time goes right after the one before
+                due = due.max(gap.div_f64(speed));
+            }
+            due
+        })
+        .collect()
+}
+
+async fn run(state: Arc<AppState>, handle: Arc<JobHandle>, requests: Vec<StoredRequest>, payload: ReplayJobPayload) {
+    let schedule = schedule(&requests, payload.timing, payload.speed);
+    let slots = Arc::new(Semaphore::new(payload.concurrency));
+    let signing = Arc::new(payload.signing);
+    let target: Arc<str> = payload.target.into();
+    let mut cancelled = handle.cancel.subscribe();
+    let start = tokio::time::Instant::now();
+    let mut in_flight = JoinSet::new();
+    let mut stopped = false;
+
+    for (index, (mut req, due)) in requests.into_iter().zip(schedule).enumerate() {
+        let slot = tokio::select! {
+            biased;
+            _ = cancelled.wait_for(|cancelled| *cancelled) => {
+                stopped = true;
+                break;
+            }
+            slot = async {
+                tokio::time::sleep_until(start + due).await;
+                slots.clone().acquire_owned().await
+            } => slot.expect("the semaphore is never closed"),
+        };
+
+        let handle = handle.clone();
+        let signing = signing.clone();
+        let target = target.clone();
+        let state = state.clone();
+        handle.update(index, |outcome| outcome.status = OutcomeStatus::Sending);
+        in_flight.spawn(async move {
+            let _slot = slot;
+            let started = Instant::now();
+            let result = match signing.as_ref() {
+                Some(signing) => signature::resign_request(signing, &mut req, chrono::Utc::now().timestamp()),
+                None => Ok(()),
+            };
+            let result = match result {
+                Ok(()) => replay::send(&state, &target, &req).await,
+                Err(e) => Err(e),
+            };
+
+            handle.update(index, |outcome| {
+                outcome.duration_ms = Some(started.elapsed().as_millis() as u64);
+                match result {
+                    Ok(status_code) => {
+                        outcome.status = OutcomeStatus::Sent;
+                        outcome.status_code = status_code;
+                    }
+                    Err(e) => {
+                        outcome.status = OutcomeStatus::Failed;
+                        outcome.error = Some(e);
+                    }
+                }
+            });
+        });
+    }
+
+    while in_flight.join_next().await.is_some() {}
+
+    let mut job = handle.job.lock().unwrap();
+    job.status = if stopped { JobStatus::Cancelled } else { JobStatus::Completed };
+    job.finished_at = Some(chrono::Utc::now().to_rfc3339());
+    for outcome in &mut job.outcomes {
+        if outcome.status == OutcomeStatus::Pending {
+            outcome.status = OutcomeStatus::Skipped;
+        }
+    }
+}
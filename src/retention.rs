@@ -0,0 +1,23 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::db::Database;
+
+/// How often the retention sweep runs
+const SWEEP_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Periodically delete unpinned requests older than `retention_hours`.
+pub fn spawn_retention(db: Arc<Database>, retention_hours: u64) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let cutoff = chrono::Utc::now() - chrono::Duration::hours(retention_hours as i64);
+            match db.purge_requests_before(cutoff).await {
+                Ok(0) => {}
+                Ok(purged) => println!("Retention: purged {purged} request(s) older than {retention_hours}h"),
+                Err(err) => eprintln!("Retention error: {err}"),
+            }
+        }
+    })
+}
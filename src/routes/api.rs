@@ -0,0 +1,649 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::{
+    auth::Credentials,
+    body::{self, ParsedBody},
+    codegen::{self, CodeTarget},
+    export::{self, ExportFormat},
+    import::{self, ImportFormat},
+    models::{DashboardEvent, StoredRequest, Webhook},
+    routes::ws,
+    search::SearchQuery,
+    AppState,
+};
+
+/// List every captured request for a webhook, newest first, with the response we sent
+pub async fn list_requests(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    if let Err(err) = state.authorize(&id, &credentials).await {
+        return err.into_response();
+    }
+
+    Json(state.get_requests(&id).await).into_response()
+}
+
+const DEFAULT_SEARCH_LIMIT: u32 = 100;
+const MAX_SEARCH_LIMIT: u32 = 1000;
+
+#[derive(Deserialize)]
+pub struct SearchRequestsQuery {
+    #[serde(default)]
+    q: String,
+    limit: Option<u32>,
+}
+
+/// Search a webhook's captured requests; see [`SearchQuery`] for the syntax
+pub async fn search_requests(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Query(params): Query<SearchRequestsQuery>,
+) -> Response {
+    if let Err(err) = state.authorize(&id, &credentials).await {
+        return err.into_response();
+    }
+
+    let search: SearchQuery = match params.q.parse() {
+        Ok(search) => search,
+        Err(err) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": err.to_string()})),
+            )
+                .into_response()
+        }
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+
+    match state.db.search_requests(&id, &search, limit).await {
+        Ok(requests) => Json(requests).into_response(),
+        Err(err) => internal_error(err),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    /// Comma-separated request IDs to export instead of the whole history
+    ids: Option<String>,
+}
+
+/// Download a webhook's requests as HAR, NDJSON or a `curl` script
+pub async fn export_requests(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    if let Err(response) = load_webhook(&state, &id, &credentials).await {
+        return response;
+    }
+
+    let ids: Vec<String> = query
+        .ids
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .collect();
+
+    let filename = format!("webhook-{id}.{}", query.format.extension());
+    export_response(&state, id, ids, query.format, &filename)
+}
+
+/// Download a single captured request as HAR, NDJSON or a `curl` script
+pub async fn export_request(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let req = match load_request(&state, &req_id, &credentials).await {
+        Ok(req) => req,
+        Err(response) => return response,
+    };
+
+    let filename = format!("request-{req_id}.{}", query.format.extension());
+    export_response(&state, req.webhook_id, vec![req_id], query.format, &filename)
+}
+
+fn export_response(state: &AppState, webhook_id: String, ids: Vec<String>, format: ExportFormat, filename: &str) -> Response {
+    let filename = filename.replace(|c: char| !(c.is_ascii_alphanumeric() || "-_.".contains(c)), "_");
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+        ],
+        export::export_body(state.db.clone(), webhook_id, ids, format),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    /// Detected from the contents when omitted
+    format: Option<ImportFormat>,
+}
+
+/// Load requests from a HAR, NDJSON or `curl` file into a webhook's history
+pub async fn import_requests(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Response {
+    if let Err(response) = load_webhook(&state, &id, &credentials).await {
+        return response;
+    }
+
+    let requests = match import::parse(&body, query.format, &id) {
+        Ok(requests) => requests,
+        Err(err) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("{err:#}")})),
+            )
+                .into_response()
+        }
+    };
+
+    if let Err(err) = state.db.import_requests(&requests).await {
+        return internal_error(err);
+    }
+
+    for req in &requests {
+        state.announce(req).await;
+    }
+    Json(json!({"imported": requests.len()})).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct CodeQuery {
+    lang: Option<CodeTarget>,
+}
+
+/// Render a captured request as code that resends it: one snippet as text
+/// with `?lang=`, otherwise every snippet as a JSON object
+pub async fn request_code(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Query(query): Query<CodeQuery>,
+) -> Response {
+    let req = match load_request(&state, &req_id, &credentials).await {
+        Ok(req) => req,
+        Err(response) => return response,
+    };
+
+    match query.lang {
+        Some(target) => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            codegen::render(&req, target),
+        )
+            .into_response(),
+        None => {
+            let snippets: serde_json::Map<String, serde_json::Value> = CodeTarget::ALL
+                .into_iter()
+                .map(|target| (target.as_str().to_string(), json!(codegen::render(&req, target))))
+                .collect();
+            Json(snippets).into_response()
+        }
+    }
+}
+
+/// A captured body broken down by its content type: JSON, form fields, multipart
+/// parts, indented XML or text, decompressed when it was sent compressed
+pub async fn request_body(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    let req = match load_request(&state, &req_id, &credentials).await {
+        Ok(req) => req,
+        Err(response) => return response,
+    };
+
+    let bytes = decoded_body(&req).await;
+    let content_type = req.header_map().get("content-type").cloned();
+    let mut parsed = body::parse(content_type.as_deref(), &bytes).await;
+
+    // Link file uploads to their stored copies
+    if let ParsedBody::Multipart { parts } = &mut parsed {
+        match state.db.list_request_files(&req.id).await {
+            Ok(files) => {
+                for file in files {
+                    if let Some(part) = parts.get_mut(file.part_index as usize) {
+                        part.file_id = Some(file.id);
+                    }
+                }
+            }
+            Err(err) => eprintln!("Could not list files of request {}: {err}", req.id),
+        }
+    }
+
+    Json(json!({
+        "content_type": content_type,
+        "encoding": req.body_encoding,
+        "size": bytes.len(),
+        "raw_size": req.raw_bytes().len(),
+        "body": parsed,
+    }))
+    .into_response()
+}
+
+/// Download a captured body exactly as it was received, still compressed if it was
+pub async fn raw_request_body(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    let req = match load_request(&state, &req_id, &credentials).await {
+        Ok(req) => req,
+        Err(response) => return response,
+    };
+
+    let extension = match req.body_encoding.as_deref() {
+        Some("gzip" | "x-gzip") => "gz",
+        Some("br") => "br",
+        _ => "bin",
+    };
+    (
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{req_id}.{extension}\"")),
+        ],
+        req.raw_bytes(),
+    )
+        .into_response()
+}
+
+/// Files uploaded with a captured request, without their contents
+pub async fn list_request_files(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    if let Err(response) = load_request(&state, &req_id, &credentials).await {
+        return response;
+    }
+
+    match state.db.list_request_files(&req_id).await {
+        Ok(files) => Json(files).into_response(),
+        Err(err) => internal_error(err),
+    }
+}
+
+/// Download one file uploaded with a captured request
+pub async fn download_request_file(
+    Path((req_id, file_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    let req = match load_request(&state, &req_id, &credentials).await {
+        Ok(req) => req,
+        Err(response) => return response,
+    };
+
+    let file = match state.db.get_request_file(&req_id, &file_id).await {
+        Ok(Some(file)) => file,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json(json!({"error": "File not found"}))).into_response(),
+        Err(err) => return internal_error(err),
+    };
+
+    // The contents are cut out of the captured body rather than stored twice
+    let content_type = req.header_map().get("content-type").cloned().unwrap_or_default();
+    let bytes = decoded_body(&req).await;
+    let data = match body::multipart_parts(&content_type, &bytes)
+        .await
+        .and_then(|parts| parts.into_iter().nth(file.part_index as usize))
+    {
+        Some((_, data)) => data,
+        None => return (StatusCode::NOT_FOUND, Json(json!({"error": "File not found"}))).into_response(),
+    };
+
+    // Always an attachment, so an uploaded page can't run on the dashboard's origin
+    let filename: String = file
+        .filename
+        .chars()
+        .map(|c| if c.is_control() || c == '"' || c == '\\' { '_' } else { c })
+        .collect();
+    (
+        [
+            (header::CONTENT_TYPE, file.content_type.unwrap_or_else(|| "application/octet-stream".to_string())),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        ],
+        data,
+    )
+        .into_response()
+}
+
+/// A captured body as it was sent, decompressed when it was sent compressed
+async fn decoded_body(req: &StoredRequest) -> Vec<u8> {
+    let raw = req.raw_bytes();
+    // Bodies that aren't UTF-8 once decoded are only kept raw
+    match &req.raw_body {
+        Some(_) => body::decode_content_async(req.body_encoding.as_deref(), raw.clone().into())
+            .await
+            .map(|d| d.bytes)
+            .unwrap_or_else(|_| raw),
+        None => req.body.clone().into_bytes(),
+    }
+}
+
+/// Fetch a single captured request and its recorded response
+pub async fn get_request(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    match load_request(&state, &req_id, &credentials).await {
+        Ok(req) => Json(req).into_response(),
+        Err(response) => response,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListWebhooksQuery {
+    #[serde(default)]
+    include_archived: bool,
+}
+
+/// List webhooks with their metadata, hiding archived ones unless asked
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Query(query): Query<ListWebhooksQuery>,
+) -> Response {
+    if let Err(err) = state.authorize_admin(&credentials) {
+        return err.into_response();
+    }
+
+    match state.db.list_webhooks(query.include_archived).await {
+        Ok(webhooks) => Json(webhooks).into_response(),
+        Err(err) => internal_error(err),
+    }
+}
+
+/// Fetch a single webhook's metadata
+pub async fn get_webhook(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    match load_webhook(&state, &id, &credentials).await {
+        Ok(webhook) => Json(webhook).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Fields that can be changed on a webhook; omitted fields are left alone
+#[derive(Deserialize)]
+pub struct UpdateWebhookPayload {
+    name: Option<String>,
+    description: Option<String>,
+    tags: Option<Vec<String>>,
+    /// RFC 3339 instant, or an empty string to clear the expiry
+    expires_at: Option<String>,
+    paused: Option<bool>,
+    /// Status ingest answers with while paused (503 by default)
+    paused_status: Option<u16>,
+    archived: Option<bool>,
+}
+
+/// Rename, describe, tag, expire, pause or archive a webhook
+pub async fn update_webhook(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Json(payload): Json<UpdateWebhookPayload>,
+) -> Response {
+    let mut webhook = match load_webhook(&state, &id, &credentials).await {
+        Ok(webhook) => webhook,
+        Err(response) => return response,
+    };
+
+    if let Some(name) = payload.name {
+        webhook.name = Some(name).filter(|n| !n.is_empty());
+    }
+    if let Some(description) = payload.description {
+        webhook.description = Some(description).filter(|d| !d.is_empty());
+    }
+    if let Some(tags) = payload.tags {
+        webhook.tags = tags;
+    }
+    if let Some(expires_at) = payload.expires_at {
+        if !expires_at.is_empty() && chrono::DateTime::parse_from_rfc3339(&expires_at).is_err() {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "expires_at must be an RFC 3339 timestamp"})),
+            )
+                .into_response();
+        }
+        webhook.expires_at = Some(expires_at).filter(|e| !e.is_empty());
+    }
+    if let Some(paused) = payload.paused {
+        webhook.paused = paused;
+    }
+    if let Some(paused_status) = payload.paused_status {
+        if !(100..=599).contains(&paused_status) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "paused_status must be an HTTP status code between 100 and 599"})),
+            )
+                .into_response();
+        }
+        webhook.paused_status = Some(paused_status);
+    }
+    if let Some(archived) = payload.archived {
+        webhook.archived_at = match (archived, webhook.archived_at.take()) {
+            (true, Some(at)) => Some(at),
+            (true, None) => Some(chrono::Utc::now().to_rfc3339()),
+            (false, _) => None,
+        };
+    }
+
+    if let Err(err) = state.db.update_webhook(&webhook).await {
+        return internal_error(err);
+    }
+
+    Json(webhook).into_response()
+}
+
+/// Delete a webhook together with all its requests and configuration
+pub async fn delete_webhook(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    if let Err(response) = load_webhook(&state, &id, &credentials).await {
+        return response;
+    }
+
+    match state.db.delete_webhook(&id).await {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => internal_error(err),
+    }
+}
+
+/// Authorize the caller and fetch the webhook, or build the error response
+async fn load_webhook(state: &AppState, id: &str, credentials: &Credentials) -> Result<Webhook, Response> {
+    state
+        .authorize(id, credentials)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    match state.db.get_webhook(id).await {
+        Ok(Some(webhook)) => Ok(webhook),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Webhook not found"})),
+        )
+            .into_response()),
+        Err(err) => Err(internal_error(err)),
+    }
+}
+
+fn internal_error(err: sqlx::Error) -> Response {
+    eprintln!("DB error: {err}");
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"error": "Database error"})),
+    )
+        .into_response()
+}
+
+/// Delete a single captured request
+pub async fn delete_request(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    let req = match load_request(&state, &req_id, &credentials).await {
+        Ok(req) => req,
+        Err(response) => return response,
+    };
+
+    if let Err(err) = state.db.delete_request(&req_id).await {
+        return internal_error(err);
+    }
+
+    ws::broadcast_event(&req.webhook_id, &DashboardEvent::RequestDeleted { request_id: req_id }).await;
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[derive(Deserialize)]
+pub struct ClearRequestsQuery {
+    #[serde(default)]
+    include_pinned: bool,
+}
+
+/// Delete all of a webhook's captured requests; pinned ones survive unless `include_pinned=true`
+pub async fn clear_requests(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Query(query): Query<ClearRequestsQuery>,
+) -> Response {
+    if let Err(err) = state.authorize(&id, &credentials).await {
+        return err.into_response();
+    }
+
+    match state.db.clear_requests(&id, query.include_pinned).await {
+        Ok(deleted) => {
+            let event = DashboardEvent::RequestsCleared { include_pinned: query.include_pinned };
+            ws::broadcast_event(&id, &event).await;
+            Json(json!({"deleted": deleted})).into_response()
+        }
+        Err(err) => internal_error(err),
+    }
+}
+
+/// Pin a captured request so retention never purges it
+pub async fn pin_request(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    set_pinned(state, req_id, credentials, true).await
+}
+
+/// Unpin a captured request
+pub async fn unpin_request(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    set_pinned(state, req_id, credentials, false).await
+}
+
+async fn set_pinned(state: Arc<AppState>, req_id: String, credentials: Credentials, pinned: bool) -> Response {
+    let req = match load_request(&state, &req_id, &credentials).await {
+        Ok(req) => req,
+        Err(response) => return response,
+    };
+
+    if let Err(err) = state.db.set_pinned(&req_id, pinned).await {
+        return internal_error(err);
+    }
+
+    ws::broadcast_event(&req.webhook_id, &DashboardEvent::RequestPinned { request_id: req_id, pinned }).await;
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// Validate a sample payload against the webhook's schemas without capturing it, for
+/// contract tests against a provider's documented payloads
+pub async fn validate_payload(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Err(err) = state.authorize(&id, &credentials).await {
+        return err.into_response();
+    }
+
+    let config = match state.db.get_response_config(&id).await {
+        Ok(config) => config,
+        Err(err) => return internal_error(err),
+    };
+    let Some(verdict) = state.schemas.validate(&config, &headers, &body) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "No payload schema is configured for this webhook"})),
+        )
+            .into_response();
+    };
+
+    Json(json!({"status": verdict.status(), "errors": verdict.errors()})).into_response()
+}
+
+/// What relays got back when they delivered a captured request locally, newest first
+pub async fn list_deliveries(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    if let Err(response) = load_request(&state, &req_id, &credentials).await {
+        return response;
+    }
+
+    match state.db.list_deliveries(&req_id).await {
+        Ok(deliveries) => Json(deliveries).into_response(),
+        Err(err) => internal_error(err),
+    }
+}
+
+/// Fetch a captured request and authorize the caller against its webhook
+pub(crate) async fn load_request(state: &AppState, req_id: &str, credentials: &Credentials) -> Result<StoredRequest, Response> {
+    let req = state.db.get_request(req_id).await.map_err(|_| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Request not found"})),
+        )
+            .into_response()
+    })?;
+
+    state
+        .authorize(&req.webhook_id, credentials)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    Ok(req)
+}
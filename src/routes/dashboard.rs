@@ -1,9 +1,9 @@
 use axum::{
     extract::{Path, State},
-    response::{Html, IntoResponse},
+    response::{Html, IntoResponse, Response},
 };
 use std::sync::Arc;
-use crate::{AppState, models::StoredRequest};
+use crate::{AppState, auth::Credentials, models::StoredRequest};
 use serde_json;
 
 /// Helper to fetch all requests for a given webhook.
@@ -19,14 +19,29 @@ impl AppState {
     }
 }
 
+/// Serialize a value for embedding in an inline `<script>` block
+fn script_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value)
+        .unwrap_or_else(|_| "null".to_string())
+        .replace('<', "\\u003c")
+}
+
 /// Dashboard handler
 pub async fn dashboard_handler(
     Path(id): Path<String>,
     State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
+    credentials: Credentials,
+) -> Response {
+    if let Err(err) = state.authorize(&id, &credentials).await {
+        return err.into_response();
+    }
+
+    // The page reuses the token for its WebSocket and replay calls
+    let token_json = script_json(&credentials.0.unwrap_or_default());
+
     // Fetch stored requests
     let requests = state.get_requests(&id).await;
-    let requests_json = serde_json::to_string(&requests).unwrap_or_else(|_| "[]".to_string());
+    let requests_json = script_json(&requests);
 
     // Render HTML
     let html = format!(
@@ -48,28 +63,141 @@ pub async fn dashboard_handler(
     <div class="flex items-center justify-between mb-4">
         <p class="text-sm">
             Total requests: <span id="count" class="font-semibold">0</span>
+            <button id="clear-btn" class="ml-4 text-xs text-red-600 underline">Clear unpinned</button>
+            <span class="ml-4 text-xs text-gray-500">Export:
+                <button class="export-link text-blue-600 underline" data-format="har">HAR</button> ·
+                <button class="export-link text-blue-600 underline" data-format="ndjson">NDJSON</button> ·
+                <button class="export-link text-blue-600 underline" data-format="curl">curl</button>
+            </span>
         </p>
         <span id="ws-status" class="text-xs px-2 py-1 rounded bg-gray-300 text-gray-700">
             Connecting…
         </span>
     </div>
 
+    <form id="search-form" class="flex gap-2 mb-1">
+        <input id="search-input" type="text" class="flex-1 px-3 py-2 border rounded text-sm font-mono"
+               placeholder='order_id method:POST header:x-github-event=push json:data.id=12345 status:5xx after:24h'>
+        <button class="bg-blue-500 text-white px-4 py-2 rounded text-sm">Search</button>
+        <button type="button" id="search-clear" class="text-sm text-gray-600 underline">Clear</button>
+    </form>
+    <p id="search-status" class="text-xs text-gray-500 mb-4"></p>
+
     <div id="requests" class="flex flex-col gap-4"></div>
 </div>
 
 <script>
 const WEBHOOK_ID = "{id}";
+const TOKEN = {token_json};
 const INITIAL_REQUESTS = {requests_json};
 const container = document.getElementById("requests");
 const countEl = document.getElementById("count");
 const statusEl = document.getElementById("ws-status");
+const searchInput = document.getElementById("search-input");
+const searchStatus = document.getElementById("search-status");
+// Live requests are held back while search results are on screen
+let searching = false;
 
 function updateCount() {{
     countEl.textContent = container.children.length;
 }}
 
+function authHeaders() {{
+    return {{ 'Authorization': `Bearer ${{TOKEN}}` }};
+}}
+
+// Downloads go through fetch so the token stays in a header, not in the URL
+async function download(url) {{
+    const res = await fetch(url, {{ headers: authHeaders() }});
+    if (!res.ok) return console.error("Download failed:", url, res.status);
+    const disposition = res.headers.get("content-disposition") || "";
+    const link = document.createElement("a");
+    link.href = URL.createObjectURL(await res.blob());
+    link.download = /filename="([^"]*)"/.exec(disposition)?.[1] || "download";
+    link.click();
+    URL.revokeObjectURL(link.href);
+}}
+
+function deleteRequest(id) {{
+    fetch(`/api/requests/${{id}}`, {{ method: 'DELETE', headers: authHeaders() }})
+        .then(res => {{ if (!res.ok) console.error("Failed to delete request:", id); }})
+        .catch(err => console.error("Delete error:", err));
+}}
+
+function togglePin(id, pinned) {{
+    fetch(`/api/requests/${{id}}/pin`, {{ method: pinned ? 'DELETE' : 'PUT', headers: authHeaders() }})
+        .then(res => {{ if (!res.ok) console.error("Failed to pin request:", id); }})
+        .catch(err => console.error("Pin error:", err));
+}}
+
+function setPinned(el, pinned) {{
+    el.dataset.pinned = pinned ? "1" : "";
+    const btn = el.querySelector(".pin-btn");
+    btn.textContent = pinned ? "★ Pinned" : "☆ Pin";
+    btn.className = "pin-btn text-xs underline " + (pinned ? "text-yellow-600" : "text-gray-500");
+}}
+
+document.getElementById("clear-btn").onclick = () => {{
+    if (!confirm("Delete all unpinned requests?")) return;
+    fetch(`/api/webhooks/${{WEBHOOK_ID}}/requests`, {{ method: 'DELETE', headers: authHeaders() }})
+        .catch(err => console.error("Clear error:", err));
+}};
+
+// Export everything, or just the search results on screen
+document.querySelectorAll(".export-link").forEach(link => {{
+    link.onclick = (e) => {{
+        e.preventDefault();
+        const params = new URLSearchParams({{ format: link.dataset.format }});
+        if (searching) params.set("ids", [...container.children].map(el => el.dataset.id).join(","));
+        download(`/api/webhooks/${{WEBHOOK_ID}}/export?${{params}}`).catch(err => console.error("Export error:", err));
+    }};
+}});
+
+// Apply a change pushed by the server to requests already on the page
+function applyEvent(data) {{
+    if (data.event === "request_deleted") {{
+        container.querySelector(`[data-id="${{CSS.escape(data.request_id)}}"]`)?.remove();
+    }} else if (data.event === "requests_cleared") {{
+        [...container.children]
+            .filter(el => data.include_pinned || !el.dataset.pinned)
+            .forEach(el => el.remove());
+    }} else if (data.event === "request_pinned") {{
+        const el = container.querySelector(`[data-id="${{CSS.escape(data.request_id)}}"]`);
+        if (el) setPinned(el, data.pinned);
+    }} else if (data.event === "request_delivered") {{
+        const meta = container.querySelector(`[data-id="${{CSS.escape(data.request_id)}}"] .delivery-meta`);
+        if (meta) {{
+            const ok = data.status_code != null && data.status_code < 400;
+            meta.className = "delivery-meta ml-2 " + (ok ? "text-green-600" : "text-red-600");
+            meta.textContent = data.status_code != null
+                ? `relayed → ${{data.status_code}}`
+                : `relay failed: ${{data.error || "no response"}}`;
+            meta.title = data.target;
+        }}
+    }}
+    updateCount();
+}}
+
+// Show a snippet that resends the request and put it on the clipboard
+async function copyAs(el, id, select) {{
+    const lang = select.value;
+    select.value = "";
+    if (!lang) return;
+    try {{
+        const res = await fetch(`/api/requests/${{id}}/code?lang=${{lang}}`, {{ headers: authHeaders() }});
+        if (!res.ok) return console.error("Failed to render request:", id);
+        const code = await res.text();
+        const snippet = el.querySelector(".code-snippet");
+        snippet.textContent = code;
+        snippet.classList.remove("hidden");
+        await navigator.clipboard.writeText(code);
+    }} catch (err) {{
+        console.error("Copy error:", err);
+    }}
+}}
+
 function replayRequest(id) {{
-    fetch(`/replay/${{id}}`, {{ method: 'POST', headers: {{ 'Content-Type': 'application/json' }}, body: JSON.stringify({{ target: "" }}) }})
+    fetch(`/replay/${{id}}`, {{ method: 'POST', headers: {{ 'Content-Type': 'application/json', 'Authorization': `Bearer ${{TOKEN}}` }}, body: JSON.stringify({{ target: "" }}) }})
         .then(res => {{
             if(res.ok) console.log("Request replayed:", id);
             else console.error("Failed to replay request:", id);
@@ -77,9 +205,116 @@ function replayRequest(id) {{
         .catch(err => console.error("Replay error:", err));
 }}
 
+// Headers and query are stored as JSON objects; show them one "name: value" per line
+function keyValueLines(json) {{
+    try {{
+        return Object.entries(JSON.parse(json || "{{}}")).map(([k, v]) => `${{k}}: ${{v}}`).join("\n");
+    }} catch (e) {{
+        return json || "";
+    }}
+}}
+
+function node(tag, className, text) {{
+    const el = document.createElement(tag);
+    if (className) el.className = className;
+    if (text != null) el.textContent = text;
+    return el;
+}}
+
+// Collapsible tree for a JSON value
+function jsonTree(value, key) {{
+    const label = key == null ? "" : `${{key}}: `;
+    if (value === null || typeof value !== "object") {{
+        const line = node("div", "pl-4");
+        const color = typeof value === "string" ? "text-green-700" : "text-blue-700";
+        line.append(node("span", "text-purple-700", label), node("span", color, JSON.stringify(value)));
+        return line;
+    }}
+    const entries = Array.isArray(value) ? value.map((v, i) => [i, v]) : Object.entries(value);
+    const details = node("details", "pl-4");
+    details.open = true;
+    const summary = node("summary", "cursor-pointer");
+    const size = Array.isArray(value) ? `[${{entries.length}} items]` : `{{${{entries.length}} keys}}`;
+    summary.append(node("span", "text-purple-700", label), node("span", "text-gray-500", size));
+    details.append(summary, ...entries.map(([k, v]) => jsonTree(v, k)));
+    return details;
+}}
+
+// Show a body as parsed by the server: a JSON tree, form fields, multipart parts or text
+function renderBody(el, view) {{
+    const body = view.body;
+    let content;
+    switch (body.kind) {{
+        case "empty":
+            content = node("div", "text-gray-400", "(empty)");
+            break;
+        case "json":
+            content = jsonTree(body.value);
+            break;
+        case "form":
+            content = node("table", "text-left");
+            body.fields.forEach(field => {{
+                const row = content.insertRow();
+                row.append(node("th", "pr-4 align-top text-purple-700", field.name), node("td", "whitespace-pre-wrap break-all", field.value));
+            }});
+            break;
+        case "multipart":
+            content = node("div", "space-y-2");
+            body.parts.forEach(part => {{
+                const item = node("div", "border-l-2 border-gray-300 pl-2");
+                const meta = [part.filename && `file ${{part.filename}}`, part.content_type, `${{part.size}} B`].filter(Boolean).join(" · ");
+                const info = node("div", "text-xs text-gray-500", meta);
+                if (part.file_id) {{
+                    const link = node("a", "ml-2 text-blue-600 underline", "download");
+                    link.href = "";
+                    link.onclick = (e) => {{
+                        e.preventDefault();
+                        download(`/api/requests/${{el.dataset.id}}/files/${{part.file_id}}`).catch(err => console.error("Download error:", err));
+                    }};
+                    info.append(link);
+                }}
+                item.append(node("div", "font-semibold", part.name || "(unnamed)"), info);
+                if (part.text != null) item.append(node("pre", "whitespace-pre-wrap", part.text));
+                content.append(item);
+            }});
+            break;
+        case "binary":
+            content = node("div", "text-gray-500", `(${{body.size}} bytes of binary data)`);
+            break;
+        default:
+            content = node("pre", "whitespace-pre-wrap", body.text);
+    }}
+    el.querySelector(".body-view").replaceChildren(content);
+
+    const meta = el.querySelector(".body-meta");
+    const notes = [view.content_type, view.encoding && `decoded from ${{view.encoding}}, ${{view.raw_size}} → ${{view.size}} bytes`];
+    meta.textContent = notes.filter(Boolean).join(" · ");
+    if (view.encoding || body.kind === "binary") {{
+        const link = node("a", "ml-2 text-blue-600 underline", "download raw");
+        link.href = "";
+        link.onclick = (e) => {{
+            e.preventDefault();
+            download(`/api/requests/${{el.dataset.id}}/raw`).catch(err => console.error("Download error:", err));
+        }};
+        meta.append(link);
+    }}
+}}
+
+// Bodies are parsed server-side once their request scrolls into view
+const bodyObserver = new IntersectionObserver(entries => {{
+    entries.filter(entry => entry.isIntersecting).forEach(entry => {{
+        bodyObserver.unobserve(entry.target);
+        fetch(`/api/requests/${{entry.target.dataset.id}}/body`, {{ headers: authHeaders() }})
+            .then(res => res.ok ? res.json() : Promise.reject(res.status))
+            .then(view => renderBody(entry.target, view))
+            .catch(err => console.error("Body parse error:", err));
+    }});
+}});
+
 function renderRequest(req, highlight=false) {{
     const el = document.createElement("div");
     el.className = "bg-white border rounded shadow-sm p-4 text-sm font-mono";
+    el.dataset.id = req.id;
 
     if (highlight) {{
         el.classList.add("ring", "ring-blue-400");
@@ -88,14 +323,42 @@ function renderRequest(req, highlight=false) {{
 
     el.innerHTML = `
         <div class="flex justify-between items-center mb-2">
-            <span class="text-xs text-gray-500 break-all">ID: \${{req.id}}</span>
-            <button
-                class="text-xs text-blue-600 underline"
-                onclick="replayRequest('\${{req.id}}')">
-                Replay
-            </button>
+            <span class="request-id text-xs text-gray-500 break-all"></span>
+            <div class="flex gap-3">
+                <select class="copy-as text-xs border rounded">
+                    <option value="">Copy as…</option>
+                    <option value="curl">curl</option>
+                    <option value="httpie">HTTPie</option>
+                    <option value="reqwest">Rust (reqwest)</option>
+                    <option value="python">Python (requests)</option>
+                    <option value="fetch">JavaScript (fetch)</option>
+                </select>
+                <button class="pin-btn text-xs underline"></button>
+                <button class="replay-btn text-xs text-blue-600 underline">Replay</button>
+                <button class="delete-btn text-xs text-red-600 underline">Delete</button>
+            </div>
+        </div>
+        <div class="text-xs text-gray-400 mb-2">
+            \${{new Date(req.created_at).toLocaleString()}}
+            <span class="client-meta ml-2"></span>
+            <span class="response-meta ml-2"></span>
+            <span class="signature-meta ml-2"></span>
+            <span class="schema-meta ml-2"></span>
+            <span class="derived-meta ml-2"></span>
+            <span class="delivery-meta ml-2"></span>
         </div>
-        <div class="text-xs text-gray-400 mb-2">\${{new Date(req.created_at).toLocaleString()}}</div>
+
+        <pre class="code-snippet hidden bg-gray-800 text-gray-100 p-2 mb-2 rounded overflow-x-auto"></pre>
+
+        <details class="schema-details hidden mb-1" open>
+            <summary class="cursor-pointer font-semibold text-red-700">Schema errors</summary>
+            <pre class="bg-red-50 text-red-800 p-2 mt-1 rounded"></pre>
+        </details>
+
+        <details class="connection-details hidden mb-1">
+            <summary class="cursor-pointer font-semibold text-gray-700">Connection</summary>
+            <pre class="bg-gray-100 p-2 mt-1 rounded"></pre>
+        </details>
 
         <details class="mb-1">
             <summary class="cursor-pointer font-semibold text-gray-700">Headers</summary>
@@ -108,27 +371,137 @@ function renderRequest(req, highlight=false) {{
         </details>
 
         <details open>
-            <summary class="cursor-pointer font-semibold text-gray-700">Body</summary>
+            <summary class="cursor-pointer font-semibold text-gray-700">Body <span class="body-meta text-xs font-normal text-gray-500 ml-2"></span></summary>
+            <div class="body-view bg-gray-100 p-2 mt-1 rounded overflow-x-auto"><pre></pre></div>
+        </details>
+
+        <details class="mt-1">
+            <summary class="cursor-pointer font-semibold text-gray-700">Response</summary>
             <pre class="bg-gray-100 p-2 mt-1 rounded"></pre>
         </details>
     `;
 
     // XSS-safe rendering
-    const pres = el.querySelectorAll("pre");
-    pres[0].textContent = req.headers || "";
-    pres[1].textContent = req.query || "";
+    el.querySelector(".request-id").textContent = `ID: ${{req.id}}`;
+    setPinned(el, req.pinned);
+    el.querySelector(".pin-btn").onclick = () => togglePin(req.id, !!el.dataset.pinned);
+    el.querySelector(".replay-btn").onclick = () => replayRequest(req.id);
+    el.querySelector(".delete-btn").onclick = () => deleteRequest(req.id);
+    el.querySelector(".copy-as").onchange = (e) => copyAs(el, req.id, e.target);
+
+    const pres = el.querySelectorAll("details:not(.schema-details):not(.connection-details) pre");
+    pres[0].textContent = keyValueLines(req.headers);
+    pres[1].textContent = keyValueLines(req.query);
     pres[2].textContent = req.body || "";
+    bodyObserver.observe(el);
+    pres[3].textContent = req.response_status == null
+        ? "(not recorded)"
+        : `${{req.response_status}}\n${{req.response_headers || ""}}\n\n${{req.response_body || ""}}`;
+
+    if (req.response_status != null) {{
+        const meta = el.querySelector(".response-meta");
+        const ok = req.response_status < 400;
+        meta.className += ok ? " text-green-600" : " text-red-600";
+        meta.textContent = `→ ${{req.response_status}} via ${{req.response_source || "?"}}`
+            + (req.config_version != null ? ` (config v${{req.config_version}})` : "")
+            + (req.latency_ms != null ? ` in ${{req.latency_ms.toFixed(2)}} ms` : "");
+    }}
+
+    if (req.client_ip) {{
+        el.querySelector(".client-meta").textContent = `from ${{req.client_ip}}`;
+    }}
+
+    const connection = [
+        ["Client IP", req.client_ip],
+        ["Peer", req.remote_addr],
+        ["HTTP version", req.http_version],
+        ["URL", req.request_uri],
+        ["Body size", req.body_size != null ? `${{req.body_size}} bytes` : null],
+        ["Received at", req.received_at],
+    ].filter(([, value]) => value != null);
+    if (connection.length) {{
+        const details = el.querySelector(".connection-details");
+        details.classList.remove("hidden");
+        details.querySelector("pre").textContent = connection.map(([name, value]) => `${{name}}: ${{value}}`).join("\n");
+    }}
+
+    if (req.derived_from) {{
+        el.querySelector(".derived-meta").textContent = `edited from ${{req.derived_from}}`;
+    }}
+
+    if (req.signature_status) {{
+        const sig = el.querySelector(".signature-meta");
+        const colors = {{ valid: "bg-green-100 text-green-700", invalid: "bg-red-100 text-red-700", missing: "bg-yellow-100 text-yellow-700" }};
+        sig.className += " px-1 rounded " + (colors[req.signature_status] || "bg-gray-200");
+        sig.textContent = `signature ${{req.signature_status}}`;
+        sig.title = req.signature_reason || "";
+    }}
+
+    if (req.schema_status) {{
+        const meta = el.querySelector(".schema-meta");
+        const colors = {{ valid: "bg-green-100 text-green-700", invalid: "bg-red-100 text-red-700", unmatched: "bg-yellow-100 text-yellow-700" }};
+        meta.className += " px-1 rounded " + (colors[req.schema_status] || "bg-gray-200");
+        meta.textContent = `schema ${{req.schema_status}}`;
+
+        let errors = [];
+        try {{ errors = JSON.parse(req.schema_errors || "[]"); }} catch (e) {{}}
+        if (errors.length) {{
+            const lines = errors.map(err => `${{err.path || "(body)"}}: ${{err.message}}`).join("\n");
+            meta.title = lines;
+            if (req.schema_status === "invalid") {{
+                el.classList.add("border-red-400");
+                const details = el.querySelector(".schema-details");
+                details.classList.remove("hidden");
+                details.querySelector("pre").textContent = lines;
+            }}
+        }}
+    }}
 
     container.prepend(el);
     updateCount();
 }};
 
+// Replace the list with `requests` (newest first)
+function showRequests(requests) {{
+    container.innerHTML = "";
+    [...requests].reverse().forEach(req => renderRequest(req));
+    updateCount();
+}}
+
+async function runSearch() {{
+    const q = searchInput.value.trim();
+    const url = q
+        ? `/api/webhooks/${{WEBHOOK_ID}}/search?q=${{encodeURIComponent(q)}}`
+        : `/api/webhooks/${{WEBHOOK_ID}}/requests`;
+    try {{
+        const res = await fetch(url, {{ headers: authHeaders() }});
+        const data = await res.json();
+        if (!res.ok) {{
+            searchStatus.textContent = data.error || "Search failed";
+            return;
+        }}
+        searching = !!q;
+        searchStatus.textContent = q ? `${{data.length}} matching request(s)` : "";
+        showRequests(data);
+    }} catch (err) {{
+        console.error("Search error:", err);
+    }}
+}}
+
+document.getElementById("search-form").onsubmit = (e) => {{
+    e.preventDefault();
+    runSearch();
+}};
+document.getElementById("search-clear").onclick = () => {{
+    searchInput.value = "";
+    runSearch();
+}};
+
 // Render initial requests
-INITIAL_REQUESTS.forEach(req => renderRequest(req));
-updateCount();
+showRequests(INITIAL_REQUESTS);
 
 // WebSocket connection for live updates
-const wsUrl = location.origin.replace(/^http/, "ws") + `/ws/${{WEBHOOK_ID}}`;
+const wsUrl = location.origin.replace(/^http/, "ws") + `/ws/${{WEBHOOK_ID}}?token=${{encodeURIComponent(TOKEN)}}`;
 const ws = new WebSocket(wsUrl);
 
 ws.onopen = () => {{
@@ -139,7 +512,12 @@ ws.onopen = () => {{
 ws.onmessage = (event) => {{
     try {{
         const data = JSON.parse(event.data);
+        if (data && data.event) return applyEvent(data);
         if (!data || !data.id) return;
+        if (searching) {{
+            searchStatus.textContent = "New requests arrived — clear the search to see them";
+            return;
+        }}
         renderRequest(data, true); // highlight new request
     }} catch (err) {{
         console.error("WS parse error:", err);
@@ -156,8 +534,9 @@ ws.onclose = () => {{
 </html>
 "#,
         id = id,
-        requests_json = requests_json
+        requests_json = requests_json,
+        token_json = token_json
     );
 
-    Html(html)
+    Html(html).into_response()
 }
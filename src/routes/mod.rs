@@ -1,3 +1,81 @@
 pub mod webhook;
 pub mod dashboard;
-pub mod ws;
\ No newline at end of file
+pub mod ws;
+pub mod api;
+
+use axum::{
+    extract::DefaultBodyLimit,
+    response::Html,
+    routing::{get, post, put},
+    Router,
+};
+use std::sync::Arc;
+use tower_http::cors::CorsLayer;
+
+use crate::{
+    expectations::{create_expectations, delete_expectations, get_expectations, verify_expectations, wait_for_request},
+    replay::replay_request,
+    replay_jobs::{cancel_replay_job, create_replay_job, get_replay_job, list_replay_jobs},
+    schedules::{create_schedule, delete_schedule, get_schedule, list_schedules},
+    AppState,
+};
+use api::{
+    clear_requests, delete_request, delete_webhook, download_request_file, export_request, export_requests, get_request,
+    get_webhook, import_requests, list_deliveries, list_request_files, list_requests, list_webhooks, pin_request,
+    raw_request_body, request_body, request_code, search_requests, unpin_request, update_webhook, validate_payload,
+};
+use dashboard::dashboard_handler;
+use webhook::{create_webhook, reset_response_sequence, set_custom_response, update_custom_response, webhook_handler};
+use ws::ws_handler;
+
+/// Every page and endpoint of the service
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(home_page))
+        .route("/new", get(create_webhook))
+        .route("/webhook/:id", post(webhook_handler))
+        .route("/webhook/:id/config", post(set_custom_response).patch(update_custom_response))
+        .route("/webhook/:id/config/reset", post(reset_response_sequence))
+        .route("/dashboard/:id", get(dashboard_handler))
+        .route("/ws/:id", get(ws_handler))
+        .route("/api/webhooks", get(list_webhooks))
+        .route(
+            "/api/webhooks/:id",
+            get(get_webhook).patch(update_webhook).delete(delete_webhook),
+        )
+        .route("/api/webhooks/:id/requests", get(list_requests).delete(clear_requests))
+        .route("/api/webhooks/:id/search", get(search_requests))
+        .route("/api/webhooks/:id/schema/validate", post(validate_payload))
+        .route("/api/webhooks/:id/wait", get(wait_for_request))
+        .route("/api/webhooks/:id/expectations", post(create_expectations))
+        .route("/api/expectations/:set_id", get(get_expectations).delete(delete_expectations))
+        .route("/api/expectations/:set_id/verify", post(verify_expectations))
+        .route("/api/webhooks/:id/export", get(export_requests))
+        .route("/api/webhooks/:id/import", post(import_requests))
+        .route("/api/requests/:req_id", get(get_request).delete(delete_request))
+        .route("/api/requests/:req_id/export", get(export_request))
+        .route("/api/requests/:req_id/code", get(request_code))
+        .route("/api/requests/:req_id/body", get(request_body))
+        .route("/api/requests/:req_id/raw", get(raw_request_body))
+        .route("/api/requests/:req_id/files", get(list_request_files))
+        .route("/api/requests/:req_id/files/:file_id", get(download_request_file))
+        .route("/api/requests/:req_id/deliveries", get(list_deliveries))
+        .route("/api/requests/:req_id/pin", put(pin_request).delete(unpin_request))
+        .route("/replay/:req_id", post(replay_request))
+        .route("/api/webhooks/:id/replay-jobs", get(list_replay_jobs).post(create_replay_job))
+        .route("/api/replay-jobs/:job_id", get(get_replay_job).delete(cancel_replay_job))
+        .route("/api/requests/:req_id/schedules", post(create_schedule))
+        .route("/api/webhooks/:id/schedules", get(list_schedules))
+        .route("/api/schedules/:schedule_id", get(get_schedule).delete(delete_schedule))
+        .layer(CorsLayer::very_permissive())
+        .layer(DefaultBodyLimit::max(10 * 1024 * 1024))
+        .with_state(state)
+}
+
+async fn home_page() -> Html<String> {
+    let html = match tokio::fs::read_to_string("static/index.html").await {
+        Ok(content) => content,
+        Err(_) => r#"<html><body><h1>Webhook Service</h1></body></html>"#.to_string(),
+    };
+    Html(html)
+}
@@ -1,98 +1,314 @@
 use axum::{
-    extract::{Path, State, Query},
+    extract::{OriginalUri, Path, State, Query},
     response::{IntoResponse, Response},
     Json,
 };
 use axum::body::Bytes;
-use axum::http::StatusCode;
+use axum::http::{header::{CONTENT_ENCODING, CONTENT_TYPE, HOST}, HeaderMap, StatusCode, Uri};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Instant};
 use uuid::Uuid;
 
 use crate::AppState;
-use crate::models::{StoredRequest, WebhookConfig, WebhookEvent};
+use crate::auth::Credentials;
+use crate::body::{decode_content_async, file_parts, DecodedBody};
+use crate::forwarded::{self, Connection, Origin};
+use crate::settings::UnknownWebhookPolicy;
+use crate::models::{RequestFile, ResponseStep, SequenceMode, StoredRequest, WebhookConfig};
 use crate::utils;
-use crate::routes::ws;
+use crate::schema::PayloadSchema;
+use crate::signature::{self, OutboundSigning, SignatureProvider, Verdict};
 
-#[derive(Serialize)]
+/// Carries the management token back to whoever auto-created a webhook
+pub const WEBHOOK_TOKEN_HEADER: &str = "x-webhook-token";
+
+#[derive(Serialize, Deserialize)]
 pub struct NewWebhookResponse {
     pub id: String,
     pub webhook_url: String,
     pub dashboard_url: String,
+    /// Management token required by every endpoint except ingest
+    pub token: String,
 }
 
-pub async fn create_webhook(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+pub async fn create_webhook(State(state): State<Arc<AppState>>) -> Response {
     let id = Uuid::new_v4().to_string();
 
-    if let Err(err) = state.create_webhook(&id).await {
-        eprintln!("Error creating webhook: {err}");
-    }
+    let token = match state.create_webhook(&id).await {
+        Ok(token) => token,
+        Err(err) => {
+            eprintln!("Error creating webhook: {err}");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": "Could not create webhook"})),
+            )
+                .into_response();
+        }
+    };
 
     let webhook_url = format!("/webhook/{}", id);
-    let dashboard_url = format!("/dashboard/{}", id);
+    let dashboard_url = format!("/dashboard/{}?token={}", id, token);
 
     Json(NewWebhookResponse {
         id,
         webhook_url,
         dashboard_url,
+        token,
     })
+    .into_response()
 }
 
 pub async fn webhook_handler(
     Path(id): Path<String>,
     State(state): State<Arc<AppState>>,
     Query(query): Query<HashMap<String, String>>,
+    OriginalUri(uri): OriginalUri,
+    connection: Connection,
     headers: axum::http::HeaderMap,
     body: Bytes,
 ) -> Response {
+    let started = Instant::now();
+    let received_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+
+    // Only webhooks created through /new (or auto-created, if allowed) accept traffic
+    let mut issued_token = None;
+    let webhook = match state.db.get_webhook(&id).await {
+        Ok(Some(webhook)) => webhook,
+        Ok(None) if state.settings.unknown_webhooks == UnknownWebhookPolicy::AutoCreate => {
+            match state.claim_webhook(&id).await {
+                Ok((webhook, token)) => {
+                    // The first sender claims the webhook and is told its token
+                    if let Some(token) = &token {
+                        println!("Auto-created webhook {id}; management token: {token}");
+                    }
+                    issued_token = token;
+                    webhook
+                }
+                Err(err) => {
+                    eprintln!("Error auto-creating webhook: {err}");
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            }
+        }
+        Ok(None) => return (StatusCode::NOT_FOUND, "Unknown webhook").into_response(),
+        Err(err) => {
+            eprintln!("DB lookup error: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    if webhook.archived_at.is_some() || webhook.is_expired(chrono::Utc::now()) {
+        return (StatusCode::GONE, "Webhook is no longer accepting requests").into_response();
+    }
+    // Paused webhooks answer without storing, forwarding or broadcasting
+    if webhook.paused {
+        let status = webhook
+            .paused_status
+            .and_then(|code| StatusCode::from_u16(code).ok())
+            .unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+        return (status, "Webhook is paused").into_response();
+    }
+
     let headers_map = utils::headers_to_map(&headers);
     let req_id = Uuid::new_v4().to_string();
 
-    // Create the StoredRequest
-    let stored_req = StoredRequest {
+    // Get custom response config (using Default if not found)
+    let mut config = state.db.get_response_config(&id).await.unwrap_or_default();
+    let mut response_source = if config.webhook_id.is_empty() {
+        "default".to_string()
+    } else {
+        "config".to_string()
+    };
+
+    // Where the request came from, looking through trusted proxies
+    let origin = state.settings.trusted_proxies.resolve(connection.peer, &headers);
+    let url = request_uri(&origin, &headers, &uri);
+
+    // Check the provider signature, if one is configured for this webhook, before
+    // a scripted sequence spends a step on a request that is turned away
+    let verdict = verify_signature(&config, &headers, &body, &url);
+    let mut rejected = verdict
+        .as_ref()
+        .is_some_and(|verdict| !verdict.is_valid() && config.reject_invalid_signatures.unwrap_or(false));
+
+    if let (true, Some(verdict)) = (rejected, &verdict) {
+        config.status_code = Some(StatusCode::UNAUTHORIZED.as_u16());
+        config.response_body = Some(format!("Signature {}: {}", verdict.status(), verdict.reason()));
+        config.content_type = Some("text/plain".to_string());
+        response_source = "signature".to_string();
+    }
+
+    // Bodies are stored decompressed, with the bytes as received kept alongside
+    let content_encoding = headers.get(CONTENT_ENCODING).and_then(|v| v.to_str().ok());
+    let decoded = decode_content_async(content_encoding, body.clone()).await.unwrap_or_else(|err| {
+        eprintln!("Request {req_id}: {err}");
+        DecodedBody { bytes: body.to_vec(), encoding: None }
+    });
+    let text_body = String::from_utf8(decoded.bytes.clone()).ok();
+    let raw_body = (decoded.encoding.is_some() || text_body.is_none()).then(|| STANDARD.encode(&body));
+
+    // Validate the body against its schema, if one is configured for this webhook;
+    // a payload that is turned away doesn't spend a sequence step either
+    let schema_verdict = state.schemas.validate(&config, &headers, &decoded.bytes);
+    if let Some(verdict) = &schema_verdict {
+        if verdict.is_invalid() && config.reject_invalid_payloads.unwrap_or(false) && !rejected {
+            rejected = true;
+            config.status_code = Some(StatusCode::BAD_REQUEST.as_u16());
+            config.response_body = Some(
+                serde_json::json!({"error": "Payload does not match its schema", "errors": verdict.errors()}).to_string(),
+            );
+            config.content_type = Some("application/json".to_string());
+            response_source = "schema".to_string();
+        }
+    }
+
+    // Scripted sequences override the static response one step per call
+    let steps = config.sequence_steps();
+    if !steps.is_empty() && !rejected {
+        match state.db.next_sequence_call(&id).await {
+            Ok(call) => {
+                let index = config.sequence_mode().step_index(call, steps.len());
+                let step = &steps[index];
+                if step.status_code.is_some() {
+                    config.status_code = step.status_code;
+                }
+                if step.response_body.is_some() {
+                    config.response_body = step.response_body.clone();
+                }
+                if step.content_type.is_some() {
+                    config.content_type = step.content_type.clone();
+                }
+                response_source = format!("sequence:{index}");
+            }
+            Err(err) => eprintln!("Sequence counter error: {err}"),
+        }
+    }
+
+    let status = StatusCode::from_u16(config.status_code.unwrap_or(200))
+        .unwrap_or(StatusCode::OK);
+
+    let content_type = config.content_type.clone().unwrap_or_else(|| "text/plain".to_string());
+    let body_content = config.response_body.clone().unwrap_or_else(|| "OK".to_string());
+    let response_headers = HashMap::from([("content-type".to_string(), content_type.clone())]);
+
+    // Create the StoredRequest, including the response we are about to send
+    let mut stored_req = StoredRequest {
         id: req_id.clone(),
         webhook_id: id.clone(),
         method: "POST".to_string(),
         headers: serde_json::to_string(&headers_map).unwrap_or_default(),
-        body: String::from_utf8(body.to_vec()).unwrap_or_default(),
+        body: text_body.unwrap_or_default(),
         query: serde_json::to_string(&query).unwrap_or_default(),
         created_at: chrono::Utc::now().to_rfc3339(),
+        response_status: Some(status.as_u16()),
+        response_headers: Some(serde_json::to_string(&response_headers).unwrap_or_default()),
+        response_body: Some(body_content.clone()),
+        response_source: Some(response_source),
+        config_version: config.version,
+        latency_ms: None,
+        signature_status: verdict.as_ref().map(|v| v.status().to_string()),
+        signature_reason: verdict.as_ref().map(|v| v.reason()),
+        schema_status: schema_verdict.as_ref().map(|v| v.status().to_string()),
+        schema_errors: schema_verdict
+            .as_ref()
+            .map(|v| v.errors())
+            .filter(|errors| !errors.is_empty())
+            .map(|errors| serde_json::to_string(&errors).unwrap_or_default()),
+        body_encoding: decoded.encoding,
+        raw_body,
+        remote_addr: connection.peer.map(|addr| addr.to_string()),
+        client_ip: origin.client_ip.map(|ip| ip.to_string()),
+        http_version: Some(format!("{:?}", connection.version)),
+        request_uri: Some(url),
+        body_size: Some(body.len() as i64),
+        received_at: Some(received_at),
+        pinned: false,
+        derived_from: None,
     };
 
-    // Save request to the database
-    if let Err(err) = state.db.store_request(&stored_req).await {
-        eprintln!("DB store error: {err}");
+    // Save request to the database, with any files uploaded in it
+    let stored = match state.db.store_request(&stored_req).await {
+        Ok(()) => {
+            store_files(&state, &stored_req, &headers, &decoded.bytes).await;
+            true
+        }
+        Err(err) => {
+            eprintln!("DB store error: {err}");
+            false
+        }
+    };
+
+    // Optional forwarding, re-signed for the target when configured
+    if let Some(forward_url) = &config.forward_url {
+        let mut outbound = stored_req.clone();
+        let signed = match config.forward_signing() {
+            Some(signing) => signature::resign_request(&signing, &mut outbound, chrono::Utc::now().timestamp()),
+            None => Ok(()),
+        };
+
+        match signed {
+            Ok(()) => {
+                if let Err(err) = utils::forward_request(&state, forward_url, &outbound).await {
+                    eprintln!("Forwarding error: {err}");
+                }
+            }
+            Err(err) => eprintln!("Forward signing error: {err}"),
+        }
     }
 
-    // Wrap the request in a WebhookEvent and broadcast
-    let event: WebhookEvent = stored_req.clone().into();
-    if let Err(err) = state.tx.send(event) {
-        eprintln!("Broadcast error: {err}");
-        // Fallback to per-webhook WS broadcast if needed
-        ws::broadcast_to_clients(&id, &stored_req).await;
+    // Everything above counts: storing, files and forwarding
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+    stored_req.latency_ms = Some(latency_ms);
+    if stored {
+        if let Err(err) = state.db.set_latency_ms(&req_id, latency_ms).await {
+            eprintln!("DB store error: {err}");
+        }
     }
 
-    // Get custom response config (using Default if not found)
-    let config = state.db.get_response_config(&id).await.unwrap_or_default();
+    state.announce(&stored_req).await;
 
-    // Optional forwarding
-    if let Some(forward_url) = &config.forward_url {
-        if let Err(err) = utils::forward_request(forward_url, &stored_req).await {
-            eprintln!("Forwarding error: {err}");
-        }
+    let mut response = Response::builder()
+        .status(status)
+        .header("Content-Type", content_type);
+    if let Some(token) = issued_token {
+        response = response.header(WEBHOOK_TOKEN_HEADER, token);
     }
+    response.body(axum::body::Body::from(body_content)).unwrap()
+}
 
-    let status = StatusCode::from_u16(config.status_code.unwrap_or(200))
-        .unwrap_or(StatusCode::OK);
+/// Absolute URL a request was sent to; HTTP/2 requests already carry their authority.
+/// A `Host` that isn't a plain authority is replaced rather than pasted into the URL.
+fn request_uri(origin: &Origin, headers: &HeaderMap, uri: &Uri) -> String {
+    if uri.authority().is_some() {
+        return uri.to_string();
+    }
+    let host = origin
+        .host
+        .as_deref()
+        .or_else(|| headers.get(HOST).and_then(|v| v.to_str().ok()))
+        .filter(|host| forwarded::valid_host(host))
+        .unwrap_or("localhost");
+    format!("{}://{host}{uri}", origin.scheme)
+}
 
-    let content_type = config.content_type.unwrap_or_else(|| "text/plain".to_string());
-    let body_content = config.response_body.unwrap_or_else(|| "OK".to_string());
+/// Run the configured provider's signature check against the raw request
+fn verify_signature(
+    config: &WebhookConfig,
+    headers: &axum::http::HeaderMap,
+    body: &[u8],
+    url: &str,
+) -> Option<Verdict> {
+    let provider = config.signing_provider.as_deref()?;
+    let secret = config.signing_secret.as_deref().unwrap_or_default();
 
-    Response::builder()
-        .status(status)
-        .header("Content-Type", content_type)
-        .body(axum::body::Body::from(body_content))
-        .unwrap()
+    let provider = match provider.parse::<SignatureProvider>() {
+        Ok(provider) => provider,
+        Err(err) => return Some(Verdict::Invalid(err.to_string())),
+    };
+
+    // `url` is the public URL the sender used, read through trusted proxies only, as Twilio signs it
+    Some(signature::verify(provider, secret, headers, body, url, chrono::Utc::now().timestamp()))
 }
 
 #[derive(Deserialize)]
@@ -101,25 +317,175 @@ pub struct CustomResponsePayload {
     response_body: Option<String>,
     content_type: Option<String>, // Added field
     forward_url: Option<String>,
+    response_sequence: Option<Vec<ResponseStep>>,
+    sequence_mode: Option<String>,
+    signing_provider: Option<String>,
+    signing_secret: Option<String>,
+    reject_invalid_signatures: Option<bool>,
+    forward_signing: Option<OutboundSigning>,
+    payload_schema: Option<PayloadSchema>,
+    reject_invalid_payloads: Option<bool>,
 }
 
 pub async fn set_custom_response(
     Path(id): Path<String>,
     State(state): State<Arc<AppState>>,
+    credentials: Credentials,
     Json(payload): Json<CustomResponsePayload>,
-) -> impl IntoResponse {
-    let config = WebhookConfig {
-        webhook_id: id.clone(),
+) -> Response {
+    save_custom_response(&state, &id, &credentials, payload, false).await
+}
+
+/// Change only the settings given, leaving the rest of the config as it is
+pub async fn update_custom_response(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Json(payload): Json<CustomResponsePayload>,
+) -> Response {
+    save_custom_response(&state, &id, &credentials, payload, true).await
+}
+
+/// Store a webhook's config: the payload as a whole, or with `merge` on top of the stored one
+async fn save_custom_response(
+    state: &AppState,
+    id: &str,
+    credentials: &Credentials,
+    payload: CustomResponsePayload,
+    merge: bool,
+) -> Response {
+    if let Err(err) = state.authorize(id, credentials).await {
+        return err.into_response();
+    }
+
+    if let Some(forward_url) = &payload.forward_url {
+        if let Err(refused) = state.settings.egress.screen_target(forward_url) {
+            return refused.into_response();
+        }
+    }
+
+    if let Some(mode) = &payload.sequence_mode {
+        if let Err(err) = mode.parse::<SequenceMode>() {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": err.to_string()}))).into_response();
+        }
+    }
+
+    if let Some(schema) = &payload.payload_schema {
+        if let Err(message) = schema.check() {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": message}))).into_response();
+        }
+    }
+
+    let current = match state.get_response_config(id).await {
+        Ok(current) => current,
+        Err(err) => {
+            eprintln!("Error loading response config: {err}");
+            return axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    // The secret is never sent back, so a form saved without re-entering it keeps
+    // the stored one; an empty string clears it
+    let signing_secret = match payload.signing_secret {
+        Some(secret) => Some(secret).filter(|secret| !secret.is_empty()),
+        None => current.signing_secret.clone(),
+    };
+    let restarts_sequence = !merge || payload.response_sequence.is_some();
+
+    let given = WebhookConfig {
+        webhook_id: id.to_string(),
         status_code: payload.status_code,
         response_body: payload.response_body,
         content_type: payload.content_type,
         forward_url: payload.forward_url,
+        response_sequence: payload
+            .response_sequence
+            .filter(|steps| !steps.is_empty())
+            .map(|steps| serde_json::to_string(&steps).unwrap_or_default()),
+        sequence_mode: payload.sequence_mode,
+        signing_provider: payload.signing_provider,
+        signing_secret,
+        reject_invalid_signatures: payload.reject_invalid_signatures,
+        forward_signing: payload
+            .forward_signing
+            .map(|signing| serde_json::to_string(&signing).unwrap_or_default()),
+        payload_schema: payload
+            .payload_schema
+            .map(|schema| serde_json::to_string(&schema).unwrap_or_default()),
+        reject_invalid_payloads: payload.reject_invalid_payloads,
+        version: None,
     };
+    let config = if merge { merge_config(given, current) } else { given };
 
     if let Err(err) = state.set_response_config(&config).await {
         eprintln!("Error setting response config: {err}");
-        return axum::http::StatusCode::INTERNAL_SERVER_ERROR;
+        return axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    // A new script always starts from its first step
+    if restarts_sequence {
+        if let Err(err) = state.db.reset_sequence(id).await {
+            eprintln!("Error resetting response sequence: {err}");
+            return axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    axum::http::StatusCode::OK.into_response()
+}
+
+/// `given` with every setting it leaves out taken from `current`
+fn merge_config(given: WebhookConfig, current: WebhookConfig) -> WebhookConfig {
+    WebhookConfig {
+        webhook_id: given.webhook_id,
+        status_code: given.status_code.or(current.status_code),
+        response_body: given.response_body.or(current.response_body),
+        content_type: given.content_type.or(current.content_type),
+        forward_url: given.forward_url.or(current.forward_url),
+        response_sequence: given.response_sequence.or(current.response_sequence),
+        sequence_mode: given.sequence_mode.or(current.sequence_mode),
+        signing_provider: given.signing_provider.or(current.signing_provider),
+        signing_secret: given.signing_secret,
+        reject_invalid_signatures: given.reject_invalid_signatures.or(current.reject_invalid_signatures),
+        forward_signing: given.forward_signing.or(current.forward_signing),
+        payload_schema: given.payload_schema.or(current.payload_schema),
+        reject_invalid_payloads: given.reject_invalid_payloads.or(current.reject_invalid_payloads),
+        version: None,
+    }
+}
+
+/// Rewind the scripted response sequence so the next call gets the first step
+pub async fn reset_response_sequence(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    if let Err(err) = state.authorize(&id, &credentials).await {
+        return err.into_response();
+    }
+
+    if let Err(err) = state.db.reset_sequence(&id).await {
+        eprintln!("Error resetting response sequence: {err}");
+        return axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
-    axum::http::StatusCode::OK
-}
\ No newline at end of file
+    axum::http::StatusCode::OK.into_response()
+}
+
+/// Keep each file uploaded in a multipart body as a file of its own
+async fn store_files(state: &AppState, req: &StoredRequest, headers: &HeaderMap, body: &[u8]) {
+    let content_type = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok());
+    for (index, part, bytes) in file_parts(content_type, body).await {
+        let file = RequestFile {
+            id: Uuid::new_v4().to_string(),
+            request_id: req.id.clone(),
+            part_index: index as i64,
+            field_name: part.name,
+            filename: part.filename.unwrap_or_default(),
+            content_type: part.content_type,
+            size: bytes.len() as i64,
+            created_at: req.created_at.clone(),
+        };
+        if let Err(err) = state.db.store_request_file(&file).await {
+            eprintln!("Could not store file {} of request {}: {err}", file.filename, req.id);
+        }
+    }
+}
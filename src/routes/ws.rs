@@ -1,31 +1,44 @@
 use axum::{
     extract::{
-        Path,
+        Path, State,
         ws::{WebSocketUpgrade, WebSocket, Message},
     },
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::{broadcast, Mutex};
 use serde_json;
-use crate::models::StoredRequest;
+use crate::{AppState, auth::Credentials, models::{DashboardEvent, Delivery, DeliveryReport, StoredRequest}};
+use futures_util::{SinkExt, StreamExt};
 use lazy_static::lazy_static;
 
 lazy_static! {
-    // Global registry of broadcast senders, one per webhook ID
-    static ref BROADCASTERS: Arc<Mutex<HashMap<String, broadcast::Sender<StoredRequest>>>> =
+    // Global registry of broadcast senders, one per webhook ID, carrying
+    // messages already serialized to JSON
+    static ref BROADCASTERS: Arc<Mutex<HashMap<String, broadcast::Sender<String>>>> =
         Arc::new(Mutex::new(HashMap::new()));
 }
 
 pub async fn ws_handler(
     Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
     ws: WebSocketUpgrade,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(id, socket))
+) -> Response {
+    if let Err(err) = state.authorize(&id, &credentials).await {
+        return err.into_response();
+    }
+
+    ws.on_upgrade(move |socket| handle_socket(state, id, socket))
 }
 
-async fn handle_socket(webhook_id: String, mut socket: WebSocket) {
+/// Longest relayed response body kept with a delivery
+const MAX_DELIVERY_BODY: usize = 64 * 1024;
+
+async fn handle_socket(state: Arc<AppState>, webhook_id: String, socket: WebSocket) {
+    let (mut socket, mut incoming) = socket.split();
+
     // Get or create a broadcast channel for this webhook
     let tx = {
         let mut broadcasters = BROADCASTERS.lock().await;
@@ -49,18 +62,21 @@ async fn handle_socket(webhook_id: String, mut socket: WebSocket) {
         )))
         .await;
 
-    // Continuously send broadcast messages to this client
-    while let Ok(request) = rx.recv().await {
-        match serde_json::to_string(&request) {
-            Ok(json) => {
+    // Send broadcast messages to this client, and take delivery reports from relays
+    loop {
+        tokio::select! {
+            json = rx.recv() => {
+                let Ok(json) = json else { break };
                 if socket.send(Message::Text(json)).await.is_err() {
                     // Client disconnected, break out of the loop
                     break;
                 }
             }
-            Err(err) => {
-                eprintln!("Error serializing StoredRequest: {err}");
-            }
+            message = incoming.next() => match message {
+                Some(Ok(Message::Text(text))) => record_delivery(&state, &webhook_id, &text).await,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
         }
     }
 
@@ -76,10 +92,79 @@ async fn handle_socket(webhook_id: String, mut socket: WebSocket) {
     }
 }
 
+/// Store a relay's report of delivering one of this webhook's requests locally
+async fn record_delivery(state: &AppState, webhook_id: &str, text: &str) {
+    let report: DeliveryReport = match serde_json::from_str(text) {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("Ignoring WebSocket message: {err}");
+            return;
+        }
+    };
+
+    // A socket only speaks for the webhook it was authorized for
+    match state.db.get_request(&report.request_id).await {
+        Ok(req) if req.webhook_id == webhook_id => {}
+        _ => {
+            eprintln!("Ignoring delivery of unknown request {}", report.request_id);
+            return;
+        }
+    }
+
+    let mut response_body = report.response_body;
+    if let Some(body) = &mut response_body {
+        if body.len() > MAX_DELIVERY_BODY {
+            let mut end = MAX_DELIVERY_BODY;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            body.truncate(end);
+        }
+    }
+
+    let delivery = Delivery {
+        id: uuid::Uuid::new_v4().to_string(),
+        request_id: report.request_id,
+        target: report.target,
+        status_code: report.status_code,
+        response_headers: report.response_headers,
+        response_body,
+        error: report.error,
+        duration_ms: report.duration_ms,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    if let Err(err) = state.db.store_delivery(&delivery).await {
+        eprintln!("DB store error: {err}");
+        return;
+    }
+
+    let event = DashboardEvent::RequestDelivered {
+        request_id: delivery.request_id,
+        target: delivery.target,
+        status_code: delivery.status_code,
+        error: delivery.error,
+    };
+    broadcast_event(webhook_id, &event).await;
+}
+
 /// Broadcasts a stored webhook request to all active WebSocket clients
 pub async fn broadcast_to_clients(webhook_id: &str, request: &StoredRequest) {
+    send_json(webhook_id, request).await;
+}
+
+/// Broadcasts a change to already captured requests (deletion, pinning, …)
+pub async fn broadcast_event(webhook_id: &str, event: &DashboardEvent) {
+    send_json(webhook_id, event).await;
+}
+
+async fn send_json<T: serde::Serialize>(webhook_id: &str, message: &T) {
     let broadcasters = BROADCASTERS.lock().await;
     if let Some(sender) = broadcasters.get(webhook_id) {
-        let _ = sender.send(request.clone()); // ignore if no active clients
+        match serde_json::to_string(message) {
+            Ok(json) => {
+                let _ = sender.send(json); // ignore if no active clients
+            }
+            Err(err) => eprintln!("Error serializing broadcast: {err}"),
+        }
     }
 }
@@ -0,0 +1,239 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::{str::FromStr, sync::Arc, time::Duration};
+use tokio::task::JoinSet;
+
+use crate::{
+    auth::Credentials,
+    models::ReplaySchedule,
+    replay,
+    routes::api::load_request,
+    signature::{self, OutboundSigning},
+    AppState,
+};
+
+/// How often the scheduler looks for due replays
+const TICK: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize)]
+pub struct SchedulePayload {
+    target: String,
+    /// RFC 3339 instant of a one-off replay
+    at: Option<String>,
+    /// Cron expression of a recurring replay: five fields, or six with seconds first
+    cron: Option<String>,
+    signing: Option<OutboundSigning>,
+}
+
+/// Timestamps are stored in one fixed UTC format so they compare as strings
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_cron(expression: &str) -> Result<cron::Schedule, String> {
+    let expression = expression.trim();
+    // The cron crate wants seconds; classic five-field expressions fire on the minute
+    let full = if expression.split_whitespace().count() == 5 {
+        format!("0 {expression}")
+    } else {
+        expression.to_string()
+    };
+    cron::Schedule::from_str(&full).map_err(|e| format!("Invalid cron expression: {e}"))
+}
+
+/// First run of a cron schedule strictly after `after`
+fn next_cron_run(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    parse_cron(expression).ok()?.after(&after).next()
+}
+
+/// Schedule a captured request to replay once or on a cron schedule
+pub async fn create_schedule(
+    Path(req_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+    Json(payload): Json<SchedulePayload>,
+) -> Response {
+    let req = match load_request(&state, &req_id, &credentials).await {
+        Ok(req) => req,
+        Err(response) => return response,
+    };
+
+    if let Err(refused) = state.settings.egress.screen_target(&payload.target) {
+        return refused.into_response();
+    }
+
+    let now = Utc::now();
+    let next_run_at = match (&payload.at, &payload.cron) {
+        (Some(at), None) => match DateTime::parse_from_rfc3339(at) {
+            Ok(at) if at > now => at.with_timezone(&Utc),
+            Ok(_) => return bad_request("`at` must be in the future".to_string()),
+            Err(e) => return bad_request(format!("Invalid `at`: {e}")),
+        },
+        (None, Some(expression)) => match parse_cron(expression).map(|schedule| schedule.after(&now).next()) {
+            Ok(Some(next)) => next,
+            Ok(None) => return bad_request("The cron expression never fires again".to_string()),
+            Err(e) => return bad_request(e),
+        },
+        _ => return bad_request("Give exactly one of `at` or `cron`".to_string()),
+    };
+
+    let schedule = ReplaySchedule {
+        id: uuid::Uuid::new_v4().to_string(),
+        webhook_id: req.webhook_id,
+        request_id: req.id,
+        target: payload.target,
+        cron: payload.cron.map(|expression| expression.trim().to_string()),
+        signing: payload.signing.map(|signing| serde_json::to_string(&signing).unwrap_or_default()),
+        next_run_at: Some(timestamp(next_run_at)),
+        last_run_at: None,
+        last_status_code: None,
+        last_error: None,
+        runs: 0,
+        created_at: timestamp(now),
+    };
+
+    match state.db.create_schedule(&schedule).await {
+        Ok(()) => (StatusCode::CREATED, Json(schedule)).into_response(),
+        Err(e) => db_error(e),
+    }
+}
+
+/// List a webhook's replay schedules, newest first
+pub async fn list_schedules(
+    Path(webhook_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    if let Err(err) = state.authorize(&webhook_id, &credentials).await {
+        return err.into_response();
+    }
+
+    match state.db.list_schedules(&webhook_id).await {
+        Ok(schedules) => Json(schedules).into_response(),
+        Err(e) => db_error(e),
+    }
+}
+
+pub async fn get_schedule(
+    Path(schedule_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    match load_schedule(&state, &schedule_id, &credentials).await {
+        Ok(schedule) => Json(schedule).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Cancel a schedule; a replay already on its way still finishes
+pub async fn delete_schedule(
+    Path(schedule_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    credentials: Credentials,
+) -> Response {
+    if let Err(response) = load_schedule(&state, &schedule_id, &credentials).await {
+        return response;
+    }
+
+    match state.db.delete_schedule(&schedule_id).await {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => db_error(e),
+    }
+}
+
+async fn load_schedule(state: &AppState, schedule_id: &str, credentials: &Credentials) -> Result<ReplaySchedule, Response> {
+    let schedule = match state.db.get_schedule(schedule_id).await {
+        Ok(Some(schedule)) => schedule,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, Json(json!({"error": "Schedule not found"}))).into_response()),
+        Err(e) => return Err(db_error(e)),
+    };
+
+    state
+        .authorize(&schedule.webhook_id, credentials)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    Ok(schedule)
+}
+
+fn bad_request(message: String) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({"error": message}))).into_response()
+}
+
+fn db_error(err: sqlx::Error) -> Response {
+    eprintln!("DB error: {err}");
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": "Database error"}))).into_response()
+}
+
+/// Send every replay due at `now`, returning how many were sent.
+///
+/// Each schedule moves on to its next run before its request goes out, so a
+/// slow target never makes a run fire twice. Runs missed while the server was
+/// down are made up once, not once per missed occurrence.
+pub async fn run_due(state: &Arc<AppState>, now: DateTime<Utc>) -> Result<usize, sqlx::Error> {
+    let db = &state.db;
+    let mut sends = JoinSet::new();
+
+    for schedule in db.due_schedules(&timestamp(now)).await? {
+        let Some(due) = &schedule.next_run_at else { continue };
+        let next = schedule
+            .cron
+            .as_deref()
+            .and_then(|expression| next_cron_run(expression, now))
+            .map(timestamp);
+        if !db.advance_schedule(&schedule.id, due, next.as_deref()).await? {
+            continue;
+        }
+
+        let state = state.clone();
+        sends.spawn(async move {
+            let result = replay_scheduled(&state, &schedule).await;
+            let (status_code, error) = match result {
+                Ok(status_code) => (status_code, None),
+                Err(e) => (None, Some(e)),
+            };
+            if let Err(e) = state.db.record_schedule_run(&schedule.id, &timestamp(Utc::now()), status_code, error.as_deref()).await {
+                eprintln!("Scheduler error: {e}");
+            }
+        });
+    }
+
+    let mut sent = 0;
+    while sends.join_next().await.is_some() {
+        sent += 1;
+    }
+    Ok(sent)
+}
+
+async fn replay_scheduled(state: &AppState, schedule: &ReplaySchedule) -> Result<Option<u16>, String> {
+    let mut req = state.db.get_request(&schedule.request_id).await.map_err(|e| e.to_string())?;
+    if let Some(signing) = schedule.signing() {
+        signature::resign_request(&signing, &mut req, Utc::now().timestamp())?;
+    }
+    replay::send(state, &schedule.target, &req).await
+}
+
+/// Send scheduled replays as they fall due.
+pub fn spawn_scheduler(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+
+            // Sweeps overlap when a target is slow; claiming each run keeps them apart
+            let state = state.clone();
+            tokio::spawn(async move {
+                if let Err(err) = run_due(&state, Utc::now()).await {
+                    eprintln!("Scheduler error: {err}");
+                }
+            });
+        }
+    })
+}
@@ -0,0 +1,205 @@
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::models::WebhookConfig;
+
+/// Most validation errors kept per request
+const MAX_ERRORS: usize = 50;
+/// Compiled schemas kept at once; the cache starts over past this
+const MAX_CACHED_SCHEMAS: usize = 1000;
+
+/// JSON Schemas captured bodies are validated against: one for every payload, or one
+/// per event type, picked by the value of `event_field`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PayloadSchema {
+    /// Schema for every payload, or for event types `schemas` doesn't list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Value>,
+    /// Schemas keyed by event type
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub schemas: BTreeMap<String, Value>,
+    /// Where the event type is read from: a dot path into the body such as `type` or
+    /// `data.event`, or `header:<name>` such as `header:x-github-event`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_field: Option<String>,
+}
+
+/// One way a payload breaks its schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaError {
+    /// JSON Pointer to the offending value, empty for the whole body
+    pub path: String,
+    pub message: String,
+}
+
+/// Outcome of validating a captured body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaVerdict {
+    Valid,
+    Invalid(Vec<SchemaError>),
+    /// No schema applies to the payload's event type
+    Unmatched(String),
+}
+
+impl SchemaVerdict {
+    /// Short status stored on the request: `valid`, `invalid` or `unmatched`
+    pub fn status(&self) -> &'static str {
+        match self {
+            SchemaVerdict::Valid => "valid",
+            SchemaVerdict::Invalid(_) => "invalid",
+            SchemaVerdict::Unmatched(_) => "unmatched",
+        }
+    }
+
+    pub fn is_invalid(&self) -> bool {
+        matches!(self, SchemaVerdict::Invalid(_))
+    }
+
+    /// What is stored with the request: the validation errors, or why no schema applied
+    pub fn errors(&self) -> Vec<SchemaError> {
+        match self {
+            SchemaVerdict::Valid => Vec::new(),
+            SchemaVerdict::Invalid(errors) => errors.clone(),
+            SchemaVerdict::Unmatched(reason) => vec![SchemaError { path: String::new(), message: reason.clone() }],
+        }
+    }
+}
+
+impl PayloadSchema {
+    /// Compile every schema, checking the event type can be found when needed.
+    pub fn compile(&self) -> Result<CompiledSchema, String> {
+        if self.schema.is_none() && self.schemas.is_empty() {
+            return Err("Give a schema, or schemas keyed by event type".to_string());
+        }
+        if !self.schemas.is_empty() && self.event_field.as_deref().is_none_or(|field| field.trim().is_empty()) {
+            return Err("Schemas keyed by event type need an event_field".to_string());
+        }
+        let schema = match &self.schema {
+            Some(schema) => Some(jsonschema::validator_for(schema).map_err(|e| format!("Invalid schema: {e}"))?),
+            None => None,
+        };
+        let mut schemas = BTreeMap::new();
+        for (event, schema) in &self.schemas {
+            let validator = jsonschema::validator_for(schema).map_err(|e| format!("Invalid schema for {event}: {e}"))?;
+            schemas.insert(event.clone(), validator);
+        }
+        Ok(CompiledSchema { schema, schemas, event_field: self.event_field.clone() })
+    }
+
+    /// Check every schema compiles and the event type can be found when needed.
+    pub fn check(&self) -> Result<(), String> {
+        self.compile().map(|_| ())
+    }
+
+    /// Validate one captured body. This compiles the schemas first; see [`SchemaCache`]
+    /// for validating many.
+    pub fn validate(&self, headers: &HeaderMap, body: &[u8]) -> SchemaVerdict {
+        match self.compile() {
+            Ok(compiled) => compiled.validate(headers, body),
+            Err(message) => SchemaVerdict::Invalid(vec![SchemaError { path: String::new(), message }]),
+        }
+    }
+}
+
+/// A [`PayloadSchema`] with its schemas compiled
+pub struct CompiledSchema {
+    schema: Option<jsonschema::Validator>,
+    schemas: BTreeMap<String, jsonschema::Validator>,
+    event_field: Option<String>,
+}
+
+impl CompiledSchema {
+    /// Validate a captured body, picking the schema by event type when there are several.
+    pub fn validate(&self, headers: &HeaderMap, body: &[u8]) -> SchemaVerdict {
+        let payload: Value = match serde_json::from_slice(body) {
+            Ok(payload) => payload,
+            Err(e) => {
+                return SchemaVerdict::Invalid(vec![SchemaError { path: String::new(), message: format!("Body is not JSON: {e}") }])
+            }
+        };
+
+        let event = self.event_field.as_deref().and_then(|field| event_type(field, headers, &payload));
+        let validator = match event.as_deref().and_then(|event| self.schemas.get(event)).or(self.schema.as_ref()) {
+            Some(validator) => validator,
+            None => {
+                return SchemaVerdict::Unmatched(match event {
+                    Some(event) => format!("No schema for event type {event}"),
+                    None => "The payload has no event type".to_string(),
+                })
+            }
+        };
+
+        let errors: Vec<SchemaError> = validator
+            .iter_errors(&payload)
+            .take(MAX_ERRORS)
+            .map(|error| SchemaError { path: error.instance_path().as_str().to_string(), message: error.to_string() })
+            .collect();
+
+        if errors.is_empty() {
+            SchemaVerdict::Valid
+        } else {
+            SchemaVerdict::Invalid(errors)
+        }
+    }
+}
+
+/// A webhook's stored schema and what it compiled to
+type CachedSchema = (String, Arc<CompiledSchema>);
+
+/// Each webhook's payload schema, compiled once and reused until the stored schema changes
+#[derive(Clone, Default)]
+pub struct SchemaCache {
+    compiled: Arc<Mutex<HashMap<String, CachedSchema>>>,
+}
+
+impl SchemaCache {
+    /// Validate a captured body against the payload schema of `config`, if it has one
+    pub fn validate(&self, config: &WebhookConfig, headers: &HeaderMap, body: &[u8]) -> Option<SchemaVerdict> {
+        let source = config.payload_schema.as_deref()?;
+        match self.compiled(&config.webhook_id, source) {
+            Some(Ok(compiled)) => Some(compiled.validate(headers, body)),
+            Some(Err(message)) => Some(SchemaVerdict::Invalid(vec![SchemaError { path: String::new(), message }])),
+            None => None,
+        }
+    }
+
+    /// The compiled schema stored as `source`; `None` when it can't be decoded
+    fn compiled(&self, webhook_id: &str, source: &str) -> Option<Result<Arc<CompiledSchema>, String>> {
+        if let Some((cached, compiled)) = self.compiled.lock().unwrap().get(webhook_id) {
+            if cached == source {
+                return Some(Ok(compiled.clone()));
+            }
+        }
+
+        let schema: PayloadSchema = serde_json::from_str(source).ok()?;
+        let compiled = match schema.compile() {
+            Ok(compiled) => Arc::new(compiled),
+            Err(message) => return Some(Err(message)),
+        };
+        let mut cache = self.compiled.lock().unwrap();
+        if cache.len() >= MAX_CACHED_SCHEMAS {
+            cache.clear();
+        }
+        cache.insert(webhook_id.to_string(), (source.to_string(), compiled.clone()));
+        Some(Ok(compiled))
+    }
+}
+
+/// The event type named by `field`, as a string even when the body holds a number
+fn event_type(field: &str, headers: &HeaderMap, payload: &Value) -> Option<String> {
+    if let Some(name) = field.strip_prefix("header:") {
+        return headers.get(name.trim()).and_then(|v| v.to_str().ok()).map(String::from);
+    }
+
+    let value = field
+        .split('.')
+        .try_fold(payload, |value, key| value.get(key))?;
+    match value {
+        Value::String(event) => Some(event.clone()),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+        other => Some(other.to_string()),
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::str::FromStr;
+
+/// A parsed search over a webhook's captured requests.
+///
+/// The syntax is a list of space-separated terms, all of which must match:
+///
+/// * `method:POST`
+/// * `header:x-github-event=push`
+/// * `json:data.order_id=12345` (a JSON path into the body, `$.` optional)
+/// * `status:500` or `status:5xx` for the response we sent back
+/// * `schema:invalid` (or `valid`, `unmatched`) for the payload schema verdict
+/// * `delivered:no` (or `yes`) for whether a relay has reported delivering it
+/// * `after:` / `before:` with an RFC 3339 instant, a `YYYY-MM-DD` date or an
+///   age such as `30m`, `24h` or `7d`
+/// * anything else is full-text search over bodies and headers; use double
+///   quotes for phrases, e.g. `"payment failed"`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub text: Vec<String>,
+    pub method: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub json: Vec<(String, String)>,
+    pub status: Option<StatusFilter>,
+    pub schema: Option<String>,
+    pub delivered: Option<bool>,
+    pub after: Option<DateTime<Utc>>,
+    pub before: Option<DateTime<Utc>>,
+}
+
+/// Filter on the status code of the response that was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFilter {
+    Exact(u16),
+    /// `4xx` and friends: the hundreds digit
+    Class(u16),
+}
+
+impl StatusFilter {
+    /// Inclusive range of status codes this filter accepts
+    pub fn range(self) -> (u16, u16) {
+        match self {
+            StatusFilter::Exact(code) => (code, code),
+            StatusFilter::Class(class) => (class * 100, class * 100 + 99),
+        }
+    }
+}
+
+impl FromStr for StatusFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        if let Some(class) = lower.strip_suffix("xx") {
+            match class.parse::<u16>() {
+                Ok(class @ 1..=5) => return Ok(Self::Class(class)),
+                _ => bail!("Invalid status class: {s}"),
+            }
+        }
+        match s.parse::<u16>() {
+            Ok(code @ 100..=599) => Ok(Self::Exact(code)),
+            _ => Err(anyhow!("Invalid status code: {s}")),
+        }
+    }
+}
+
+impl SearchQuery {
+    /// The free-text terms as an FTS5 `MATCH` expression, each term quoted
+    /// so user input can never be read as FTS syntax.
+    pub fn fts_expression(&self) -> Option<String> {
+        if self.text.is_empty() {
+            return None;
+        }
+        let terms: Vec<String> = self
+            .text
+            .iter()
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect();
+        Some(terms.join(" "))
+    }
+
+    /// Parse a query, resolving relative times against `now`.
+    pub fn parse_at(input: &str, now: DateTime<Utc>) -> anyhow::Result<Self> {
+        let mut query = SearchQuery::default();
+
+        for (token, quoted) in tokenize(input)? {
+            let filter = if quoted { None } else { token.split_once(':') };
+            let Some((key, value)) = filter else {
+                query.text.push(token);
+                continue;
+            };
+
+            match key.to_ascii_lowercase().as_str() {
+                "method" => query.method = Some(value.to_ascii_uppercase()),
+                "header" => {
+                    let (name, value) = split_equals(key, value)?;
+                    if name.contains('"') {
+                        bail!("Invalid header name: {name}");
+                    }
+                    query.headers.push((name.to_ascii_lowercase(), value));
+                }
+                "json" => {
+                    let (path, value) = split_equals(key, value)?;
+                    query.json.push((json_path(&path)?, value));
+                }
+                "status" => query.status = Some(value.parse()?),
+                "schema" => query.schema = Some(value.to_ascii_lowercase()),
+                "delivered" => query.delivered = Some(parse_yes_no(value)?),
+                "after" => query.after = Some(parse_time(value, now)?),
+                "before" => query.before = Some(parse_time(value, now)?),
+                // Not a filter we know (e.g. a URL), so search for it as text
+                _ => query.text.push(token),
+            }
+        }
+
+        Ok(query)
+    }
+}
+
+impl FromStr for SearchQuery {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SearchQuery::parse_at(s, Utc::now())
+    }
+}
+
+fn parse_yes_no(value: &str) -> anyhow::Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" | "true" => Ok(true),
+        "no" | "false" => Ok(false),
+        _ => bail!("Expected yes or no, got {value}"),
+    }
+}
+
+/// Split on whitespace outside double quotes, dropping the quotes.
+///
+/// Each token is paired with whether it opened with a quote, which keeps
+/// `"method:POST"` a text search rather than a filter.
+fn tokenize(input: &str) -> anyhow::Result<Vec<(String, bool)>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut started = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                if !started {
+                    quoted = true;
+                }
+                in_quotes = !in_quotes;
+                started = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if started {
+                    tokens.push((std::mem::take(&mut current), quoted));
+                }
+                quoted = false;
+                started = false;
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+
+    if in_quotes {
+        bail!("Unterminated quote in search query");
+    }
+    if started {
+        tokens.push((current, quoted));
+    }
+    tokens.retain(|(token, _)| !token.is_empty());
+    Ok(tokens)
+}
+
+fn split_equals(key: &str, value: &str) -> anyhow::Result<(String, String)> {
+    match value.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(anyhow!("Expected {key}:<name>=<value>")),
+    }
+}
+
+/// Accept `a.b[0]` as well as SQLite's own `$.a.b[0]`, rejecting anything SQLite
+/// couldn't evaluate so a bad path is the caller's error rather than a failed query
+fn json_path(path: &str) -> anyhow::Result<String> {
+    let path = if path.starts_with('$') {
+        path.to_string()
+    } else if path.starts_with('[') {
+        format!("${path}")
+    } else {
+        format!("$.{path}")
+    };
+    validate_json_path(&path).map_err(|reason| anyhow!("Invalid JSON path {path}: {reason}"))?;
+    Ok(path)
+}
+
+/// `$` followed by any number of `.key`, `."quoted key"`, `[N]`, `[#]` or `[#-N]`
+fn validate_json_path(path: &str) -> Result<(), &'static str> {
+    let mut rest = path.strip_prefix('$').ok_or("must start with $")?;
+    while !rest.is_empty() {
+        if let Some(member) = rest.strip_prefix('.') {
+            rest = if let Some(quoted) = member.strip_prefix('"') {
+                let end = quoted.find('"').ok_or("unterminated quoted key")?;
+                &quoted[end + 1..]
+            } else {
+                let end = member.find(['.', '[']).unwrap_or(member.len());
+                if end == 0 {
+                    return Err("empty key");
+                }
+                &member[end..]
+            };
+        } else if let Some(index) = rest.strip_prefix('[') {
+            let end = index.find(']').ok_or("unterminated index")?;
+            let valid = match index[..end].strip_prefix('#') {
+                Some("") => true,
+                Some(from_end) => from_end.strip_prefix('-').is_some_and(is_digits),
+                None => is_digits(&index[..end]),
+            };
+            if !valid {
+                return Err("array indexes must be numbers");
+            }
+            rest = &index[end + 1..];
+        } else {
+            return Err("expected . or [ after $");
+        }
+    }
+    Ok(())
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn parse_time(value: &str, now: DateTime<Utc>) -> anyhow::Result<DateTime<Utc>> {
+    if let Ok(instant) = DateTime::parse_from_rfc3339(value) {
+        return Ok(instant.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+
+    let invalid = || anyhow!("Invalid time: {value} (use RFC 3339, YYYY-MM-DD or an age like 24h)");
+    let (split, _) = value.char_indices().last().ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let age = match unit {
+        "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => None,
+    };
+    age.and_then(|age| now.checked_sub_signed(age)).ok_or_else(invalid)
+}
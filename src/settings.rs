@@ -0,0 +1,65 @@
+use std::str::FromStr;
+
+use crate::{egress::EgressPolicy, forwarded::TrustedProxies, outbound::OutboundSettings};
+
+/// What ingest does with traffic for a webhook ID that was never created
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownWebhookPolicy {
+    /// Answer 404 without storing anything
+    #[default]
+    Reject,
+    /// Create the webhook on first use; the first sender gets its management token
+    /// in an `X-Webhook-Token` response header
+    AutoCreate,
+}
+
+impl FromStr for UnknownWebhookPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" | "404" => Ok(Self::Reject),
+            "create" | "auto-create" => Ok(Self::AutoCreate),
+            other => Err(anyhow::anyhow!("Unknown webhook policy: {other}")),
+        }
+    }
+}
+
+/// Runtime settings, read from the environment at startup
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// `UNKNOWN_WEBHOOK_POLICY`: `reject` (default) or `create`
+    pub unknown_webhooks: UnknownWebhookPolicy,
+    /// `ADMIN_TOKEN`: master token that may list and manage every webhook
+    pub admin_token: Option<String>,
+    /// `RETENTION_HOURS`: unpinned requests older than this are purged (off by default, 0 keeps everything)
+    pub retention_hours: Option<u64>,
+    /// `EGRESS_*`: where replayed and forwarded requests may go
+    pub egress: EgressPolicy,
+    /// `OUTBOUND_*`: timeouts, redirects, proxy and TLS of the outbound client
+    pub outbound: OutboundSettings,
+    /// `TRUSTED_PROXIES`: proxies whose forwarding headers name the real client
+    pub trusted_proxies: TrustedProxies,
+}
+
+impl Settings {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut settings = Settings::default();
+
+        if let Ok(policy) = std::env::var("UNKNOWN_WEBHOOK_POLICY") {
+            settings.unknown_webhooks = policy.parse()?;
+        }
+        settings.admin_token = std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty());
+
+        if let Ok(hours) = std::env::var("RETENTION_HOURS") {
+            let hours: u64 = hours.parse()?;
+            settings.retention_hours = Some(hours).filter(|h| *h > 0);
+        }
+
+        settings.egress = EgressPolicy::from_env()?;
+        settings.outbound = OutboundSettings::from_env()?;
+        settings.trusted_proxies = TrustedProxies::from_env()?;
+
+        Ok(settings)
+    }
+}
@@ -0,0 +1,376 @@
+use axum::http::HeaderMap;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::models::StoredRequest;
+
+type HmacSha256 = Hmac<Sha256>;
+type HmacSha1 = Hmac<Sha1>;
+
+/// How far a signed timestamp may drift from our clock before we call it a replay
+pub const TIMESTAMP_TOLERANCE_SECS: i64 = 300;
+
+/// Webhook providers whose signing schemes we know how to check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureProvider {
+    Github,
+    Stripe,
+    Slack,
+    Shopify,
+    Twilio,
+    /// Svix and anything else following the Standard Webhooks spec
+    StandardWebhooks,
+}
+
+impl FromStr for SignatureProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "github" => Ok(Self::Github),
+            "stripe" => Ok(Self::Stripe),
+            "slack" => Ok(Self::Slack),
+            "shopify" => Ok(Self::Shopify),
+            "twilio" => Ok(Self::Twilio),
+            "svix" | "standard" | "standard-webhooks" => Ok(Self::StandardWebhooks),
+            other => Err(anyhow::anyhow!("Unknown signature provider: {other}")),
+        }
+    }
+}
+
+/// Outcome of checking a captured request's signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Valid,
+    Invalid(String),
+    Missing(String),
+}
+
+impl Verdict {
+    /// Short status stored on the request: `valid`, `invalid` or `missing`
+    pub fn status(&self) -> &'static str {
+        match self {
+            Verdict::Valid => "valid",
+            Verdict::Invalid(_) => "invalid",
+            Verdict::Missing(_) => "missing",
+        }
+    }
+
+    pub fn reason(&self) -> String {
+        match self {
+            Verdict::Valid => "Signature matches".to_string(),
+            Verdict::Invalid(reason) | Verdict::Missing(reason) => reason.clone(),
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Verdict::Valid)
+    }
+}
+
+/// Verify a request against the provider's signing scheme.
+///
+/// `url` is the full URL the sender posted to (only Twilio signs it) and
+/// `now` is the current Unix time used for timestamp tolerance checks.
+pub fn verify(
+    provider: SignatureProvider,
+    secret: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+    url: &str,
+    now: i64,
+) -> Verdict {
+    match provider {
+        SignatureProvider::Github => verify_github(secret, headers, body),
+        SignatureProvider::Stripe => verify_stripe(secret, headers, body, now),
+        SignatureProvider::Slack => verify_slack(secret, headers, body, now),
+        SignatureProvider::Shopify => verify_shopify(secret, headers, body),
+        SignatureProvider::Twilio => verify_twilio(secret, headers, body, url),
+        SignatureProvider::StandardWebhooks => verify_standard(secret, headers, body, now),
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn sha256_mac(key: &[u8], parts: &[&[u8]]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+fn check_hex(mac: HmacSha256, signature: &str) -> bool {
+    hex::decode(signature.trim())
+        .map(|expected| mac.verify_slice(&expected).is_ok())
+        .unwrap_or(false)
+}
+
+fn check_timestamp(timestamp: &str, now: i64) -> Result<(), Verdict> {
+    let ts: i64 = timestamp
+        .trim()
+        .parse()
+        .map_err(|_| Verdict::Invalid(format!("Malformed timestamp: {timestamp}")))?;
+
+    if (now - ts).abs() > TIMESTAMP_TOLERANCE_SECS {
+        return Err(Verdict::Invalid(format!(
+            "Timestamp {ts} is outside the {TIMESTAMP_TOLERANCE_SECS}s tolerance"
+        )));
+    }
+    Ok(())
+}
+
+fn verify_github(secret: &str, headers: &HeaderMap, body: &[u8]) -> Verdict {
+    let Some(value) = header(headers, "x-hub-signature-256") else {
+        return Verdict::Missing("No X-Hub-Signature-256 header".to_string());
+    };
+    let Some(signature) = value.strip_prefix("sha256=") else {
+        return Verdict::Invalid("X-Hub-Signature-256 must start with sha256=".to_string());
+    };
+
+    if check_hex(sha256_mac(secret.as_bytes(), &[body]), signature) {
+        Verdict::Valid
+    } else {
+        Verdict::Invalid("HMAC-SHA256 of the body does not match".to_string())
+    }
+}
+
+fn verify_stripe(secret: &str, headers: &HeaderMap, body: &[u8], now: i64) -> Verdict {
+    let Some(value) = header(headers, "stripe-signature") else {
+        return Verdict::Missing("No Stripe-Signature header".to_string());
+    };
+
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in value.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", t)) => timestamp = Some(t),
+            Some(("v1", sig)) => signatures.push(sig),
+            _ => {}
+        }
+    }
+
+    let Some(timestamp) = timestamp else {
+        return Verdict::Invalid("Stripe-Signature has no t= timestamp".to_string());
+    };
+    if signatures.is_empty() {
+        return Verdict::Invalid("Stripe-Signature has no v1= signature".to_string());
+    }
+    if let Err(verdict) = check_timestamp(timestamp, now) {
+        return verdict;
+    }
+
+    let mac = sha256_mac(secret.as_bytes(), &[timestamp.as_bytes(), b".", body]);
+    if signatures.iter().any(|sig| check_hex(mac.clone(), sig)) {
+        Verdict::Valid
+    } else {
+        Verdict::Invalid("No v1 signature matches the payload".to_string())
+    }
+}
+
+fn verify_slack(secret: &str, headers: &HeaderMap, body: &[u8], now: i64) -> Verdict {
+    let Some(value) = header(headers, "x-slack-signature") else {
+        return Verdict::Missing("No X-Slack-Signature header".to_string());
+    };
+    let Some(timestamp) = header(headers, "x-slack-request-timestamp") else {
+        return Verdict::Missing("No X-Slack-Request-Timestamp header".to_string());
+    };
+    let Some(signature) = value.strip_prefix("v0=") else {
+        return Verdict::Invalid("X-Slack-Signature must start with v0=".to_string());
+    };
+    if let Err(verdict) = check_timestamp(timestamp, now) {
+        return verdict;
+    }
+
+    let mac = sha256_mac(secret.as_bytes(), &[b"v0:", timestamp.as_bytes(), b":", body]);
+    if check_hex(mac, signature) {
+        Verdict::Valid
+    } else {
+        Verdict::Invalid("v0 signature does not match the payload".to_string())
+    }
+}
+
+fn verify_shopify(secret: &str, headers: &HeaderMap, body: &[u8]) -> Verdict {
+    let Some(value) = header(headers, "x-shopify-hmac-sha256") else {
+        return Verdict::Missing("No X-Shopify-Hmac-Sha256 header".to_string());
+    };
+    let Ok(expected) = STANDARD.decode(value.trim()) else {
+        return Verdict::Invalid("X-Shopify-Hmac-Sha256 is not valid base64".to_string());
+    };
+
+    if sha256_mac(secret.as_bytes(), &[body]).verify_slice(&expected).is_ok() {
+        Verdict::Valid
+    } else {
+        Verdict::Invalid("HMAC-SHA256 of the body does not match".to_string())
+    }
+}
+
+fn verify_twilio(secret: &str, headers: &HeaderMap, body: &[u8], url: &str) -> Verdict {
+    let Some(value) = header(headers, "x-twilio-signature") else {
+        return Verdict::Missing("No X-Twilio-Signature header".to_string());
+    };
+    let Ok(expected) = STANDARD.decode(value.trim()) else {
+        return Verdict::Invalid("X-Twilio-Signature is not valid base64".to_string());
+    };
+
+    // Twilio signs the URL followed by every POST parameter, sorted by name
+    let mut params: Vec<(String, String)> = serde_urlencoded::from_bytes(body).unwrap_or_default();
+    params.sort();
+
+    let mut mac = HmacSha1::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(url.as_bytes());
+    for (key, value) in &params {
+        mac.update(key.as_bytes());
+        mac.update(value.as_bytes());
+    }
+
+    if mac.verify_slice(&expected).is_ok() {
+        Verdict::Valid
+    } else {
+        Verdict::Invalid(format!("HMAC-SHA1 of {url} and the form parameters does not match"))
+    }
+}
+
+fn verify_standard(secret: &str, headers: &HeaderMap, body: &[u8], now: i64) -> Verdict {
+    // Svix sends the same headers with an svix- prefix
+    let pick = |name: &str| header(headers, &format!("webhook-{name}")).or_else(|| header(headers, &format!("svix-{name}")));
+
+    let (Some(id), Some(timestamp), Some(value)) = (pick("id"), pick("timestamp"), pick("signature")) else {
+        return Verdict::Missing("Missing webhook-id, webhook-timestamp or webhook-signature header".to_string());
+    };
+    if let Err(verdict) = check_timestamp(timestamp, now) {
+        return verdict;
+    }
+
+    let key = match standard_webhooks_key(secret) {
+        Ok(key) => key,
+        Err(reason) => return Verdict::Invalid(reason),
+    };
+    let mac = sha256_mac(&key, &[id.as_bytes(), b".", timestamp.as_bytes(), b".", body]);
+
+    let matches = value
+        .split_whitespace()
+        .filter_map(|entry| entry.strip_prefix("v1,"))
+        .filter_map(|sig| STANDARD.decode(sig).ok())
+        .any(|sig| mac.clone().verify_slice(&sig).is_ok());
+
+    if matches {
+        Verdict::Valid
+    } else {
+        Verdict::Invalid("No v1 signature matches the payload".to_string())
+    }
+}
+
+/// Standard Webhooks secrets are base64, optionally prefixed with `whsec_`
+pub fn standard_webhooks_key(secret: &str) -> Result<Vec<u8>, String> {
+    STANDARD
+        .decode(secret.strip_prefix("whsec_").unwrap_or(secret))
+        .map_err(|_| "Signing secret is not valid base64".to_string())
+}
+
+/// Scheme used to re-sign a payload we send out on forward or replay
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SigningScheme {
+    /// Hex HMAC-SHA256 of the body in a single header
+    HmacSha256Hex,
+    /// Base64 HMAC-SHA256 of the body in a single header
+    HmacSha256Base64,
+    /// `Stripe-Signature: t=…,v1=…`
+    Stripe,
+    /// `webhook-id`, `webhook-timestamp` and `webhook-signature`
+    StandardWebhooks,
+}
+
+/// Per-target signing settings for outbound requests
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutboundSigning {
+    pub scheme: SigningScheme,
+    pub secret: String,
+    /// Header for the HMAC schemes, `x-webhook-signature` by default
+    pub header: Option<String>,
+    /// Prepended to the HMAC value, e.g. `sha256=` for GitHub-style receivers
+    pub prefix: Option<String>,
+}
+
+/// Provider signature headers that no longer match once a payload is re-sent
+const STALE_SIGNATURE_HEADERS: &[&str] = &[
+    "x-hub-signature",
+    "x-hub-signature-256",
+    "stripe-signature",
+    "x-slack-signature",
+    "x-slack-request-timestamp",
+    "x-shopify-hmac-sha256",
+    "x-twilio-signature",
+    "webhook-id",
+    "webhook-timestamp",
+    "webhook-signature",
+    "svix-id",
+    "svix-timestamp",
+    "svix-signature",
+];
+
+/// Compute the headers that sign `body` under the given scheme.
+///
+/// `message_id` is only used by Standard Webhooks, which signs it alongside the body.
+pub fn sign_outbound(
+    signing: &OutboundSigning,
+    body: &[u8],
+    message_id: &str,
+    now: i64,
+) -> Result<Vec<(String, String)>, String> {
+    let secret = signing.secret.as_bytes();
+    let hmac_header = || signing.header.clone().unwrap_or_else(|| "x-webhook-signature".to_string());
+    let prefix = signing.prefix.as_deref().unwrap_or_default();
+
+    let headers = match signing.scheme {
+        SigningScheme::HmacSha256Hex => {
+            let digest = sha256_mac(secret, &[body]).finalize().into_bytes();
+            vec![(hmac_header(), format!("{prefix}{}", hex::encode(digest)))]
+        }
+        SigningScheme::HmacSha256Base64 => {
+            let digest = sha256_mac(secret, &[body]).finalize().into_bytes();
+            vec![(hmac_header(), format!("{prefix}{}", STANDARD.encode(digest)))]
+        }
+        SigningScheme::Stripe => {
+            let ts = now.to_string();
+            let digest = sha256_mac(secret, &[ts.as_bytes(), b".", body]).finalize().into_bytes();
+            vec![("stripe-signature".to_string(), format!("t={ts},v1={}", hex::encode(digest)))]
+        }
+        SigningScheme::StandardWebhooks => {
+            let key = standard_webhooks_key(&signing.secret)?;
+            let ts = now.to_string();
+            let digest = sha256_mac(&key, &[message_id.as_bytes(), b".", ts.as_bytes(), b".", body])
+                .finalize()
+                .into_bytes();
+            vec![
+                ("webhook-id".to_string(), message_id.to_string()),
+                ("webhook-timestamp".to_string(), ts),
+                ("webhook-signature".to_string(), format!("v1,{}", STANDARD.encode(digest))),
+            ]
+        }
+    };
+    Ok(headers)
+}
+
+/// Replace a stored request's provider signature with a fresh one for the new target
+pub fn resign_request(signing: &OutboundSigning, req: &mut StoredRequest, now: i64) -> Result<(), String> {
+    let mut headers: HashMap<String, String> = serde_json::from_str(&req.headers).unwrap_or_default();
+    headers.retain(|name, _| !STALE_SIGNATURE_HEADERS.contains(&name.to_ascii_lowercase().as_str()));
+
+    // Receivers deduplicate on the message ID, so every delivery attempt needs its own
+    let message_id = format!("msg_{}", uuid::Uuid::new_v4().simple());
+    for (name, value) in sign_outbound(signing, &req.raw_bytes(), &message_id, now)? {
+        headers.insert(name, value);
+    }
+
+    req.headers = serde_json::to_string(&headers).unwrap_or_default();
+    Ok(())
+}
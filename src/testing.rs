@@ -0,0 +1,202 @@
+//! The whole service inside a test: the full router on an ephemeral port, backed by
+//! an in-memory database.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use std::time::Duration;
+//! use webhook_tester::testing::TestServer;
+//!
+//! let server = TestServer::start().await?;
+//! let webhook = server.create_webhook().await?;
+//!
+//! // ... point the code under test at `webhook.url` ...
+//!
+//! let req = server
+//!     .next_request(Duration::from_secs(5), |req| req.webhook_id == webhook.id && req.method == "POST")
+//!     .await?;
+//! assert!(req.body.contains("invoice.paid"));
+//! server.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::anyhow;
+use std::{collections::VecDeque, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, oneshot, Mutex, Notify},
+    task::JoinHandle,
+};
+
+use crate::{db, models::StoredRequest, routes, schedules, settings::Settings, AppState};
+
+/// How long [`TestServer::shutdown`] waits for open connections, such as WebSockets
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// A running service; stopped by [`TestServer::shutdown`], or when dropped
+pub struct TestServer {
+    url: String,
+    state: Arc<AppState>,
+    captured: Arc<Captured>,
+    shutdown: Option<oneshot::Sender<()>>,
+    server: Option<JoinHandle<std::io::Result<()>>>,
+    background: Vec<JoinHandle<()>>,
+}
+
+/// A webhook created on a [`TestServer`]
+#[derive(Debug, Clone)]
+pub struct TestWebhook {
+    pub id: String,
+    /// Full URL requests are sent to
+    pub url: String,
+    /// Management token for the API, the dashboard and the WebSocket feed
+    pub token: String,
+}
+
+/// Requests captured since the server started and not yet returned by `next_request`
+#[derive(Default)]
+struct Captured {
+    pending: Mutex<VecDeque<StoredRequest>>,
+    arrived: Notify,
+}
+
+impl TestServer {
+    /// Start with default settings.
+    pub async fn start() -> anyhow::Result<Self> {
+        Self::with_settings(Settings::default()).await
+    }
+
+    pub async fn with_settings(settings: Settings) -> anyhow::Result<Self> {
+        let db = Arc::new(db::init_in_memory().await?);
+        let (tx, rx) = broadcast::channel(1024);
+        let state = Arc::new(AppState::with_settings(db, tx, settings)?);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+
+        let captured = Arc::new(Captured::default());
+        let mut background = vec![
+            tokio::spawn(collect(state.clone(), rx, captured.clone())),
+            schedules::spawn_scheduler(state.clone()),
+        ];
+        if let Some(hours) = state.settings.retention_hours {
+            background.push(crate::retention::spawn_retention(state.db.clone(), hours));
+        }
+
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let app = routes::router(state.clone());
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .with_graceful_shutdown(async {
+                    signal.await.ok();
+                })
+                .await
+        });
+
+        Ok(Self {
+            url,
+            state,
+            captured,
+            shutdown: Some(shutdown),
+            server: Some(server),
+            background,
+        })
+    }
+
+    /// Base URL, such as `http://127.0.0.1:49152`
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The server's state, for inspecting or seeding the database directly
+    pub fn state(&self) -> &Arc<AppState> {
+        &self.state
+    }
+
+    /// Create a webhook, as `GET /new` does.
+    pub async fn create_webhook(&self) -> anyhow::Result<TestWebhook> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let token = self.state.create_webhook(&id).await?;
+        Ok(TestWebhook {
+            url: format!("{}/webhook/{id}", self.url),
+            id,
+            token,
+        })
+    }
+
+    /// The oldest captured request matching `predicate` that no earlier call has returned,
+    /// waiting up to `timeout` for one to arrive. Requests that came in before the call
+    /// count, so there is no race between sending a request and waiting for it.
+    pub async fn next_request(
+        &self,
+        timeout: Duration,
+        predicate: impl Fn(&StoredRequest) -> bool,
+    ) -> anyhow::Result<StoredRequest> {
+        let wait = async {
+            loop {
+                // Registered before looking, so an arrival in between isn't missed
+                let arrived = self.captured.arrived.notified();
+                tokio::pin!(arrived);
+                arrived.as_mut().enable();
+
+                {
+                    let mut pending = self.captured.pending.lock().await;
+                    if let Some(index) = pending.iter().position(&predicate) {
+                        return pending.remove(index).expect("the index was just found");
+                    }
+                }
+                arrived.await;
+            }
+        };
+
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| anyhow!("No matching request arrived within {timeout:?}"))
+    }
+
+    /// Stop accepting connections and wait for open ones to finish, for a few seconds at most.
+    pub async fn shutdown(mut self) -> anyhow::Result<()> {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        if let Some(mut server) = self.server.take() {
+            match tokio::time::timeout(SHUTDOWN_GRACE, &mut server).await {
+                Ok(result) => result??,
+                Err(_) => server.abort(),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(server) = &self.server {
+            server.abort();
+        }
+        for task in &self.background {
+            task.abort();
+        }
+    }
+}
+
+/// Keep every captured request for `next_request`, read back in full from the database
+async fn collect(state: Arc<AppState>, mut rx: broadcast::Receiver<crate::models::WebhookEvent>, captured: Arc<Captured>) {
+    loop {
+        let event = match rx.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                eprintln!("Test server missed {missed} requests");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        match state.db.get_request(&event.request_id).await {
+            Ok(req) => {
+                captured.pending.lock().await.push_back(req);
+                captured.arrived.notify_waiters();
+            }
+            Err(err) => eprintln!("Test server could not load request {}: {err}", event.request_id),
+        }
+    }
+}
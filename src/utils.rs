@@ -1,10 +1,11 @@
 use axum::http::HeaderMap;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
+use crate::egress;
+use crate::AppState;
 use crate::models::StoredRequest;
-use reqwest::Client;
-use sqlx::SqlitePool;
 use crate::db::Database;
 
 pub fn headers_to_json(headers: &HeaderMap) -> Value {
@@ -30,41 +31,31 @@ pub fn headers_to_map(headers: &HeaderMap) -> HashMap<String, String> {
         .collect()
 }
 
-/// Forward the webhook request to another URL
-pub async fn forward_request(forward_url: &str, req: &StoredRequest) -> Result<(), reqwest::Error> {
-    let client = Client::new();
+/// Parse a stored timestamp: RFC 3339, or SQLite's `CURRENT_TIMESTAMP` format (UTC)
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").map(|t| t.and_utc()))
+        .ok()
+}
+
+/// Forward the webhook request to another URL, if the egress policy allows it
+pub async fn forward_request(state: &AppState, forward_url: &str, req: &StoredRequest) -> Result<(), String> {
+    state.settings.egress.check_target(forward_url)?;
+
+    let client = state.client_for(forward_url).await;
     let headers: HashMap<String, String> = serde_json::from_str(&req.headers).unwrap_or_default();
-    let mut request_builder = client.post(forward_url).body(req.body.clone());
+    let mut request_builder = client.post(forward_url).body(req.raw_bytes());
 
     for (key, value) in headers {
         request_builder = request_builder.header(&key, &value);
     }
 
-    let _res = request_builder.send().await?;
+    let _res = request_builder.send().await.map_err(|e| egress::describe_error(&e))?;
 
     Ok(())
 }
 
 pub async fn new_for_tests() -> Arc<Database> {
-    let pool = SqlitePool::connect(":memory:").await.unwrap();
-
-    sqlx::query(
-        r#"
-        CREATE TABLE requests (
-            id TEXT PRIMARY KEY,
-            webhook_id TEXT NOT NULL,
-            method TEXT NOT NULL,
-            headers TEXT NOT NULL,
-            body TEXT NOT NULL,
-            query TEXT,
-            created_at TEXT NOT NULL
-        )
-        "#,
-    )
-        .execute(&pool)
-        .await
-        .unwrap();
-
-    Arc::new(Database { pool })
+    Arc::new(crate::db::init_in_memory().await.unwrap())
 }
-
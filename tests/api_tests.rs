@@ -0,0 +1,68 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    routing::{get, post},
+    Router,
+};
+use tower::ServiceExt;
+
+use webhook_tester::models::StoredRequest;
+use webhook_tester::routes::api::{get_request, list_requests};
+use webhook_tester::routes::webhook::webhook_handler;
+use common::{register_webhook, test_state};
+
+#[tokio::test]
+async fn api_lists_requests_with_recorded_response() {
+    let state = test_state().await;
+    let token = register_webhook(&state, "api-wh").await;
+
+    let app = Router::new()
+        .route("/webhook/:id", post(webhook_handler))
+        .route("/api/webhooks/:id/requests", get(list_requests))
+        .route("/api/requests/:req_id", get(get_request))
+        .with_state(state);
+
+    app.clone()
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/api-wh")
+                .method("POST")
+                .body(Body::from("ping"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/webhooks/api-wh/requests")
+                .header("authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let requests: Vec<StoredRequest> = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].body, "ping");
+    assert_eq!(requests[0].response_status, Some(200));
+    assert_eq!(requests[0].response_source.as_deref(), Some("default"));
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/requests/missing")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
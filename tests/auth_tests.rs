@@ -0,0 +1,131 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use common::{app, test_state};
+
+async fn new_webhook(app: &Router) -> (String, String) {
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri("/new").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let created: Value = serde_json::from_slice(&bytes).unwrap();
+
+    let token = created["token"].as_str().unwrap().to_string();
+    assert!(created["dashboard_url"].as_str().unwrap().ends_with(&token));
+    (created["id"].as_str().unwrap().to_string(), token)
+}
+
+fn config_request(id: &str, auth: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder()
+        .uri(format!("/webhook/{id}/config"))
+        .method("POST")
+        .header("content-type", "application/json");
+    if let Some(auth) = auth {
+        builder = builder.header("authorization", auth);
+    }
+    builder
+        .body(Body::from(json!({ "status_code": 201 }).to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn config_requires_the_webhook_token() {
+    let app = app(test_state().await);
+    let (id, token) = new_webhook(&app).await;
+
+    let response = app.clone().oneshot(config_request(&id, None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .clone()
+        .oneshot(config_request(&id, Some("Bearer not-the-token")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app
+        .oneshot(config_request(&id, Some(&format!("Bearer {token}"))))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn ingest_stays_public_but_reads_need_token() {
+    let app = app(test_state().await);
+    let (id, token) = new_webhook(&app).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/webhook/{id}"))
+                .method("POST")
+                .body(Body::from("public"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/webhooks/{id}/requests"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/dashboard/{id}?token={token}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert!(String::from_utf8(bytes.to_vec()).unwrap().contains("public"));
+}
+
+#[tokio::test]
+async fn unknown_webhooks_are_not_found() {
+    let app = app(test_state().await);
+
+    let response = app
+        .oneshot(config_request("never-created", Some("Bearer anything")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn tokenless_webhooks_are_closed_until_migrated() {
+    let state = test_state().await;
+    state.db.create_webhook("legacy", None).await.unwrap();
+    let app = app(state.clone());
+
+    let response = app.clone().oneshot(config_request("legacy", None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.oneshot(config_request("legacy", Some("Bearer guess"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    webhook_tester::db::run_migrations(&state.db.pool).await.unwrap();
+    assert!(state.db.get_token_hash("legacy").await.unwrap().flatten().is_some());
+}
@@ -0,0 +1,199 @@
+use flate2::{
+    write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+    Compression,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::time::Duration;
+
+use webhook_tester::body::{decode_content, decode_content_async, indent_xml, parse, FormField, ParsedBody};
+use webhook_tester::export::replayable_headers;
+use webhook_tester::testing::TestServer;
+
+const PAYLOAD: &[u8] = br#"{"ok":true}"#;
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// A brotli stream holding `data` in one uncompressed meta-block
+fn brotli(data: &[u8]) -> Vec<u8> {
+    let header = (((data.len() - 1) as u32) << 4) | (1 << 20);
+    let mut out = header.to_le_bytes()[..3].to_vec();
+    out.extend_from_slice(data);
+    out.push(0x03);
+    out
+}
+
+#[test]
+fn content_encodings_are_undone_in_order() {
+    let decoded = decode_content(Some("gzip"), &gzip(PAYLOAD)).unwrap();
+    assert_eq!(decoded.bytes, PAYLOAD);
+    assert_eq!(decoded.encoding.as_deref(), Some("gzip"));
+
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(PAYLOAD).unwrap();
+    assert_eq!(decode_content(Some("deflate"), &zlib.finish().unwrap()).unwrap().bytes, PAYLOAD);
+
+    let mut raw = DeflateEncoder::new(Vec::new(), Compression::default());
+    raw.write_all(PAYLOAD).unwrap();
+    assert_eq!(decode_content(Some("Deflate"), &raw.finish().unwrap()).unwrap().bytes, PAYLOAD);
+
+    assert_eq!(decode_content(Some("br"), &brotli(PAYLOAD)).unwrap().bytes, PAYLOAD);
+
+    // Listed in the order they were applied: gzip first, then br
+    let stacked = decode_content(Some("gzip, br"), &brotli(&gzip(PAYLOAD))).unwrap();
+    assert_eq!(stacked.bytes, PAYLOAD);
+    assert_eq!(stacked.encoding.as_deref(), Some("gzip, br"));
+
+    let plain = decode_content(Some("identity"), PAYLOAD).unwrap();
+    assert_eq!(plain.encoding, None);
+
+    assert!(decode_content(Some("gzip"), PAYLOAD).unwrap_err().contains("gzip"));
+    assert!(decode_content(Some("zstd"), PAYLOAD).unwrap_err().contains("Unsupported"));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn decoding_on_the_blocking_pool_gives_the_same_result() {
+    let decoded = decode_content_async(Some("gzip"), gzip(PAYLOAD).into()).await.unwrap();
+    assert_eq!(decoded, decode_content(Some("gzip"), &gzip(PAYLOAD)).unwrap());
+
+    let plain = decode_content_async(None, PAYLOAD.to_vec().into()).await.unwrap();
+    assert_eq!(plain.bytes, PAYLOAD);
+    assert_eq!(plain.encoding, None);
+
+    assert!(decode_content_async(Some("gzip"), PAYLOAD.to_vec().into()).await.unwrap_err().contains("gzip"));
+}
+
+#[tokio::test]
+async fn bodies_are_parsed_by_content_type() {
+    assert_eq!(parse(Some("application/json"), b"").await, ParsedBody::Empty);
+    assert_eq!(
+        parse(Some("application/vnd.api+json; charset=utf-8"), PAYLOAD).await,
+        ParsedBody::Json { value: json!({"ok": true}) }
+    );
+    assert_eq!(parse(None, PAYLOAD).await, ParsedBody::Json { value: json!({"ok": true}) });
+
+    assert_eq!(
+        parse(Some("application/x-www-form-urlencoded"), b"name=Ada+Lovelace&tag=a&tag=b").await,
+        ParsedBody::Form {
+            fields: vec![
+                FormField { name: "name".into(), value: "Ada Lovelace".into() },
+                FormField { name: "tag".into(), value: "a".into() },
+                FormField { name: "tag".into(), value: "b".into() },
+            ]
+        }
+    );
+
+    let xml = parse(Some("text/xml"), br#"<order id="7"><item>tea</item></order>"#).await;
+    assert_eq!(xml, ParsedBody::Xml { text: "<order id=\"7\">\n  <item>tea</item>\n</order>".into() });
+    assert_eq!(indent_xml(b"<open>"), None);
+    assert_eq!(parse(Some("application/xml"), b"<a></b>").await, ParsedBody::Text { text: "<a></b>".into() });
+
+    assert_eq!(parse(Some("image/png"), &[0x89, b'P', 0xff]).await, ParsedBody::Binary { size: 3 });
+    assert_eq!(parse(Some("application/json"), b"{oops").await, ParsedBody::Text { text: "{oops".into() });
+}
+
+#[tokio::test]
+async fn multipart_parts_carry_file_metadata() {
+    let body = "--XYZ\r\n\
+        Content-Disposition: form-data; name=\"note\"\r\n\r\n\
+        hello\r\n\
+        --XYZ\r\n\
+        Content-Disposition: form-data; name=\"upload\"; filename=\"logo.png\"\r\n\
+        Content-Type: image/png\r\n\r\n\
+        \u{1}\u{2}\u{3}\u{4}\r\n\
+        --XYZ--\r\n";
+
+    let ParsedBody::Multipart { parts } = parse(Some("multipart/form-data; boundary=XYZ"), body.as_bytes()).await else {
+        panic!("expected a multipart body");
+    };
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0].name.as_deref(), Some("note"));
+    assert_eq!(parts[0].text.as_deref(), Some("hello"));
+    assert_eq!(parts[1].filename.as_deref(), Some("logo.png"));
+    assert_eq!(parts[1].content_type.as_deref(), Some("image/png"));
+    assert_eq!(parts[1].size, 4);
+    assert_eq!(parts[1].text, None);
+}
+
+#[tokio::test]
+async fn compressed_requests_are_stored_decoded_with_the_raw_original() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let compressed = gzip(PAYLOAD);
+
+    let response = reqwest::Client::new()
+        .post(&webhook.url)
+        .header("content-type", "application/json")
+        .header("content-encoding", "gzip")
+        .body(compressed.clone())
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let req = server.next_request(Duration::from_secs(5), |_| true).await.unwrap();
+    assert_eq!(req.body, r#"{"ok":true}"#);
+    assert_eq!(req.body_encoding.as_deref(), Some("gzip"));
+    assert_eq!(req.raw_bytes(), compressed);
+    assert!(!replayable_headers(&req).contains_key("content-encoding"));
+
+    let client = reqwest::Client::new();
+    let view: Value = client
+        .get(format!("{}/api/requests/{}/body", server.url(), req.id))
+        .bearer_auth(&webhook.token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(view["encoding"], "gzip");
+    assert_eq!(view["raw_size"], compressed.len());
+    assert_eq!(view["body"], json!({"kind": "json", "value": {"ok": true}}));
+
+    let raw = client
+        .get(format!("{}/api/requests/{}/raw", server.url(), req.id))
+        .bearer_auth(&webhook.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(raw.status(), StatusCode::OK);
+    assert!(raw.headers()["content-disposition"].to_str().unwrap().contains(".gz"));
+    assert_eq!(raw.bytes().await.unwrap().as_ref(), compressed.as_slice());
+}
+
+#[tokio::test]
+async fn binary_bodies_keep_their_bytes() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let bytes = vec![0x00, 0xff, 0xfe, 0x10];
+
+    reqwest::Client::new()
+        .post(&webhook.url)
+        .header("content-type", "application/octet-stream")
+        .body(bytes.clone())
+        .send()
+        .await
+        .unwrap();
+
+    let req = server.next_request(Duration::from_secs(5), |_| true).await.unwrap();
+    assert_eq!(req.body, "");
+    assert_eq!(req.body_encoding, None);
+    assert_eq!(req.raw_bytes(), bytes);
+
+    let view: Value = reqwest::Client::new()
+        .get(format!("{}/api/requests/{}/body", server.url(), req.id))
+        .bearer_auth(&webhook.token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(view["body"], json!({"kind": "binary", "size": 4}));
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use axum::{
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use webhook_tester::cli::{self, Command, FileFormat, RequestFilter, Server};
+use webhook_tester::models::StoredRequest;
+use webhook_tester::replay::replay_request;
+use webhook_tester::routes::{
+    api::{export_requests, get_request, list_requests, search_requests},
+    webhook::{create_webhook, set_custom_response, update_custom_response, webhook_handler},
+};
+use webhook_tester::AppState;
+use common::{loopback_state, register_webhook, serve, test_state};
+
+async fn server(state: Arc<AppState>) -> Server {
+    let url = serve(
+        Router::new()
+            .route("/new", get(create_webhook))
+            .route("/webhook/:id", post(webhook_handler))
+            .route("/webhook/:id/config", post(set_custom_response).patch(update_custom_response))
+            .route("/api/webhooks/:id/requests", get(list_requests))
+            .route("/api/webhooks/:id/search", get(search_requests))
+            .route("/api/webhooks/:id/export", get(export_requests))
+            .route("/api/requests/:req_id", get(get_request))
+            .route("/replay/:req_id", post(replay_request))
+            .with_state(state),
+    )
+    .await;
+    Server { url, token: None }
+}
+
+async fn first_request(state: &AppState, webhook_id: &str) -> StoredRequest {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    state.db.stream_requests(webhook_id, &[], tx).await.unwrap();
+    rx.recv().await.unwrap()
+}
+
+#[tokio::test]
+async fn new_creates_a_webhook() {
+    let state = test_state().await;
+    let server = server(state.clone()).await;
+
+    cli::run(Command::New { server }).await.unwrap();
+    assert_eq!(state.db.list_webhooks(false).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn config_ls_show_replay_and_export() {
+    let state = loopback_state().await;
+    let token = register_webhook(&state, "cli").await;
+    let server = Server { token: Some(token.clone()), ..server(state.clone()).await };
+
+    let signing = serde_json::json!({"signing_provider": "github", "signing_secret": "s3cret", "content_type": "text/plain"});
+    reqwest::Client::new()
+        .post(format!("{}/webhook/cli/config", server.url))
+        .bearer_auth(&token)
+        .json(&signing)
+        .send()
+        .await
+        .unwrap();
+    cli::run(Command::Config {
+        webhook_id: "cli".into(),
+        status: Some(500),
+        body: Some("nope".into()),
+        content_type: None,
+        forward_url: None,
+        server: server.clone(),
+    })
+    .await
+    .unwrap();
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/webhook/cli?attempt=1", server.url))
+        .body(r#"{"order":42}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(response.text().await.unwrap(), "nope");
+    let req = first_request(&state, "cli").await;
+    // Settings the command didn't mention are kept
+    let config = state.get_response_config("cli").await.unwrap();
+    assert_eq!(config.signing_secret.as_deref(), Some("s3cret"));
+    assert_eq!(config.content_type.as_deref(), Some("text/plain"));
+
+    cli::run(Command::Ls { webhook_id: "cli".into(), query: None, limit: 20, server: server.clone() })
+        .await
+        .unwrap();
+    cli::run(Command::Show { request_id: req.id.clone(), server: server.clone() })
+        .await
+        .unwrap();
+
+    // Replays go to the given URL
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let target = serve(Router::new().route(
+        "/hooks",
+        post(move || async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            StatusCode::ACCEPTED
+        }),
+    ))
+    .await;
+    cli::run(Command::Replay { request_id: req.id.clone(), to: format!("{target}/hooks"), server: server.clone() })
+        .await
+        .unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // A target that can't be reached fails the command
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let failed = cli::run(Command::Replay { request_id: req.id.clone(), to: format!("http://{closed}/hooks"), server: server.clone() }).await;
+    assert!(failed.is_err());
+
+    let output = std::env::temp_dir().join(format!("cli-export-{}.ndjson", req.id));
+    cli::run(Command::Export {
+        webhook_id: "cli".into(),
+        format: FileFormat::Ndjson,
+        ids: None,
+        output: Some(output.clone()),
+        server: server.clone(),
+    })
+    .await
+    .unwrap();
+    let exported = std::fs::read_to_string(&output).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    let exported: StoredRequest = serde_json::from_str(exported.lines().next().unwrap()).unwrap();
+    assert_eq!(exported.id, req.id);
+    assert_eq!(exported.body, r#"{"order":42}"#);
+}
+
+#[tokio::test]
+async fn server_errors_are_reported() {
+    let state = test_state().await;
+    let server = server(state).await;
+
+    let err = cli::run(Command::Show { request_id: "missing".into(), server }).await.unwrap_err();
+    assert!(err.to_string().starts_with("Server answered 404"), "{err}");
+}
+
+#[test]
+fn tail_filters_by_method_status_and_text() {
+    let req = StoredRequest {
+        method: "POST".into(),
+        headers: r#"{"x-event":"invoice.paid"}"#.into(),
+        body: r#"{"amount":100}"#.into(),
+        response_status: Some(503),
+        ..Default::default()
+    };
+
+    assert!(RequestFilter::default().matches(&req));
+    assert!(RequestFilter { method: Some("post".into()), ..Default::default() }.matches(&req));
+    assert!(!RequestFilter { method: Some("GET".into()), ..Default::default() }.matches(&req));
+    assert!(RequestFilter { status: Some("5xx".parse().unwrap()), ..Default::default() }.matches(&req));
+    assert!(!RequestFilter { status: Some("500".parse().unwrap()), ..Default::default() }.matches(&req));
+    assert!(RequestFilter { grep: Some("INVOICE".into()), ..Default::default() }.matches(&req));
+    assert!(RequestFilter { grep: Some("amount".into()), ..Default::default() }.matches(&req));
+    assert!(!RequestFilter { grep: Some("refund".into()), ..Default::default() }.matches(&req));
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    routing::get,
+    Router,
+};
+use std::process::Command;
+use tower::ServiceExt;
+
+use webhook_tester::codegen::{render, CodeTarget};
+use webhook_tester::models::StoredRequest;
+use webhook_tester::routes::api::request_code;
+use common::{register_webhook, test_state};
+
+const BODY: &str = "{\"note\": \"it's a \\\"test\\\" of $HOME and `id`\",\n \"emoji\": \"🚀\"}";
+
+fn tricky() -> StoredRequest {
+    StoredRequest {
+        id: "req-1".into(),
+        webhook_id: "code".into(),
+        method: "POST".into(),
+        headers: serde_json::json!({
+            "host": "hooks.example.com",
+            "content-length": "42",
+            "content-type": "application/json",
+            "x-quote": "it's \"here\" \\ $(whoami)",
+        })
+        .to_string(),
+        body: BODY.into(),
+        query: r#"{"q":"a b&c"}"#.into(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        ..Default::default()
+    }
+}
+
+/// Run a shell snippet with `program` stubbed out to print its arguments, one per NUL
+fn shell_args(snippet: &str, program: &str) -> Vec<String> {
+    let dir = std::env::temp_dir().join(format!("codegen-test-{program}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let stub = dir.join(program);
+    std::fs::write(&stub, "#!/bin/sh\nfor arg in \"$@\"; do printf '%s\\0' \"$arg\"; done\n").unwrap();
+    std::fs::set_permissions(&stub, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(snippet)
+        .env("PATH", format!("{}:{}", dir.display(), std::env::var("PATH").unwrap()))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .split_terminator('\0')
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn shell_snippets_survive_quoting() {
+    let req = tricky();
+
+    let args = shell_args(&render(&req, CodeTarget::Curl), "curl");
+    assert_eq!(
+        args,
+        vec![
+            "-sS",
+            "-X",
+            "POST",
+            "http://hooks.example.com/webhook/code?q=a+b%26c",
+            "-H",
+            "content-type: application/json",
+            "-H",
+            "x-quote: it's \"here\" \\ $(whoami)",
+            "--data-binary",
+            BODY,
+        ]
+    );
+
+    let args = shell_args(&render(&req, CodeTarget::Httpie), "http");
+    assert_eq!(
+        args,
+        vec![
+            "--ignore-stdin",
+            "POST",
+            "http://hooks.example.com/webhook/code?q=a+b%26c",
+            "content-type:application/json",
+            "x-quote:it's \"here\" \\ $(whoami)",
+            "--raw",
+            BODY,
+        ]
+    );
+}
+
+#[test]
+fn code_snippets_use_escaped_string_literals() {
+    let req = tricky();
+
+    let rust = render(&req, CodeTarget::Reqwest);
+    assert!(rust.contains(".request(reqwest::Method::POST, \"http://hooks.example.com/webhook/code?q=a+b%26c\")"));
+    assert!(rust.contains(r#".header("x-quote", "it's \"here\" \\ $(whoami)")"#));
+    assert!(rust.contains(&format!(".body({BODY:?})")));
+    assert!(!rust.contains("content-length"));
+
+    let python = render(&req, CodeTarget::Python);
+    assert!(python.starts_with("import requests\n"));
+    assert!(python.contains(r#""x-quote": "it's \"here\" \\ $(whoami)","#));
+    assert!(python.contains(&format!("data={}.encode(\"utf-8\")", serde_json::to_string(BODY).unwrap())));
+
+    let js = render(&req, CodeTarget::Fetch);
+    assert!(js.contains(r#"method: "POST","#));
+    assert!(js.contains(&format!("body: {},", serde_json::to_string(BODY).unwrap())));
+
+    // fetch can't send a body with GET
+    let get = StoredRequest { method: "GET".into(), ..tricky() };
+    assert!(!render(&get, CodeTarget::Fetch).contains("body:"));
+}
+
+#[tokio::test]
+async fn code_endpoint_renders_one_or_all_snippets() {
+    let state = test_state().await;
+    let token = register_webhook(&state, "code").await;
+    state.store_request(&tricky()).await.unwrap();
+
+    let app = Router::new()
+        .route("/api/requests/:req_id/code", get(request_code))
+        .with_state(state);
+
+    let get = |uri: &'static str| {
+        let app = app.clone();
+        let authorization = format!("Bearer {token}");
+        async move {
+            let request = Request::builder().uri(uri).header("authorization", authorization).body(Body::empty()).unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, String::from_utf8(bytes.to_vec()).unwrap())
+        }
+    };
+
+    let (status, body) = get("/api/requests/req-1/code?lang=httpie").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.starts_with("http --ignore-stdin POST"));
+
+    let (status, body) = get("/api/requests/req-1/code").await;
+    assert_eq!(status, StatusCode::OK);
+    let all: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(all.len(), 5);
+    assert!(all["python"].as_str().unwrap().contains("requests.request("));
+
+    let (status, _) = get("/api/requests/req-1/code?lang=cobol").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = get("/api/requests/missing/code?lang=curl").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
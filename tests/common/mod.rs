@@ -1,44 +1,82 @@
+#![allow(dead_code)]
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
 use std::sync::Arc;
 use tokio::sync::broadcast;
-use webhook_tester::{AppState, db};
+use tower::ServiceExt;
+use webhook_tester::testing::{TestServer, TestWebhook};
+use webhook_tester::{AppState, db, routes, settings::Settings};
 
 pub async fn test_state() -> Arc<AppState> {
-    let pool = sqlx::SqlitePool::connect(":memory:").await.unwrap();
-
-    sqlx::query("PRAGMA foreign_keys = ON;").execute(&pool).await.unwrap();
-
-    sqlx::query(
-        "CREATE TABLE requests (
-            id TEXT PRIMARY KEY,
-            webhook_id TEXT,
-            method TEXT,
-            headers TEXT,
-            body TEXT,
-            query TEXT,
-            created_at TEXT
-        )"
-    )
-        .execute(&pool)
-        .await
-        .unwrap();
-
-    sqlx::query(
-        "CREATE TABLE webhook_configs (
-            webhook_id TEXT PRIMARY KEY,
-            status_code INTEGER,
-            response_body TEXT,
-            content_type TEXT,
-            forward_url TEXT
-        )"
-    )
-        .execute(&pool)
-        .await
-        .unwrap();
+    let (tx, _) = broadcast::channel(10);
+
+    Arc::new(AppState::new(Arc::new(db::init_in_memory().await.unwrap()), tx).unwrap())
+}
 
+/// [`test_state`] that may replay and forward to servers on this machine, which the
+/// default egress policy refuses
+pub async fn loopback_state() -> Arc<AppState> {
     let (tx, _) = broadcast::channel(10);
+    let mut settings = Settings::default();
+    settings.egress.allowed_cidrs = vec!["127.0.0.0/8".parse().unwrap()];
+
+    Arc::new(AppState::with_settings(Arc::new(db::init_in_memory().await.unwrap()), tx, settings).unwrap())
+}
+
+/// Create a webhook with a known ID and return its management token
+pub async fn register_webhook(state: &AppState, id: &str) -> String {
+    state.create_webhook(id).await.unwrap()
+}
+
+/// Every route of the service over `state`
+pub fn app(state: Arc<AppState>) -> Router {
+    routes::router(state)
+}
+
+/// Serve `app` on a free loopback port and return its base URL
+pub async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{addr}")
+}
+
+/// Send a JSON request to `app` with a bearer token, returning the status and JSON body
+/// (`Null` when the body isn't JSON)
+pub async fn call(app: &Router, method: &str, uri: &str, token: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri).header("authorization", format!("Bearer {token}"));
+    if body.is_some() {
+        request = request.header("content-type", "application/json");
+    }
+    let body = body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty);
+    let response = app.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
 
-    Arc::new(AppState {
-        db: Arc::new(db::Database { pool }),
-        tx,
-    })
+/// [`call`] over HTTP to a [`TestServer`], with the webhook's token
+pub async fn call_server(
+    server: &TestServer,
+    webhook: &TestWebhook,
+    method: &str,
+    path: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let mut request = reqwest::Client::new()
+        .request(method.parse().unwrap(), format!("{}{path}", server.url()))
+        .bearer_auth(&webhook.token);
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    let response = request.send().await.unwrap();
+    let status = response.status();
+    (status, response.json().await.unwrap_or(Value::Null))
 }
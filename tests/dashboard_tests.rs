@@ -4,11 +4,12 @@ use axum::{Router, routing::get};
 use tower::ServiceExt;
 use axum::http::Request;
 use webhook_tester::routes::dashboard::dashboard_handler;
-use common::test_state;
+use common::{register_webhook, test_state};
 
 #[tokio::test]
 async fn dashboard_renders_html() {
     let state = test_state().await;
+    let token = register_webhook(&state, "test").await;
 
     let app = Router::new()
         .route("/dashboard/:id", get(dashboard_handler))
@@ -17,7 +18,7 @@ async fn dashboard_renders_html() {
     let response = app
         .oneshot(
             Request::builder()
-                .uri("/dashboard/test")
+                .uri(format!("/dashboard/test?token={token}"))
                 .body(axum::body::Body::empty())
                 .unwrap(),
         )
@@ -9,15 +9,8 @@ async fn create_and_fetch_request() {
     let pool = SqlitePool::connect(":memory:").await.unwrap();
     let db = Database { pool };
 
-    sqlx::query("CREATE TABLE requests (
-        id TEXT PRIMARY KEY,
-        webhook_id TEXT,
-        method TEXT,
-        headers TEXT,
-        body TEXT,
-        query TEXT,
-        created_at TEXT
-    )").execute(&db.pool).await.unwrap();
+    webhook_tester::db::run_migrations(&db.pool).await.unwrap();
+    db.create_webhook("wh", None).await.unwrap();
 
     let req = StoredRequest {
         id: "1".into(),
@@ -27,10 +20,76 @@ async fn create_and_fetch_request() {
         body: "body".into(),
         query: "{}".into(),
         created_at: "now".into(),
+        response_status: Some(200),
+        response_headers: None,
+        response_body: Some("OK".into()),
+        response_source: Some("default".into()),
+        latency_ms: Some(0.5),
+        ..Default::default()
     };
 
     db.store_request(&req).await.unwrap();
     let fetched = db.get_request("1").await.unwrap();
 
     assert_eq!(fetched.body, "body");
+    assert_eq!(fetched.response_status, Some(200));
+    assert_eq!(fetched.response_source.as_deref(), Some("default"));
+}
+
+#[tokio::test]
+async fn migration_adds_foreign_key_and_keeps_legacy_rows() {
+    let db = webhook_tester::db::init_in_memory().await.unwrap();
+    let pool = &db.pool;
+
+    // Recreate the original schema, with a request for a webhook that was never created
+    for table in ["deliveries", "request_files", "replay_schedules", "requests", "webhook_configs", "sequence_counters", "webhooks"] {
+        sqlx::query(&format!("DROP TABLE {table}")).execute(pool).await.unwrap();
+    }
+    sqlx::query("CREATE TABLE webhooks (id TEXT PRIMARY KEY, created_at TEXT)").execute(pool).await.unwrap();
+    sqlx::query("CREATE TABLE requests (id TEXT PRIMARY KEY, webhook_id TEXT NOT NULL, method TEXT NOT NULL,
+                 headers TEXT, body TEXT, query TEXT, created_at TEXT)")
+        .execute(pool).await.unwrap();
+    sqlx::query("CREATE TABLE webhook_configs (webhook_id TEXT PRIMARY KEY, status_code INTEGER,
+                 response_body TEXT, content_type TEXT, forward_url TEXT)")
+        .execute(pool).await.unwrap();
+    sqlx::query("INSERT INTO requests (id, webhook_id, method, body) VALUES ('old', 'orphan', 'POST', 'kept')")
+        .execute(pool).await.unwrap();
+
+    webhook_tester::db::run_migrations(pool).await.unwrap();
+
+    assert!(db.webhook_exists("orphan").await.unwrap());
+    assert_eq!(db.get_request("old").await.unwrap().body, "kept");
+
+    let foreign_keys: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_foreign_key_list('requests') WHERE \"table\" = 'webhooks'")
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    assert_eq!(foreign_keys, 1);
+
+    sqlx::query("DELETE FROM webhooks WHERE id = 'orphan'").execute(pool).await.unwrap();
+    assert!(db.get_request("old").await.is_err());
+}
+
+#[tokio::test]
+async fn migration_drops_stored_file_contents() {
+    let db = webhook_tester::db::init_in_memory().await.unwrap();
+    let pool = &db.pool;
+
+    sqlx::query("DROP TABLE request_files").execute(pool).await.unwrap();
+    sqlx::query("CREATE TABLE request_files (id TEXT PRIMARY KEY, request_id TEXT NOT NULL, part_index INTEGER NOT NULL,
+                 field_name TEXT, filename TEXT NOT NULL, content_type TEXT, size INTEGER NOT NULL,
+                 created_at TEXT NOT NULL, data BLOB NOT NULL)")
+        .execute(pool).await.unwrap();
+    sqlx::query("INSERT INTO request_files (id, request_id, part_index, filename, size, created_at, data)
+                 VALUES ('f', 'r', 1, 'a.png', 3, 'now', x'010203')")
+        .execute(pool).await.unwrap();
+
+    webhook_tester::db::run_migrations(pool).await.unwrap();
+
+    let data_columns: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info('request_files') WHERE name = 'data'")
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    assert_eq!(data_columns, 0);
+    assert_eq!(db.get_request_file("r", "f").await.unwrap().unwrap().filename, "a.png");
 }
@@ -0,0 +1,196 @@
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    routing::post,
+    Router,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
+use tower::ServiceExt;
+
+use webhook_tester::models::StoredRequest;
+use webhook_tester::replay::{replay_request, ForwardRequestFn, RequestEdit, MOCK_FORWARD_REQUEST};
+use webhook_tester::utils::new_for_tests;
+use webhook_tester::AppState;
+
+fn original() -> StoredRequest {
+    StoredRequest {
+        id: "orig".into(),
+        webhook_id: "wh".into(),
+        method: "POST".into(),
+        headers: r#"{"content-length":"52","content-type":"application/json","x-drop":"1","x-keep":"old"}"#.into(),
+        body: r#"{"order":{"id":1,"status":"paid"},"ts":100,"items":[]}"#.into(),
+        query: r#"{"page":"1"}"#.into(),
+        created_at: "2025-01-01T00:00:00Z".into(),
+        response_status: Some(200),
+        ..Default::default()
+    }
+}
+
+fn edit(value: serde_json::Value) -> RequestEdit {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn edits_apply_to_method_headers_query_and_json_body() {
+    let derived = edit(json!({
+        "method": "put",
+        "set_headers": {"X-Keep": "new", "x-added": "yes"},
+        "remove_headers": ["X-Drop"],
+        "query": {"page": "2", "debug": "true"},
+        "merge_patch": {"order": {"status": "refunded"}, "ts": 200},
+        "json_patch": [{"op": "add", "path": "/items/-", "value": "sku-1"}]
+    }))
+    .apply(&original())
+    .unwrap();
+
+    assert_ne!(derived.id, "orig");
+    assert_eq!(derived.derived_from.as_deref(), Some("orig"));
+    assert_eq!(derived.webhook_id, "wh");
+    assert_eq!(derived.method, "PUT");
+    assert_eq!(derived.response_status, None);
+
+    let headers = derived.header_map();
+    assert_eq!(headers["x-keep"], "new");
+    assert_eq!(headers["x-added"], "yes");
+    assert!(!headers.contains_key("x-drop"));
+    // The body changed, so its captured length is gone
+    assert!(!headers.contains_key("content-length"));
+
+    assert_eq!(derived.query_map()["debug"], "true");
+    let body: serde_json::Value = serde_json::from_str(&derived.body).unwrap();
+    assert_eq!(body, json!({"order": {"id": 1, "status": "refunded"}, "ts": 200, "items": ["sku-1"]}));
+
+    // Replacing the body and then patching works on the new body
+    let derived = edit(json!({"body": "{\"a\":1}", "merge_patch": {"b": 2}})).apply(&original()).unwrap();
+    assert_eq!(derived.body, r#"{"a":1,"b":2}"#);
+
+    // Untouched requests keep everything, including the content length
+    let copy = RequestEdit::default().apply(&original()).unwrap();
+    assert_eq!(copy.body, original().body);
+    assert_eq!(copy.header_map()["content-length"], "52");
+}
+
+#[test]
+fn invalid_edits_are_reported() {
+    let plain = StoredRequest { body: "not json".into(), ..original() };
+    assert!(edit(json!({"merge_patch": {"a": 1}})).apply(&plain).unwrap_err().contains("not JSON"));
+
+    let err = edit(json!({"json_patch": [{"op": "remove", "path": "/missing"}]})).apply(&original()).unwrap_err();
+    assert!(err.contains("JSON patch failed"), "{err}");
+
+    assert!(edit(json!({"method": "NOT VALID"})).apply(&original()).is_err());
+}
+
+#[test]
+fn untouched_bodies_keep_their_raw_bytes() {
+    let binary = StoredRequest {
+        headers: r#"{"content-encoding":"gzip"}"#.into(),
+        body: String::new(),
+        body_encoding: Some("gzip".into()),
+        raw_body: Some("H4sIAAAA".into()),
+        ..original()
+    };
+
+    let derived = edit(json!({"set_headers": {"x-retry": "1"}})).apply(&binary).unwrap();
+    assert_eq!(derived.raw_bytes(), binary.raw_bytes());
+    assert_eq!(derived.body_encoding.as_deref(), Some("gzip"));
+    assert_eq!(derived.header_map()["content-encoding"], "gzip");
+
+    let derived = edit(json!({"body": "plain"})).apply(&binary).unwrap();
+    assert_eq!(derived.raw_bytes(), b"plain");
+    assert_eq!(derived.body_encoding, None);
+    assert!(!derived.header_map().contains_key("content-encoding"));
+}
+
+#[tokio::test]
+async fn edited_replay_is_stored_and_sent() {
+    let (tx, _rx) = broadcast::channel(10);
+    let db = new_for_tests().await;
+    let state = Arc::new(AppState::new(db, tx).unwrap());
+    let token = state.create_webhook("wh").await.unwrap();
+    state.db.store_request(&original()).await.unwrap();
+
+    let sent: Arc<Mutex<Option<(String, StoredRequest)>>> = Arc::new(Mutex::new(None));
+    let sent_clone = sent.clone();
+    let mock: Box<ForwardRequestFn> = Box::new(move |url: &str, req: &StoredRequest| {
+        let sent = sent_clone.clone();
+        let captured = (url.to_string(), req.clone());
+        Box::pin(async move {
+            *sent.lock().await = Some(captured);
+            Ok(())
+        })
+    });
+    MOCK_FORWARD_REQUEST.set(mock).ok();
+
+    let app = Router::new()
+        .route("/replay/:id", post(replay_request))
+        .with_state(state.clone());
+
+    let replay = |payload: serde_json::Value| {
+        let app = app.clone();
+        let authorization = format!("Bearer {token}");
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/replay/orig")
+                        .header("content-type", "application/json")
+                        .header("authorization", authorization)
+                        .body(Body::from(payload.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+        }
+    };
+
+    let (status, result) = replay(json!({
+        "target": "http://consumer.local/hook?from=replay",
+        "edit": {"merge_patch": {"ts": 999}, "query": {"page": "2"}}
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let derived_id = result["request_id"].as_str().unwrap().to_string();
+
+    let (url, sent_req) = sent.lock().await.clone().unwrap();
+    assert_eq!(url, "http://consumer.local/hook?from=replay&page=2");
+    assert_eq!(sent_req.id, derived_id);
+    assert!(sent_req.body.contains(r#""ts":999"#));
+
+    let stored = state.db.get_request(&derived_id).await.unwrap();
+    assert_eq!(stored.derived_from.as_deref(), Some("orig"));
+    assert_eq!(stored.body, sent_req.body);
+    assert_eq!(state.db.get_request("orig").await.unwrap().body, original().body);
+
+    let (status, result) = replay(json!({"target": "http://x", "edit": {"method": "bad method"}})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(result["error"].as_str().unwrap().contains("Invalid method"));
+
+    // An edit that can't be signed is never sent, so nothing is stored for it
+    let (status, result) = replay(json!({
+        "target": "http://consumer.local/hook",
+        "edit": {"merge_patch": {"ts": 1}},
+        "signing": {"scheme": "standard-webhooks", "secret": "whsec_%%%"}
+    }))
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(result["error"].is_string(), "{result}");
+    assert_eq!(state.get_requests("wh").await.len(), 2);
+
+    // Plain replays, and edits that leave the query alone, go to the target as given
+    let (_, result) = replay(json!({"target": "http://consumer.local/hook"})).await;
+    assert!(result["request_id"].is_null());
+    assert_eq!(sent.lock().await.clone().unwrap().0, "http://consumer.local/hook");
+    replay(json!({"target": "http://consumer.local/hook?from=replay", "edit": {"body": "plain"}})).await;
+    assert_eq!(sent.lock().await.clone().unwrap().0, "http://consumer.local/hook?from=replay");
+
+    // Deleting the original keeps the derived request, unlinked
+    state.db.delete_request("orig").await.unwrap();
+    assert_eq!(state.db.get_request(&derived_id).await.unwrap().derived_from, None);
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    response::Redirect,
+    routing::post,
+    Router,
+};
+use serde_json::json;
+use tower::ServiceExt;
+
+use webhook_tester::egress::{describe_error, EgressPolicy};
+use webhook_tester::models::StoredRequest;
+use webhook_tester::replay::{self, replay_request};
+use webhook_tester::routes::webhook::set_custom_response;
+use common::{loopback_state, register_webhook, serve, test_state};
+
+fn url(value: &str) -> reqwest::Url {
+    reqwest::Url::parse(value).unwrap()
+}
+
+#[test]
+fn policy_checks_schemes_hosts_and_addresses() {
+    let policy = EgressPolicy::default();
+    assert!(policy.check_url(&url("https://example.com/hook")).is_ok());
+    assert!(policy.check_url(&url("ftp://example.com/")).is_err());
+
+    // Cloud metadata and private networks, however they are spelled
+    for target in [
+        "http://169.254.169.254/latest/meta-data",
+        "http://[::ffff:169.254.169.254]/",
+        "http://[64:ff9b::a9fe:a9fe]/",
+        "http://[::a9fe:a9fe]/",
+        "http://[fd00:ec2::254]/",
+        "http://0.0.0.0:22/",
+        "http://127.0.0.1:8080/hook",
+        "http://localhost:3000/",
+        "http://[::1]/",
+        "http://[64:ff9b::7f00:1]/",
+        "http://[::7f00:1]/",
+        "http://10.0.0.5/",
+        "http://172.20.0.1/",
+        "http://192.168.1.10/",
+    ] {
+        let err = policy.check_url(&url(target)).unwrap_err();
+        assert!(err.to_string().starts_with("Blocked by egress policy"), "{target}: {err}");
+    }
+    // NAT64 addresses of public hosts are public
+    assert!(policy.check_url(&url("http://[64:ff9b::5db8:d822]/")).is_ok());
+
+    let local = EgressPolicy {
+        allowed_cidrs: vec!["127.0.0.1/32".parse().unwrap(), "10.1.0.0/16".parse().unwrap()],
+        blocked_hosts: vec!["admin.example.com".into()],
+        ..EgressPolicy::default()
+    };
+    assert!(local.check_url(&url("http://localhost:3000/")).is_ok());
+    assert!(local.check_url(&url("http://10.1.2.3/")).is_ok());
+    assert!(local.check_url(&url("http://10.2.0.1/")).is_err());
+    assert!(local.check_url(&url("http://[::1]/")).is_err());
+    assert!(local.check_url(&url("http://ADMIN.example.com./")).is_err());
+
+    let open = EgressPolicy { block_private: false, ..EgressPolicy::default() };
+    assert!(open.check_url(&url("http://192.168.1.10/")).is_ok());
+    assert!(open.check_url(&url("http://[::1]/")).is_ok());
+    assert!(open.check_url(&url("http://169.254.169.254/")).is_err());
+
+    let allowlist = EgressPolicy {
+        allowed_hosts: vec!["*.example.com".into(), "hooks.test".into()],
+        ..EgressPolicy::default()
+    };
+    assert!(allowlist.check_url(&url("https://api.example.com/")).is_ok());
+    assert!(allowlist.check_url(&url("https://hooks.test/")).is_ok());
+    assert!(allowlist.check_url(&url("https://example.com/")).is_err());
+    assert!(allowlist.check_url(&url("https://badexample.com/")).is_err());
+
+    // The client that skips certificate checks only reaches local addresses
+    let local_only = EgressPolicy { block_private: false, ..EgressPolicy::default() }.local_only();
+    assert!(local_only.check_url(&url("https://192.168.1.10/")).is_ok());
+    assert!(local_only.check_url(&url("https://[::ffff:127.0.0.1]/")).is_ok());
+    assert!(local_only.check_url(&url("https://93.184.216.34/")).is_err());
+    assert!(local_only.check_url(&url("https://169.254.169.254/")).is_err());
+}
+
+/// A target that answers, or redirects to the cloud metadata address
+fn target() -> Router {
+    Router::new()
+        .route("/ok", post(|| async { "ok" }))
+        .route("/metadata", post(|| async { Redirect::temporary("http://169.254.169.254/latest/meta-data") }))
+}
+
+#[tokio::test]
+async fn resolved_and_redirected_addresses_are_checked() {
+    let target = serve(target()).await;
+    let req = StoredRequest { method: "POST".into(), headers: "{}".into(), body: "{}".into(), ..Default::default() };
+    let state = loopback_state().await;
+
+    assert_eq!(replay::send(&state, &format!("{target}/ok"), &req).await, Ok(Some(200)));
+
+    // A redirect to a blocked address is not followed
+    let err = replay::send(&state, &format!("{target}/metadata"), &req).await.unwrap_err();
+    assert!(err.contains("Blocked by egress policy: 169.254.169.254"), "{err}");
+
+    // Names are checked once resolved, on the addresses actually connected to
+    let loopback_blocked = EgressPolicy::default();
+    let port = target.rsplit(':').next().unwrap();
+    let err = loopback_blocked
+        .client_builder(10)
+        .build()
+        .unwrap()
+        .post(format!("http://localhost:{port}/ok"))
+        .send()
+        .await
+        .unwrap_err();
+    let message = describe_error(&err);
+    assert!(message.contains("Blocked by egress policy: localhost resolves to"), "{message}");
+}
+
+#[tokio::test]
+async fn replay_and_forwarding_refuse_blocked_targets() {
+    let state = test_state().await;
+    let token = register_webhook(&state, "egress").await;
+    state
+        .store_request(&StoredRequest {
+            id: "req".into(),
+            webhook_id: "egress".into(),
+            method: "POST".into(),
+            headers: "{}".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let app = Router::new()
+        .route("/replay/:req_id", post(replay_request))
+        .route("/webhook/:id/config", post(set_custom_response))
+        .with_state(state);
+
+    let post_json = |uri: &'static str, payload: serde_json::Value| {
+        let app = app.clone();
+        let authorization = format!("Bearer {token}");
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri(uri)
+                        .header("content-type", "application/json")
+                        .header("authorization", authorization)
+                        .body(Body::from(payload.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, String::from_utf8(bytes.to_vec()).unwrap())
+        }
+    };
+
+    let (status, body) = post_json("/replay/req", json!({"target": "http://169.254.169.254/latest/meta-data"})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body.contains("Blocked by egress policy"), "{body}");
+
+    let (status, body) = post_json("/webhook/egress/config", json!({"forward_url": "file:///etc/passwd"})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body.contains("scheme file is not allowed"), "{body}");
+
+    let (status, body) = post_json("/webhook/egress/config", json!({"forward_url": "http://localhost:9000/hook"})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body.contains("127.0.0.1 is in the reserved range"), "{body}");
+
+    let (status, _) = post_json("/webhook/egress/config", json!({"forward_url": "https://hooks.example.com/in"})).await;
+    assert_eq!(status, StatusCode::OK);
+}
@@ -0,0 +1,213 @@
+mod common;
+
+use axum::{routing::post, Router};
+use chrono::Utc;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use webhook_tester::models::StoredRequest;
+use webhook_tester::settings::Settings;
+use webhook_tester::testing::{TestServer, TestWebhook};
+use common::{call_server, serve};
+
+async fn send(webhook: &TestWebhook, body: Value) {
+    let response = reqwest::Client::new().post(&webhook.url).json(&body).send().await.unwrap();
+    assert!(response.status().is_success());
+}
+
+#[tokio::test]
+async fn wait_blocks_until_a_matching_request_arrives() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let started = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+    send(&webhook, json!({"type": "order.updated"})).await;
+
+    let late = webhook.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        send(&late, json!({"type": "order.created", "id": 7})).await;
+    });
+
+    let wait = format!("/api/webhooks/{}/wait?match=method:POST%20json:type=order.created&timeout=5", webhook.id);
+    let (status, req) = call_server(&server, &webhook, "GET", &wait, None).await;
+    assert_eq!(status, StatusCode::OK, "{req}");
+    assert_eq!(serde_json::from_str::<Value>(req["body"].as_str().unwrap()).unwrap()["id"], 7);
+
+    // Requests from before the call, such as an earlier run's, don't count
+    let again = wait.replace("timeout=5", "timeout=0.2");
+    let (status, _) = call_server(&server, &webhook, "GET", &again, None).await;
+    assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+
+    // Unless the wait looks back to them
+    let (status, req) = call_server(&server, &webhook, "GET", &format!("{again}&since={started}"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_str::<Value>(req["body"].as_str().unwrap()).unwrap()["id"], 7);
+
+    let never = format!("/api/webhooks/{}/wait?match=json:type=order.deleted&timeout=0.2", webhook.id);
+    let (status, body) = call_server(&server, &webhook, "GET", &never, None).await;
+    assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+    assert!(body["error"].as_str().unwrap().starts_with("No matching request"));
+
+    for bad in ["match=%22unterminated", "timeout=9999", "since=yesterday"] {
+        let (status, _) = call_server(&server, &webhook, "GET", &format!("/api/webhooks/{}/wait?{bad}", webhook.id), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{bad}");
+    }
+}
+
+#[tokio::test]
+async fn expectations_report_what_differed() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    // Captured before the expectations, so never counted
+    send(&webhook, json!({"type": "order.paid", "amount": 100})).await;
+
+    let (status, set) = call_server(&server, &webhook, "POST", &format!("/api/webhooks/{}/expectations", webhook.id), Some(json!({
+        "ordered": true,
+        "expect": [
+            {"match": "json:type=order.created", "count": 1},
+            {"match": "json:type=order.paid", "body": {"amount": 100, "currency": "EUR"}},
+        ]
+    })))
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{set}");
+    let verify = format!("/api/expectations/{}/verify", set["id"].as_str().unwrap());
+
+    send(&webhook, json!({"type": "order.created"})).await;
+    send(&webhook, json!({"type": "order.paid", "amount": 90, "currency": "EUR"})).await;
+
+    let (status, verification) = call_server(&server, &webhook, "POST", &verify, None).await;
+    assert_eq!(status, StatusCode::EXPECTATION_FAILED);
+    assert_eq!(verification["passed"], false);
+    assert_eq!(verification["results"][0]["passed"], true);
+    let report = verification["report"].as_str().unwrap();
+    assert!(report.contains("1 of 2 met"), "{report}");
+    assert!(report.contains("- expected at least 1 request\n       + got 0 requests"), "{report}");
+    assert!(report.contains("-   amount: 100\n       +   amount: 90"), "{report}");
+    assert!(!report.contains("currency:"), "{report}");
+
+    // Verifying with a timeout waits for the rest to arrive
+    let paid = webhook.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        send(&paid, json!({"type": "order.paid", "amount": 100, "currency": "EUR", "extra": true})).await;
+    });
+    let (status, verification) = call_server(&server, &webhook, "POST", &format!("{verify}?timeout=5"), None).await;
+    assert_eq!(status, StatusCode::OK, "{verification}");
+    assert_eq!(verification["results"][1]["matched"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn expectations_check_counts_and_order() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+
+    let (_, set) = call_server(&server, &webhook, "POST", &format!("/api/webhooks/{}/expectations", webhook.id), Some(json!({
+        "ordered": true,
+        "expect": [
+            {"match": "json:step=first"},
+            {"match": "json:step=second", "max": 1},
+        ]
+    })))
+    .await;
+    let set_path = format!("/api/expectations/{}", set["id"].as_str().unwrap());
+
+    send(&webhook, json!({"step": "second"})).await;
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    send(&webhook, json!({"step": "first"})).await;
+    send(&webhook, json!({"step": "second"})).await;
+
+    let (status, verification) = call_server(&server, &webhook, "POST", &format!("{set_path}/verify"), None).await;
+    assert_eq!(status, StatusCode::EXPECTATION_FAILED);
+    let problems = verification["results"][1]["problems"].to_string();
+    assert!(problems.contains("expected at most 1 request"), "{problems}");
+    assert!(problems.contains("arrived earlier"), "{problems}");
+
+    let (status, _) = call_server(&server, &webhook, "DELETE", &set_path, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = call_server(&server, &webhook, "GET", &set_path, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    for invalid in [
+        json!({"expect": []}),
+        json!({"expect": [{"match": "status:9xx"}]}),
+        json!({"expect": [{"count": 1, "min": 1}]}),
+        json!({"expect": [{"min": 3, "max": 1}]}),
+    ] {
+        let (status, _) = call_server(&server, &webhook, "POST", &format!("/api/webhooks/{}/expectations", webhook.id), Some(invalid.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{invalid}");
+    }
+}
+
+#[tokio::test]
+async fn expectations_count_every_matching_request() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let (_, set) = call_server(&server, &webhook, "POST", &format!("/api/webhooks/{}/expectations", webhook.id), Some(json!({
+        "expect": [
+            {"match": "method:PUT", "count": 1001},
+            {"match": "method:PUT", "body": {"batch": true}, "count": 1001},
+        ]
+    })))
+    .await;
+
+    for n in 0..1001 {
+        let req = StoredRequest {
+            id: format!("bulk-{n}"),
+            webhook_id: webhook.id.clone(),
+            method: "PUT".into(),
+            headers: "{}".into(),
+            body: json!({"batch": true, "n": n}).to_string(),
+            query: "{}".into(),
+            created_at: Utc::now().to_rfc3339(),
+            ..Default::default()
+        };
+        server.state().store_request(&req).await.unwrap();
+    }
+
+    let verify = format!("/api/expectations/{}/verify", set["id"].as_str().unwrap());
+    let (status, verification) = call_server(&server, &webhook, "POST", &verify, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", verification["report"]);
+    for result in verification["results"].as_array().unwrap() {
+        assert_eq!(result["count"], 1001);
+        assert_eq!(result["matched"].as_array().unwrap().len(), 1000);
+        assert_eq!(result["matched"][0], "bulk-0");
+    }
+}
+
+#[tokio::test]
+async fn imported_and_replayed_requests_wake_waiters() {
+    let mut settings = Settings::default();
+    settings.egress.allowed_cidrs = vec!["127.0.0.0/8".parse().unwrap()];
+    let server = TestServer::with_settings(settings).await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let target = serve(Router::new().route("/hook", post(|| async { "ok" }))).await;
+
+    let importer = webhook.clone();
+    let base = server.url().to_string();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        reqwest::Client::new()
+            .post(format!("{base}/api/webhooks/{}/import?format=curl", importer.id))
+            .bearer_auth(&importer.token)
+            .body(r#"curl -d '{"type":"imported"}' http://example.com/hook"#)
+            .send()
+            .await
+            .unwrap();
+    });
+    let wait = format!("/api/webhooks/{}/wait?match=json:type=imported&timeout=5", webhook.id);
+    let (status, imported) = call_server(&server, &webhook, "GET", &wait, None).await;
+    assert_eq!(status, StatusCode::OK, "{imported}");
+
+    let replay = json!({"target": format!("{target}/hook"), "edit": {"set_headers": {"x-edited": "yes"}}});
+    let replayer = webhook.clone();
+    let path = format!("{}/replay/{}", server.url(), imported["id"].as_str().unwrap());
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        reqwest::Client::new().post(path).bearer_auth(&replayer.token).json(&replay).send().await.unwrap();
+    });
+    let wait = format!("/api/webhooks/{}/wait?match=header:x-edited=yes&timeout=5", webhook.id);
+    let (status, derived) = call_server(&server, &webhook, "GET", &wait, None).await;
+    assert_eq!(status, StatusCode::OK, "{derived}");
+    assert_eq!(derived["derived_from"], imported["id"]);
+}
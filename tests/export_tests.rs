@@ -0,0 +1,137 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use chrono::{Duration, Utc};
+use tower::ServiceExt;
+
+use webhook_tester::models::StoredRequest;
+use common::{app, register_webhook, test_state};
+
+fn stored(id: &str, body: &str, age_minutes: i64) -> StoredRequest {
+    StoredRequest {
+        id: id.into(),
+        webhook_id: "export".into(),
+        method: "POST".into(),
+        // The sender's own X-Forwarded-Proto wasn't believed when the URL was recorded
+        headers: r#"{"host":"hooks.example.com","content-type":"application/json","content-length":"99","x-event":"order.paid","x-forwarded-proto":"https"}"#.into(),
+        body: body.into(),
+        query: r#"{"source":"stripe test"}"#.into(),
+        created_at: (Utc::now() - Duration::minutes(age_minutes)).to_rfc3339(),
+        response_status: Some(202),
+        response_headers: Some(r#"{"content-type":"text/plain"}"#.into()),
+        response_body: Some("accepted".into()),
+        latency_ms: Some(1.5),
+        request_uri: Some("http://hooks.example.com/webhook/export?source=stripe+test".into()),
+        ..Default::default()
+    }
+}
+
+async fn get_text(app: &Router, token: &str, uri: &str) -> (StatusCode, String, String) {
+    let request = Request::builder().uri(uri).header("authorization", format!("Bearer {token}")).body(Body::empty()).unwrap();
+    let response = app
+        .clone()
+        .oneshot(request)
+        .await
+        .unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|v| v.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+/// The service over two captured requests, with the webhook's token
+async fn two_requests() -> (Router, String) {
+    let state = test_state().await;
+    let token = register_webhook(&state, "export").await;
+    state.store_request(&stored("older", r#"{"order":1}"#, 10)).await.unwrap();
+    state.store_request(&stored("newer", "it's done", 5)).await.unwrap();
+
+    (app(state), token)
+}
+
+#[tokio::test]
+async fn har_export_holds_requests_and_responses_in_capture_order() {
+    let (app, token) = two_requests().await;
+
+    let (status, content_type, body) = get_text(&app, &token, "/api/webhooks/export/export").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/json");
+
+    let har: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(har["log"]["version"], "1.2");
+    let entries = har["log"]["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["_id"], "older");
+
+    let entry = &entries[0];
+    assert_eq!(entry["request"]["method"], "POST");
+    assert_eq!(entry["request"]["url"], "http://hooks.example.com/webhook/export?source=stripe+test");
+    assert_eq!(entry["request"]["postData"]["mimeType"], "application/json");
+    assert_eq!(entry["request"]["postData"]["text"], r#"{"order":1}"#);
+    assert_eq!(entry["request"]["queryString"][0]["value"], "stripe test");
+    assert_eq!(entry["response"]["status"], 202);
+    assert_eq!(entry["response"]["statusText"], "Accepted");
+    assert_eq!(entry["response"]["content"]["text"], "accepted");
+    assert_eq!(entry["time"], 1.5);
+}
+
+#[tokio::test]
+async fn ndjson_export_can_be_limited_to_a_selection() {
+    let (app, token) = two_requests().await;
+
+    let (status, content_type, body) = get_text(&app, &token, "/api/webhooks/export/export?format=ndjson&ids=newer,missing").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/x-ndjson");
+
+    let lines: Vec<StoredRequest> = body.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].id, "newer");
+    assert_eq!(lines[0].body, "it's done");
+
+    let (status, _, body) = get_text(&app, &token, "/api/requests/older/export?format=ndjson").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.lines().count(), 1);
+    assert!(body.contains(r#""id":"older""#));
+
+    let (status, _, _) = get_text(&app, &token, "/api/webhooks/unknown/export").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn curl_export_is_a_runnable_script() {
+    let (app, token) = two_requests().await;
+
+    let (status, _, script) = get_text(&app, &token, "/api/webhooks/export/export?format=curl").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(script.starts_with("#!/bin/sh\n"));
+    assert!(script.contains(r#"curl -sS -X POST "${BASE_URL:-http://hooks.example.com}"'/webhook/export?source=stripe+test'"#));
+    assert!(script.contains("-H 'x-event: order.paid'"));
+    assert!(script.contains(r"--data-binary 'it'\''s done'"));
+    // curl computes these itself
+    assert!(!script.contains("content-length"));
+    assert!(!script.contains("'host: "));
+
+    // Run the script against a stub `curl` that echoes its body argument
+    let dir = std::env::temp_dir().join(format!("export-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let stub = dir.join("curl");
+    std::fs::write(&stub, "#!/bin/sh\nwhile [ $# -gt 0 ]; do [ \"$1\" = --data-binary ] && printf '%s\\n' \"$2\"; shift; done\n").unwrap();
+    std::fs::set_permissions(&stub, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(&script)
+        .env("PATH", format!("{}:{}", dir.display(), std::env::var("PATH").unwrap()))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "{\"order\":1}\nit's done\n");
+}
@@ -0,0 +1,107 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::time::Duration;
+
+use webhook_tester::models::StoredRequest;
+use webhook_tester::testing::{TestServer, TestWebhook};
+
+const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0xff];
+
+/// An email-inbound style body: a text field, a binary attachment and a text attachment
+fn inbound_email() -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(b"--BOUNDARY\r\nContent-Disposition: form-data; name=\"subject\"\r\n\r\nHello\r\n");
+    body.extend_from_slice(
+        b"--BOUNDARY\r\nContent-Disposition: form-data; name=\"attachment1\"; filename=\"logo \\\"v2\\\".png\"\r\nContent-Type: image/png\r\n\r\n",
+    );
+    body.extend_from_slice(PNG);
+    body.extend_from_slice(
+        b"\r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"attachment2\"; filename=\"notes.txt\"\r\nContent-Type: text/plain\r\n\r\nsee attached\r\n",
+    );
+    body.extend_from_slice(b"--BOUNDARY--\r\n");
+    body
+}
+
+async fn capture(server: &TestServer, webhook: &TestWebhook) -> StoredRequest {
+    let response = reqwest::Client::new()
+        .post(&webhook.url)
+        .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+        .body(inbound_email())
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    server.next_request(Duration::from_secs(5), |_| true).await.unwrap()
+}
+
+async fn get(server: &TestServer, webhook: &TestWebhook, path: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(format!("{}{path}", server.url()))
+        .bearer_auth(&webhook.token)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn file_parts_are_stored_and_downloadable() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let req = capture(&server, &webhook).await;
+    assert_eq!(req.raw_bytes(), inbound_email());
+
+    let files: Value = get(&server, &webhook, &format!("/api/requests/{}/files", req.id)).await.json().await.unwrap();
+    let files = files.as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0]["filename"], "logo \"v2\".png");
+    assert_eq!(files[0]["field_name"], "attachment1");
+    assert_eq!(files[0]["content_type"], "image/png");
+    assert_eq!(files[0]["size"], PNG.len());
+    assert_eq!(files[0]["part_index"], 1);
+    assert_eq!(files[1]["filename"], "notes.txt");
+    assert!(files[0].get("data").is_none());
+
+    let download = get(&server, &webhook, &format!("/api/requests/{}/files/{}", req.id, files[0]["id"].as_str().unwrap())).await;
+    assert_eq!(download.status(), StatusCode::OK);
+    assert_eq!(download.headers()["content-type"], "image/png");
+    assert_eq!(download.headers()["content-disposition"], "attachment; filename=\"logo _v2_.png\"");
+    assert_eq!(download.headers()["x-content-type-options"], "nosniff");
+    assert_eq!(download.bytes().await.unwrap().as_ref(), PNG);
+
+    // The parsed body points file parts at their downloads
+    let view: Value = get(&server, &webhook, &format!("/api/requests/{}/body", req.id)).await.json().await.unwrap();
+    let parts = view["body"]["parts"].as_array().unwrap();
+    assert_eq!(parts.len(), 3);
+    assert!(parts[0].get("file_id").is_none());
+    assert_eq!(parts[0]["text"], "Hello");
+    assert_eq!(parts[1]["file_id"], files[0]["id"]);
+    assert_eq!(parts[2]["file_id"], files[1]["id"]);
+}
+
+#[tokio::test]
+async fn files_are_scoped_to_their_request_and_deleted_with_it() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let req = capture(&server, &webhook).await;
+    let other = capture(&server, &webhook).await;
+
+    let files = server.state().db.list_request_files(&req.id).await.unwrap();
+    let file_id = &files[0].id;
+
+    let wrong_request = get(&server, &webhook, &format!("/api/requests/{}/files/{file_id}", other.id)).await;
+    assert_eq!(wrong_request.status(), StatusCode::NOT_FOUND);
+
+    let intruder = server.create_webhook().await.unwrap();
+    let forbidden = get(&server, &intruder, &format!("/api/requests/{}/files/{file_id}", req.id)).await;
+    assert_eq!(forbidden.status(), StatusCode::FORBIDDEN);
+
+    let deleted = reqwest::Client::new()
+        .delete(format!("{}/api/requests/{}", server.url(), req.id))
+        .bearer_auth(&webhook.token)
+        .send()
+        .await
+        .unwrap();
+    assert!(deleted.status().is_success());
+    assert!(server.state().db.get_request_file(&req.id, file_id).await.unwrap().is_none());
+    assert_eq!(server.state().db.list_request_files(&other.id).await.unwrap().len(), 2);
+}
@@ -0,0 +1,231 @@
+use axum::http::{HeaderMap, HeaderValue};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use webhook_tester::forwarded::{self, TrustedProxies};
+use webhook_tester::settings::Settings;
+use webhook_tester::testing::TestServer;
+
+fn trusted(cidrs: &[&str]) -> TrustedProxies {
+    TrustedProxies { cidrs: cidrs.iter().map(|cidr| cidr.parse().unwrap()).collect() }
+}
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.append(*name, HeaderValue::from_str(value).unwrap());
+    }
+    map
+}
+
+fn peer(addr: &str) -> Option<SocketAddr> {
+    Some(addr.parse().unwrap())
+}
+
+fn ip(addr: &str) -> Option<IpAddr> {
+    Some(addr.parse().unwrap())
+}
+
+#[test]
+fn forwarding_headers_count_only_from_trusted_peers() {
+    let spoofed = headers(&[("x-forwarded-for", "1.2.3.4"), ("x-forwarded-proto", "https")]);
+
+    let origin = TrustedProxies::default().resolve(peer("198.51.100.7:5000"), &spoofed);
+    assert_eq!(origin.client_ip, ip("198.51.100.7"));
+    assert_eq!(origin.scheme, "http");
+
+    let origin = trusted(&["10.0.0.0/8"]).resolve(peer("198.51.100.7:5000"), &spoofed);
+    assert_eq!(origin.client_ip, ip("198.51.100.7"));
+
+    assert_eq!(trusted(&["10.0.0.0/8"]).resolve(None, &spoofed).client_ip, None);
+}
+
+#[test]
+fn x_forwarded_for_is_walked_from_the_right() {
+    let proxies = trusted(&["10.0.0.0/8"]);
+    // The leftmost entry was written by the client and can't be trusted
+    let chain = headers(&[("x-forwarded-for", "6.6.6.6, 203.0.113.9"), ("x-forwarded-for", "10.0.0.3"), ("x-forwarded-proto", "https")]);
+
+    let origin = proxies.resolve(peer("10.0.0.2:443"), &chain);
+    assert_eq!(origin.client_ip, ip("203.0.113.9"));
+    assert_eq!(origin.scheme, "https");
+
+    // Only proxies all the way: the furthest one is the best guess
+    let internal = headers(&[("x-forwarded-for", "10.1.1.1, 10.0.0.3")]);
+    assert_eq!(proxies.resolve(peer("10.0.0.2:443"), &internal).client_ip, ip("10.1.1.1"));
+
+    let unknown = headers(&[("x-forwarded-for", "203.0.113.9, unknown, 10.0.0.3")]);
+    assert_eq!(proxies.resolve(peer("10.0.0.2:443"), &unknown).client_ip, ip("10.0.0.3"));
+}
+
+#[test]
+fn forwarded_header_takes_precedence() {
+    let proxies = trusted(&["10.0.0.0/8", "127.0.0.1/32"]);
+    let both = headers(&[
+        ("forwarded", r#"for="[2001:db8:cafe::17]:4711";proto=https, for=10.0.0.9"#),
+        ("x-forwarded-for", "192.0.2.1"),
+    ]);
+
+    let origin = proxies.resolve(peer("127.0.0.1:8080"), &both);
+    assert_eq!(origin.client_ip, ip("2001:db8:cafe::17"));
+    assert_eq!(origin.scheme, "https");
+
+    let port = headers(&[("forwarded", "for=192.0.2.60:8080;proto=ftp")]);
+    let origin = proxies.resolve(peer("127.0.0.1:8080"), &port);
+    assert_eq!(origin.client_ip, ip("192.0.2.60"));
+    assert_eq!(origin.scheme, "http");
+}
+
+#[test]
+fn scheme_and_host_come_from_the_clients_hop() {
+    let proxies = trusted(&["10.0.0.0/8"]);
+    // The client made up the first element; the trusted proxy wrote the second
+    let spoofed = headers(&[(
+        "forwarded",
+        "for=6.6.6.6;proto=https;host=evil.example, for=203.0.113.9;proto=http;host=hooks.example, for=10.0.0.3;proto=https",
+    )]);
+    let origin = proxies.resolve(peer("10.0.0.2:443"), &spoofed);
+    assert_eq!(origin.client_ip, ip("203.0.113.9"));
+    assert_eq!(origin.scheme, "http");
+    assert_eq!(origin.host.as_deref(), Some("hooks.example"));
+
+    let appended = headers(&[
+        ("x-forwarded-for", "203.0.113.9, 10.0.0.3"),
+        ("x-forwarded-proto", "https, http"),
+        ("x-forwarded-host", "hooks.example, internal.example"),
+    ]);
+    let origin = proxies.resolve(peer("10.0.0.2:443"), &appended);
+    assert_eq!(origin.scheme, "https");
+    assert_eq!(origin.host.as_deref(), Some("hooks.example"));
+
+    let bad_host = headers(&[("forwarded", "for=203.0.113.9;host=\"user@evil.example\"")]);
+    assert_eq!(proxies.resolve(peer("10.0.0.2:443"), &bad_host).host, None);
+}
+
+#[test]
+fn hosts_must_be_plain_authorities() {
+    assert!(forwarded::valid_host("hooks.example"));
+    assert!(forwarded::valid_host("127.0.0.1:3000"));
+    assert!(forwarded::valid_host("[::1]:3000"));
+    assert!(!forwarded::valid_host("user@evil.example"));
+    assert!(!forwarded::valid_host("evil.example/path"));
+    assert!(!forwarded::valid_host(""));
+}
+
+#[tokio::test]
+async fn invalid_host_headers_are_not_recorded_in_the_url() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+
+    reqwest::Client::new()
+        .post(&webhook.url)
+        .header("host", "evil.example/phish?")
+        .send()
+        .await
+        .unwrap();
+
+    let req = server.next_request(Duration::from_secs(5), |_| true).await.unwrap();
+    assert_eq!(req.request_uri, Some(format!("http://localhost/webhook/{}", webhook.id)));
+}
+
+#[tokio::test]
+async fn captured_requests_carry_connection_metadata() {
+    let settings = Settings { trusted_proxies: trusted(&["127.0.0.0/8"]), ..Default::default() };
+    let server = TestServer::with_settings(settings).await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+
+    reqwest::Client::new()
+        .post(format!("{}?attempt=2", webhook.url))
+        .header("x-forwarded-for", "203.0.113.9")
+        .header("x-forwarded-proto", "https")
+        .body("12345")
+        .send()
+        .await
+        .unwrap();
+
+    let req = server.next_request(Duration::from_secs(5), |_| true).await.unwrap();
+    assert_eq!(req.client_ip.as_deref(), Some("203.0.113.9"));
+    assert!(req.remote_addr.as_deref().unwrap().starts_with("127.0.0.1:"));
+    assert_eq!(req.http_version.as_deref(), Some("HTTP/1.1"));
+    let host = server.url().trim_start_matches("http://");
+    assert_eq!(req.request_uri, Some(format!("https://{host}/webhook/{}?attempt=2", webhook.id)));
+    assert_eq!(req.body_size, Some(5));
+
+    let received_at = req.received_at.unwrap();
+    assert!(chrono::DateTime::parse_from_rfc3339(&received_at).is_ok());
+    assert_eq!(received_at.split('.').nth(1).unwrap().trim_end_matches('Z').len(), 6, "{received_at}");
+
+    let latency = req.latency_ms.unwrap();
+    let stored = server.state().db.get_request(&req.id).await.unwrap();
+    assert_eq!(stored.latency_ms, Some(latency));
+}
+
+#[tokio::test]
+async fn untrusted_senders_cannot_spoof_their_address() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+
+    reqwest::Client::new()
+        .post(&webhook.url)
+        .header("x-forwarded-for", "203.0.113.9")
+        .header("forwarded", "for=203.0.113.9;proto=https")
+        .send()
+        .await
+        .unwrap();
+
+    let req = server.next_request(Duration::from_secs(5), |_| true).await.unwrap();
+    assert_eq!(req.client_ip.as_deref(), Some("127.0.0.1"));
+    assert!(req.request_uri.unwrap().starts_with("http://127.0.0.1:"));
+    assert_eq!(req.body_size, Some(0));
+}
+
+/// A Twilio signature over `url` and the form parameters of `form`, sorted by name
+fn twilio_signature(secret: &str, url: &str, form: &str) -> String {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use hmac::{Hmac, Mac};
+
+    let mut params: Vec<(String, String)> = serde_urlencoded::from_str(form).unwrap();
+    params.sort();
+    let mut mac = Hmac::<sha1::Sha1>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(url.as_bytes());
+    for (key, value) in &params {
+        mac.update(key.as_bytes());
+        mac.update(value.as_bytes());
+    }
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+#[tokio::test]
+async fn signed_urls_are_rebuilt_from_trusted_proxies_only() {
+    let form = "To=%2B15551234567&Body=hi";
+    for trusted_proxies in [trusted(&["127.0.0.0/8"]), TrustedProxies::default()] {
+        let believed = !trusted_proxies.cidrs.is_empty();
+        let server = TestServer::with_settings(Settings { trusted_proxies, ..Default::default() }).await.unwrap();
+        let webhook = server.create_webhook().await.unwrap();
+        let config = serde_json::json!({"signing_provider": "twilio", "signing_secret": "twiliotoken"});
+        reqwest::Client::new()
+            .post(format!("{}/webhook/{}/config", server.url(), webhook.id))
+            .bearer_auth(&webhook.token)
+            .json(&config)
+            .send()
+            .await
+            .unwrap();
+
+        let public_url = format!("https://hooks.example/webhook/{}", webhook.id);
+        reqwest::Client::new()
+            .post(&webhook.url)
+            .header("x-forwarded-proto", "https")
+            .header("x-forwarded-host", "hooks.example")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .header("x-twilio-signature", twilio_signature("twiliotoken", &public_url, form))
+            .body(form)
+            .send()
+            .await
+            .unwrap();
+
+        let req = server.next_request(Duration::from_secs(5), |_| true).await.unwrap();
+        let expected = if believed { "valid" } else { "invalid" };
+        assert_eq!(req.signature_status.as_deref(), Some(expected), "trusted: {believed}");
+        assert_eq!(req.request_uri.as_deref() == Some(public_url.as_str()), believed);
+    }
+}
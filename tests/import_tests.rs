@@ -0,0 +1,168 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use webhook_tester::import::{self, ImportFormat};
+use webhook_tester::models::StoredRequest;
+use webhook_tester::AppState;
+use common::{app, register_webhook, test_state};
+
+const CAPTURED_AT: &str = "2024-05-01T10:00:00+00:00";
+
+/// The service over a captured `source` request, with the tokens of the `source` and `target` webhooks
+async fn source_and_target() -> (Router, Arc<AppState>, String, String) {
+    let state = test_state().await;
+    let source = register_webhook(&state, "source").await;
+    let target = register_webhook(&state, "target").await;
+    state
+        .store_request(&StoredRequest {
+            id: "original".into(),
+            webhook_id: "source".into(),
+            method: "PUT".into(),
+            headers: r#"{"host":"hooks.example.com","content-type":"application/json","x-event":"it's \"quoted\""}"#.into(),
+            body: "{\"multi\":\n\"line 'body'\"}".into(),
+            query: r#"{"a":"1 2"}"#.into(),
+            created_at: CAPTURED_AT.into(),
+            response_status: Some(201),
+            response_body: Some("created".into()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    (app(state.clone()), state, source, target)
+}
+
+async fn call(app: &Router, method: &str, uri: &str, token: &str, body: String) -> (StatusCode, String) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("authorization", format!("Bearer {token}"))
+        .body(Body::from(body))
+        .unwrap();
+    let response = app
+        .clone()
+        .oneshot(request)
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn exports_round_trip_through_import() {
+    for format in ["har", "ndjson", "curl"] {
+        let (app, state, source, target) = source_and_target().await;
+
+        let (_, exported) = call(&app, "GET", &format!("/api/webhooks/source/export?format={format}"), &source, String::new()).await;
+        // Let the format be detected from the contents
+        let (status, body) = call(&app, "POST", "/api/webhooks/target/import", &target, exported).await;
+        assert_eq!(status, StatusCode::OK, "{format}: {body}");
+        assert_eq!(body, r#"{"imported":1}"#);
+
+        let imported = state.get_requests("target").await;
+        assert_eq!(imported.len(), 1, "{format}");
+        let req = &imported[0];
+        assert_ne!(req.id, "original");
+        assert_eq!(req.webhook_id, "target");
+        assert_eq!(req.method, "PUT", "{format}");
+        assert_eq!(req.body, "{\"multi\":\n\"line 'body'\"}", "{format}");
+        assert_eq!(req.query, r#"{"a":"1 2"}"#, "{format}");
+        assert_eq!(req.header_map()["x-event"], "it's \"quoted\"", "{format}");
+        assert_eq!(req.created_at, CAPTURED_AT, "{format}");
+
+        if format != "curl" {
+            assert_eq!(req.response_status, Some(201), "{format}");
+            assert_eq!(req.response_body.as_deref(), Some("created"), "{format}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn compressed_bodies_keep_their_bytes_through_export_and_import() {
+    for format in ["har", "ndjson"] {
+        let (app, state, source, target) = source_and_target().await;
+        state
+            .store_request(&StoredRequest {
+                id: "compressed".into(),
+                webhook_id: "source".into(),
+                method: "POST".into(),
+                headers: r#"{"content-encoding":"gzip","content-type":"application/json"}"#.into(),
+                body: r#"{"ok":true}"#.into(),
+                query: "{}".into(),
+                created_at: "2024-05-01T11:00:00+00:00".into(),
+                body_encoding: Some("gzip".into()),
+                raw_body: Some("H4sIAAAAAAAA/w==".into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let (_, exported) = call(&app, "GET", &format!("/api/webhooks/source/export?format={format}"), &source, String::new()).await;
+        let (status, body) = call(&app, "POST", "/api/webhooks/target/import", &target, exported).await;
+        assert_eq!(status, StatusCode::OK, "{format}: {body}");
+
+        let imported = state.get_requests("target").await;
+        let req = imported.iter().find(|req| req.body_encoding.is_some()).unwrap();
+        assert_eq!(req.raw_body.as_deref(), Some("H4sIAAAAAAAA/w=="), "{format}");
+        assert_eq!(req.body_encoding.as_deref(), Some("gzip"), "{format}");
+        assert_eq!(req.header_map()["content-encoding"], "gzip", "{format}");
+        assert_eq!(req.body, r#"{"ok":true}"#, "{format}");
+    }
+
+    // Without the bytes as received, the decoded body is no longer labelled compressed
+    let line = r#"{"method":"POST","headers":"{\"content-encoding\":\"gzip\"}","body":"{}","body_encoding":"gzip"}"#;
+    let requests = import::parse(line, Some(ImportFormat::Ndjson), "wh").unwrap();
+    assert_eq!(requests[0].body_encoding, None);
+    assert!(!requests[0].header_map().contains_key("content-encoding"));
+}
+
+#[test]
+fn payload_lines_and_hand_written_curl_commands_are_imported() {
+    // Objects that aren't captured requests, like the lines of a requests.jsonl fixture file
+    let fixtures = "{\"request_id\": \"a\", \"title\": \"First\"}\n\n{\"request_id\": \"b\"}\n";
+    let requests = import::parse(fixtures, None, "wh").unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].body, r#"{"request_id": "a", "title": "First"}"#);
+    assert_eq!(requests[0].header_map()["content-type"], "application/json");
+    assert!(requests[0].created_at < requests[1].created_at);
+
+    let script = r#"
+curl https://example.com/hook?x=1 -H "X-Token: \"abc\"" -d 'a=1' --data b=2
+curl -X delete --url http://example.com/hook
+"#;
+    assert_eq!(ImportFormat::detect(script), ImportFormat::Curl);
+    let requests = import::parse(script, None, "wh").unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].body, "a=1&b=2");
+    assert_eq!(requests[0].header_map()["x-token"], "\"abc\"");
+    assert_eq!(requests[0].header_map()["content-type"], "application/x-www-form-urlencoded");
+    assert_eq!(requests[0].query, r#"{"x":"1"}"#);
+    assert_eq!(requests[1].method, "DELETE");
+}
+
+#[tokio::test]
+async fn invalid_files_are_rejected_without_importing_anything() {
+    let (app, state, _, target) = source_and_target().await;
+
+    let (status, body) = call(&app, "POST", "/api/webhooks/target/import?format=ndjson", &target, "{}\nnot json\n".into()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("Line 2"), "{body}");
+
+    let (status, _) = call(&app, "POST", "/api/webhooks/target/import?format=curl", &target, "curl -d @payload.json http://x\n".into()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(&app, "POST", "/api/webhooks/missing/import", &target, "{}".into()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    assert!(state.get_requests("target").await.is_empty());
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use common::{app, test_state};
+
+const ADMIN: &str = "admin-secret";
+
+async fn admin_app() -> (Router, std::sync::Arc<webhook_tester::AppState>) {
+    let mut state = (*test_state().await).clone();
+    state.settings.admin_token = Some(ADMIN.into());
+    let state = std::sync::Arc::new(state);
+    (app(state.clone()), state)
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+async fn new_webhook(app: &Router) -> (String, String) {
+    let (_, created) = send(app, Request::builder().uri("/new").body(Body::empty()).unwrap()).await;
+    (
+        created["id"].as_str().unwrap().to_string(),
+        created["token"].as_str().unwrap().to_string(),
+    )
+}
+
+fn patch(id: &str, token: &str, body: Value) -> Request<Body> {
+    Request::builder()
+        .uri(format!("/api/webhooks/{id}"))
+        .method("PATCH")
+        .header("authorization", format!("Bearer {token}"))
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn list(token: &str) -> Request<Body> {
+    Request::builder()
+        .uri("/api/webhooks")
+        .header("authorization", format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn ingest(id: &str) -> Request<Body> {
+    Request::builder()
+        .uri(format!("/webhook/{id}"))
+        .method("POST")
+        .body(Body::from("event"))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn webhooks_can_be_renamed_tagged_and_listed() {
+    let (app, _) = admin_app().await;
+    let (id, token) = new_webhook(&app).await;
+
+    let (status, updated) = send(
+        &app,
+        patch(&id, &token, json!({ "name": "Stripe staging", "tags": ["stripe", "staging"] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(updated["name"], "Stripe staging");
+
+    // Listing spans every webhook, so a webhook's own token isn't enough
+    let (status, _) = send(&app, list(&token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, listed) = send(&app, list(ADMIN)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed[0]["tags"], json!(["stripe", "staging"]));
+    assert!(listed[0].get("token_hash").is_none());
+
+    // Archived webhooks drop out of the default listing and stop accepting traffic
+    send(&app, patch(&id, &token, json!({ "archived": true }))).await;
+    let (_, listed) = send(&app, list(ADMIN)).await;
+    assert_eq!(listed, json!([]));
+
+    let (status, _) = send(&app, ingest(&id)).await;
+    assert_eq!(status, StatusCode::GONE);
+}
+
+#[tokio::test]
+async fn paused_webhook_answers_without_storing() {
+    let (app, state) = admin_app().await;
+    let (id, token) = new_webhook(&app).await;
+
+    let (status, _) = send(&app, patch(&id, &token, json!({ "paused": true, "paused_status": 1000 }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    send(&app, patch(&id, &token, json!({ "paused": true, "paused_status": 429 }))).await;
+
+    let (status, _) = send(&app, ingest(&id)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(state.get_requests(&id).await.is_empty());
+
+    send(&app, patch(&id, &token, json!({ "paused": false }))).await;
+    let (status, _) = send(&app, ingest(&id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(state.get_requests(&id).await.len(), 1);
+}
+
+#[tokio::test]
+async fn deleting_a_webhook_removes_requests_and_config() {
+    let (app, state) = admin_app().await;
+    let (id, token) = new_webhook(&app).await;
+
+    send(
+        &app,
+        Request::builder()
+            .uri(format!("/webhook/{id}/config"))
+            .method("POST")
+            .header("authorization", format!("Bearer {token}"))
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "status_code": 202 }).to_string()))
+            .unwrap(),
+    )
+    .await;
+    send(&app, ingest(&id)).await;
+
+    let (status, _) = send(
+        &app,
+        Request::builder()
+            .uri(format!("/api/webhooks/{id}"))
+            .method("DELETE")
+            .header("authorization", format!("Bearer {token}"))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    assert!(!state.db.webhook_exists(&id).await.unwrap());
+    assert!(state.get_requests(&id).await.is_empty());
+    assert!(state.get_response_config(&id).await.unwrap().webhook_id.is_empty());
+}
+
+#[tokio::test]
+async fn listing_is_disabled_without_an_admin_token() {
+    let app = app(test_state().await);
+    let (_, token) = new_webhook(&app).await;
+
+    let (status, _) = send(&app, list(&token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send(&app, Request::builder().uri("/api/webhooks").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
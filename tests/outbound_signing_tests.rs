@@ -0,0 +1,145 @@
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    routing::post,
+    Router,
+};
+use serde_json::json;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{broadcast, Mutex};
+use tower::ServiceExt;
+
+use webhook_tester::auth::{generate_token, hash_token};
+use webhook_tester::models::{StoredRequest, WebhookEvent};
+use webhook_tester::replay::{replay_request, ForwardRequestFn, MOCK_FORWARD_REQUEST};
+use webhook_tester::signature::{resign_request, sign_outbound, verify, OutboundSigning, SignatureProvider, SigningScheme};
+use webhook_tester::utils::new_for_tests;
+use webhook_tester::AppState;
+
+const NOW: i64 = 1_700_000_000;
+
+fn to_header_map(pairs: Vec<(String, String)>) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.insert(
+            HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            HeaderValue::from_str(&value).unwrap(),
+        );
+    }
+    map
+}
+
+#[test]
+fn stripe_and_standard_signatures_round_trip() {
+    let body = br#"{"id":"evt_1"}"#;
+
+    let stripe = OutboundSigning {
+        scheme: SigningScheme::Stripe,
+        secret: "whsec_local".into(),
+        header: None,
+        prefix: None,
+    };
+    let headers = to_header_map(sign_outbound(&stripe, body, "msg_1", NOW).unwrap());
+    assert!(verify(SignatureProvider::Stripe, "whsec_local", &headers, body, "", NOW).is_valid());
+
+    let standard = OutboundSigning {
+        scheme: SigningScheme::StandardWebhooks,
+        secret: "whsec_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw".into(),
+        header: None,
+        prefix: None,
+    };
+    let headers = to_header_map(sign_outbound(&standard, body, "msg_1", NOW).unwrap());
+    assert_eq!(headers["webhook-id"], "msg_1");
+    assert!(verify(SignatureProvider::StandardWebhooks, &standard.secret, &headers, body, "", NOW).is_valid());
+}
+
+#[test]
+fn every_standard_webhooks_delivery_gets_a_new_message_id() {
+    let signing = OutboundSigning {
+        scheme: SigningScheme::StandardWebhooks,
+        secret: "whsec_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw".into(),
+        header: None,
+        prefix: None,
+    };
+    let req = StoredRequest { id: "req-1".into(), headers: "{}".into(), body: "{}".into(), ..Default::default() };
+
+    let message_id = |req: &StoredRequest| {
+        let mut outbound = req.clone();
+        resign_request(&signing, &mut outbound, NOW).unwrap();
+        outbound.header_map()["webhook-id"].clone()
+    };
+    let first = message_id(&req);
+    assert!(first.starts_with("msg_"));
+    assert_ne!(first, message_id(&req));
+}
+
+#[test]
+fn hmac_hex_signing_supports_github_style_header() {
+    let signing = OutboundSigning {
+        scheme: SigningScheme::HmacSha256Hex,
+        secret: "s3cret".into(),
+        header: Some("x-hub-signature-256".into()),
+        prefix: Some("sha256=".into()),
+    };
+    let headers = to_header_map(sign_outbound(&signing, b"payload", "", NOW).unwrap());
+    assert!(verify(SignatureProvider::Github, "s3cret", &headers, b"payload", "", NOW).is_valid());
+}
+
+#[tokio::test]
+async fn replay_replaces_stale_signature_with_new_one() {
+    let (tx, _rx) = broadcast::channel::<WebhookEvent>(100);
+    let db = new_for_tests().await;
+    let token = generate_token();
+    db.create_webhook("wh", Some(&hash_token(&token))).await.unwrap();
+    db.store_request(&StoredRequest {
+        id: "req-signed".into(),
+        webhook_id: "wh".into(),
+        method: "POST".into(),
+        headers: r#"{"x-hub-signature-256":"sha256=stale","x-test":"1"}"#.into(),
+        body: "hello".into(),
+        query: "".into(),
+        created_at: "2025-01-01T00:00:00Z".into(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let captured: Arc<Mutex<Option<HashMap<String, String>>>> = Arc::new(Mutex::new(None));
+    let captured_clone = captured.clone();
+    let mock_fn: Box<ForwardRequestFn> = Box::new(move |_url: &str, req: &StoredRequest| {
+        let captured_inner = captured_clone.clone();
+        let headers: HashMap<String, String> = serde_json::from_str(&req.headers).unwrap();
+        Box::pin(async move {
+            *captured_inner.lock().await = Some(headers);
+            Ok(())
+        })
+    });
+    MOCK_FORWARD_REQUEST.set(mock_fn).ok();
+
+    let app = Router::new()
+        .route("/replay/:id", post(replay_request))
+        .with_state(Arc::new(AppState::new(db, tx).unwrap()));
+
+    let body = json!({
+        "target": "http://consumer.example/hooks",
+        "signing": { "scheme": "hmac-sha256-base64", "secret": "local", "header": "x-signature" }
+    });
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/replay/req-signed")
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {token}"))
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let headers = captured.lock().await.clone().unwrap();
+    assert!(!headers.contains_key("x-hub-signature-256"));
+    assert_eq!(headers.get("x-test").map(String::as_str), Some("1"));
+    assert!(headers.contains_key("x-signature"));
+}
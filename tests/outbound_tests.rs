@@ -0,0 +1,124 @@
+mod common;
+
+use axum::{
+    http::{HeaderMap, Uri},
+    response::Redirect,
+    routing::post,
+    Router,
+};
+use std::{sync::Arc, time::Duration};
+
+use webhook_tester::models::StoredRequest;
+use webhook_tester::replay;
+use webhook_tester::settings::Settings;
+use webhook_tester::AppState;
+use common::serve;
+
+/// A target that echoes the request's User-Agent and full URI, or stalls, or redirects
+fn target() -> Router {
+    Router::new()
+        .route(
+            "/echo",
+            post(|headers: HeaderMap, uri: Uri| async move {
+                let agent = headers.get("user-agent").and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+                ([("x-agent", agent), ("x-uri", uri.to_string())], "ok")
+            }),
+        )
+        .route("/slow", post(|| async { tokio::time::sleep(Duration::from_secs(5)).await }))
+        .route("/moved", post(|| async { Redirect::temporary("/echo") }))
+}
+
+async fn state(configure: impl FnOnce(&mut Settings)) -> Arc<AppState> {
+    let (tx, _) = tokio::sync::broadcast::channel(10);
+    let db = Arc::new(webhook_tester::db::init_in_memory().await.unwrap());
+    let mut settings = Settings::default();
+    // The targets here listen on loopback
+    settings.egress.allowed_cidrs = vec!["127.0.0.0/8".parse().unwrap()];
+    configure(&mut settings);
+    Arc::new(AppState::with_settings(db, tx, settings).unwrap())
+}
+
+fn request(headers: &str) -> StoredRequest {
+    StoredRequest { method: "POST".into(), headers: headers.into(), body: "{}".into(), ..Default::default() }
+}
+
+#[tokio::test]
+async fn the_shared_client_identifies_itself_unless_the_request_did() {
+    let target = serve(target()).await;
+    let state = state(|_| {}).await;
+
+    let response = state.http.post(format!("{target}/echo")).send().await.unwrap();
+    assert!(response.headers()["x-agent"].to_str().unwrap().starts_with("webhook-tester/"));
+
+    // Replays keep the User-Agent they were captured with
+    let response = state.http.post(format!("{target}/echo")).header("user-agent", "Stripe/1.0").send().await.unwrap();
+    assert_eq!(response.headers()["x-agent"], "Stripe/1.0");
+}
+
+#[tokio::test]
+async fn timeouts_and_redirect_policy_apply() {
+    let target = serve(target()).await;
+    let state = state(|settings| {
+        settings.outbound.timeout = Duration::from_millis(200);
+        settings.outbound.max_redirects = 0;
+    })
+    .await;
+
+    let err = replay::send(&state, &format!("{target}/slow"), &request("{}")).await.unwrap_err();
+    assert!(err.contains("timed out"), "{err}");
+
+    // Without redirects, the redirect itself is the answer
+    assert_eq!(replay::send(&state, &format!("{target}/moved"), &request("{}")).await, Ok(Some(307)));
+
+    let following = self::state(|_| {}).await;
+    assert_eq!(replay::send(&following, &format!("{target}/moved"), &request("{}")).await, Ok(Some(200)));
+}
+
+#[tokio::test]
+async fn requests_go_through_the_configured_proxy() {
+    let proxy = serve(target()).await;
+    let state = state(|settings| {
+        settings.outbound.proxy = Some(proxy.clone());
+        settings.egress.block_private = false;
+    })
+    .await;
+
+    // The proxy gets the absolute URL; the target's name is never resolved here
+    let response = state.http.post("http://hooks.invalid/echo").send().await.unwrap();
+    assert_eq!(response.headers()["x-uri"], "http://hooks.invalid/echo");
+}
+
+#[tokio::test]
+async fn certificate_checks_are_only_skipped_for_local_targets() {
+    let strict = state(|_| {}).await;
+    assert!(strict.local_http.is_none());
+
+    let state = state(|settings| settings.outbound.insecure_tls = true).await;
+    let local = state.local_http.as_ref().unwrap();
+    assert!(std::ptr::eq(state.client_for("https://127.0.0.1:8443/hook").await, local));
+    assert!(std::ptr::eq(state.client_for("https://localhost:8443/hook").await, local));
+    assert!(std::ptr::eq(state.client_for("https://93.184.216.34/hook").await, &state.http));
+    assert!(std::ptr::eq(state.client_for("https://hooks.invalid/hook").await, &state.http));
+
+    // Local targets are still reached, and still through the egress policy
+    let target = serve(target()).await;
+    let response = local.post(format!("{target}/echo")).send().await.unwrap();
+    assert!(response.status().is_success());
+}
+
+#[tokio::test]
+async fn invalid_client_settings_are_reported() {
+    let (tx, _) = tokio::sync::broadcast::channel(10);
+    let db = Arc::new(webhook_tester::db::init_in_memory().await.unwrap());
+    let mut settings = Settings::default();
+    settings.outbound.ca_bundle = Some("/nonexistent/ca.pem".into());
+
+    let err = AppState::with_settings(db.clone(), tx.clone(), settings).err().unwrap();
+    assert!(err.to_string().contains("OUTBOUND_CA_BUNDLE"), "{err}");
+
+    // A proxy would resolve names where the blocked ranges can't be checked
+    let mut settings = Settings::default();
+    settings.outbound.proxy = Some("http://proxy.internal:3128".into());
+    let err = AppState::with_settings(db, tx, settings).err().unwrap();
+    assert!(err.to_string().contains("EGRESS_BLOCK_PRIVATE=false"), "{err}");
+}
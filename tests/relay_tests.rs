@@ -0,0 +1,221 @@
+mod common;
+
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, StatusCode, Uri},
+    routing::post,
+    Router,
+};
+use flate2::{write::GzEncoder, Compression};
+use serde_json::Value;
+use std::{io::Write, sync::Arc, time::Duration};
+use tokio::{net::TcpListener, sync::broadcast};
+
+use webhook_tester::cli::{self, Command, Server};
+use webhook_tester::routes;
+use webhook_tester::AppState;
+use common::{register_webhook, serve, test_state};
+
+#[tokio::test]
+async fn relayed_requests_are_stored_as_deliveries() {
+    let state = test_state().await;
+    let token = register_webhook(&state, "relay").await;
+    let server = serve(routes::router(state.clone())).await;
+    // The local consumer echoes the path and query it was called on
+    let local = serve(Router::new().route(
+        "/hooks",
+        post(|uri: Uri, body: String| async move { (StatusCode::CREATED, format!("{uri} {body}")) }),
+    ))
+    .await;
+
+    let relay = tokio::spawn(cli::run(Command::Relay {
+        webhook_id: "relay".into(),
+        target: format!("{local}/hooks"),
+        server: Server { url: server.clone(), token: Some(token.clone()) },
+    }));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{server}/webhook/relay?order=7"))
+        .body(r#"{"paid":true}"#)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    state.db.stream_requests("relay", &[], tx).await.unwrap();
+    let request_id = rx.recv().await.unwrap().id;
+
+    let mut deliveries = serde_json::Value::Null;
+    for _ in 0..50 {
+        deliveries = client
+            .get(format!("{server}/api/requests/{request_id}/deliveries"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if deliveries.as_array().is_some_and(|d| !d.is_empty()) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    relay.abort();
+
+    let delivery = &deliveries[0];
+    assert_eq!(delivery["status_code"], 201, "{deliveries}");
+    assert_eq!(delivery["target"], format!("{local}/hooks?order=7"));
+    assert_eq!(delivery["response_body"], r#"/hooks?order=7 {"paid":true}"#);
+    assert!(delivery["error"].is_null());
+}
+
+/// A TCP proxy to `upstream` whose connections are all dropped on `cut`
+async fn flaky_proxy(upstream: &str) -> (String, broadcast::Sender<()>) {
+    let upstream = upstream.trim_start_matches("http://").to_string();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (cut, _) = broadcast::channel(1);
+    let cuts = cut.clone();
+    tokio::spawn(async move {
+        while let Ok((mut inbound, _)) = listener.accept().await {
+            let upstream = upstream.clone();
+            let mut cut = cuts.subscribe();
+            tokio::spawn(async move {
+                let mut outbound = tokio::net::TcpStream::connect(upstream).await.unwrap();
+                tokio::select! {
+                    _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound) => {}
+                    _ = cut.recv() => {}
+                }
+            });
+        }
+    });
+    (format!("http://{addr}"), cut)
+}
+
+async fn deliveries_of(server: &str, token: &str, request_id: &str) -> Value {
+    let mut deliveries = Value::Null;
+    for _ in 0..100 {
+        deliveries = reqwest::Client::new()
+            .get(format!("{server}/api/requests/{request_id}/deliveries"))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if deliveries.as_array().is_some_and(|d| !d.is_empty()) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    deliveries
+}
+
+fn relay_to(server: &str, token: &str, target: String) -> tokio::task::JoinHandle<anyhow::Result<()>> {
+    tokio::spawn(cli::run(Command::Relay {
+        webhook_id: "relay".into(),
+        target,
+        server: Server { url: server.to_string(), token: Some(token.to_string()) },
+    }))
+}
+
+async fn last_request(state: &Arc<AppState>) -> String {
+    let newest = state.db.search_requests("relay", &Default::default(), 1).await.unwrap();
+    newest[0].id.clone()
+}
+
+#[tokio::test]
+async fn requests_missed_while_disconnected_are_relayed_on_reconnect() {
+    let state = test_state().await;
+    let token = register_webhook(&state, "relay").await;
+    let server = serve(routes::router(state.clone())).await;
+    let (proxy, cut) = flaky_proxy(&server).await;
+    let (delivered, mut deliveries) = tokio::sync::mpsc::unbounded_channel();
+    let local = serve(Router::new().route(
+        "/hooks",
+        post(move |body: String| async move {
+            delivered.send(body).unwrap();
+            StatusCode::NO_CONTENT
+        }),
+    ))
+    .await;
+
+    let relay = relay_to(&proxy, &token, format!("{local}/hooks"));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    cut.send(()).unwrap();
+    let client = reqwest::Client::new();
+    client.post(format!("{server}/webhook/relay")).body("missed").send().await.unwrap();
+    let request_id = last_request(&state).await;
+
+    let deliveries_json = deliveries_of(&server, &token, &request_id).await;
+    assert_eq!(deliveries_json[0]["status_code"], 204, "{deliveries_json}");
+    assert_eq!(deliveries.recv().await.unwrap(), "missed");
+
+    // Once caught up, the live feed carries on without sending anything twice
+    client.post(format!("{server}/webhook/relay")).body("live").send().await.unwrap();
+    let next = tokio::time::timeout(Duration::from_secs(5), deliveries.recv()).await.unwrap();
+    assert_eq!(next.unwrap(), "live");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    relay.abort();
+    assert!(deliveries.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn bodies_that_are_not_text_are_relayed_as_received() {
+    let state = test_state().await;
+    let token = register_webhook(&state, "relay").await;
+    let server = serve(routes::router(state.clone())).await;
+    // The local consumer echoes the bytes it got and how they were encoded
+    let local = serve(Router::new().route(
+        "/hooks",
+        post(|headers: HeaderMap, body: Bytes| async move {
+            let encoding = headers.get("content-encoding").map(|v| v.to_str().unwrap().to_string());
+            format!("{encoding:?} {:?}", body.to_vec())
+        }),
+    ))
+    .await;
+
+    let relay = relay_to(&server, &token, format!("{local}/hooks"));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let client = reqwest::Client::new();
+    client.post(format!("{server}/webhook/relay")).body(vec![0xff, 0x00, 0xfe]).send().await.unwrap();
+    let binary = last_request(&state).await;
+    let deliveries = deliveries_of(&server, &token, &binary).await;
+    assert_eq!(deliveries[0]["response_body"], "None [255, 0, 254]", "{deliveries}");
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(br#"{"paid":true}"#).unwrap();
+    let compressed = gzip.finish().unwrap();
+    client
+        .post(format!("{server}/webhook/relay"))
+        .header("content-encoding", "gzip")
+        .body(compressed.clone())
+        .send()
+        .await
+        .unwrap();
+    let gzipped = last_request(&state).await;
+    let deliveries = deliveries_of(&server, &token, &gzipped).await;
+    relay.abort();
+    assert_eq!(deliveries[0]["response_body"], format!("Some(\"gzip\") {compressed:?}"), "{deliveries}");
+}
+
+#[tokio::test]
+async fn relay_fails_fast_when_the_server_is_unreachable() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+
+    let result = cli::run(Command::Relay {
+        webhook_id: "relay".into(),
+        target: "http://localhost:8080/hooks".into(),
+        server: Server { url, token: None },
+    })
+    .await;
+    assert!(result.is_err());
+}
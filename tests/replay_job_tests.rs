@@ -0,0 +1,184 @@
+mod common;
+
+use axum::{http::StatusCode, routing::get, Router};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use webhook_tester::models::StoredRequest;
+use webhook_tester::replay::{ForwardRequestFn, MOCK_FORWARD_REQUEST};
+use webhook_tester::replay_jobs::{cancel_replay_job, create_replay_job, get_replay_job, list_replay_jobs};
+use common::{call, register_webhook, test_state};
+
+/// What the mock target saw, per target URL
+#[derive(Default)]
+struct Target {
+    received: Vec<(String, Instant)>,
+    in_flight: usize,
+    max_in_flight: usize,
+}
+
+static TARGETS: Mutex<Option<HashMap<String, Target>>> = Mutex::new(None);
+
+fn with_target<T>(url: &str, f: impl FnOnce(&mut Target) -> T) -> T {
+    let mut targets = TARGETS.lock().unwrap();
+    f(targets.get_or_insert_with(HashMap::new).entry(url.to_string()).or_default())
+}
+
+/// Every test shares one mock: it takes 30ms per request and fails those whose body says so
+fn install_mock() {
+    let mock: Box<ForwardRequestFn> = Box::new(|url: &str, req: &StoredRequest| {
+        let url = url.to_string();
+        let id = req.id.clone();
+        let fail = req.body.contains("fail");
+        Box::pin(async move {
+            with_target(&url, |target| {
+                target.received.push((id, Instant::now()));
+                target.in_flight += 1;
+                target.max_in_flight = target.max_in_flight.max(target.in_flight);
+            });
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            with_target(&url, |target| target.in_flight -= 1);
+            if fail {
+                anyhow::bail!("connection refused");
+            }
+            Ok(())
+        })
+    });
+    MOCK_FORWARD_REQUEST.set(mock).ok();
+}
+
+/// A webhook with one request per body, captured a second apart, and its token
+async fn app(webhook_id: &str, bodies: &[&str]) -> (Router, String) {
+    install_mock();
+    let state = test_state().await;
+    let token = register_webhook(&state, webhook_id).await;
+    let start = chrono::Utc::now() - chrono::Duration::hours(1);
+    for (i, body) in bodies.iter().enumerate() {
+        state
+            .store_request(&StoredRequest {
+                id: format!("{webhook_id}-{i}"),
+                webhook_id: webhook_id.into(),
+                method: "POST".into(),
+                headers: "{}".into(),
+                body: body.to_string(),
+                query: "{}".into(),
+                created_at: (start + chrono::Duration::seconds(i as i64)).to_rfc3339(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    let app = Router::new()
+        .route("/api/webhooks/:id/replay-jobs", get(list_replay_jobs).post(create_replay_job))
+        .route("/api/replay-jobs/:job_id", get(get_replay_job).delete(cancel_replay_job))
+        .with_state(state);
+    (app, token)
+}
+
+async fn start_job(app: &Router, token: &str, webhook_id: &str, payload: Value) -> String {
+    let (status, job) = call(app, "POST", &format!("/api/webhooks/{webhook_id}/replay-jobs"), token, Some(payload)).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+    assert_eq!(job["status"], "running");
+    job["id"].as_str().unwrap().to_string()
+}
+
+async fn wait_for_job(app: &Router, token: &str, job_id: &str) -> Value {
+    for _ in 0..500 {
+        let (_, job) = call(app, "GET", &format!("/api/replay-jobs/{job_id}"), token, None).await;
+        if job["status"] != "running" {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("job {job_id} never finished");
+}
+
+#[tokio::test]
+async fn sequential_job_keeps_order_and_scaled_timing() {
+    let (app, token) = app("ordered", &["a", "b", "c"]).await;
+    let target = "http://consumer.local/ordered";
+
+    // Captured a second apart; ten times faster is 100ms apart
+    let job_id = start_job(&app, &token, "ordered", json!({"target": target, "timing": "original", "speed": 10.0})).await;
+    let job = wait_for_job(&app, &token, &job_id).await;
+    assert_eq!(job["status"], "completed");
+    assert!(job["finished_at"].is_string());
+
+    let outcomes = job["outcomes"].as_array().unwrap();
+    let ids: Vec<&str> = outcomes.iter().map(|o| o["request_id"].as_str().unwrap()).collect();
+    assert_eq!(ids, ["ordered-0", "ordered-1", "ordered-2"]);
+    assert!(outcomes.iter().all(|o| o["status"] == "sent"));
+
+    let received = with_target(target, |t| t.received.clone());
+    let order: Vec<&str> = received.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(order, ids);
+    let gap = received[2].1 - received[0].1;
+    assert!(gap >= Duration::from_millis(190), "{gap:?}");
+    assert!(gap < Duration::from_millis(1000), "{gap:?}");
+    assert_eq!(with_target(target, |t| t.max_in_flight), 1);
+}
+
+#[tokio::test]
+async fn concurrent_job_bounds_in_flight_requests_and_reports_failures() {
+    let (app, token) = app("parallel", &["ok", "ok", "ok fail", "ok", "ok", "ok", "skip me"]).await;
+    let target = "http://consumer.local/parallel";
+
+    let job_id = start_job(&app, &token, "parallel", json!({"target": target, "concurrency": 3, "q": "ok"})).await;
+    let job = wait_for_job(&app, &token, &job_id).await;
+    assert_eq!(job["status"], "completed");
+
+    let outcomes = job["outcomes"].as_array().unwrap();
+    assert_eq!(outcomes.len(), 6);
+    let failed: Vec<&Value> = outcomes.iter().filter(|o| o["status"] == "failed").collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0]["request_id"], "parallel-2");
+    assert!(failed[0]["error"].as_str().unwrap().contains("connection refused"));
+    assert!(outcomes.iter().all(|o| o["duration_ms"].is_u64()));
+
+    let max_in_flight = with_target(target, |t| t.max_in_flight);
+    assert!((2..=3).contains(&max_in_flight), "{max_in_flight}");
+
+    let (_, jobs) = call(&app, "GET", "/api/webhooks/parallel/replay-jobs", &token, None).await;
+    assert_eq!(jobs[0]["id"], job_id.as_str());
+}
+
+#[tokio::test]
+async fn cancelled_job_skips_the_remaining_requests() {
+    let (app, token) = app("cancel", &["a", "b", "c"]).await;
+    let target = "http://consumer.local/cancel";
+
+    // A second between captures, slowed down to a minute
+    let job_id = start_job(&app, &token, "cancel", json!({"target": target, "ids": ["cancel-0", "cancel-2"], "timing": "original", "speed": 0.0167})).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (status, _) = call(&app, "DELETE", &format!("/api/replay-jobs/{job_id}"), &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let job = wait_for_job(&app, &token, &job_id).await;
+    assert_eq!(job["status"], "cancelled");
+
+    let statuses: Vec<&str> = job["outcomes"].as_array().unwrap().iter().map(|o| o["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["sent", "skipped"]);
+    assert_eq!(with_target(target, |t| t.received.len()), 1);
+}
+
+#[tokio::test]
+async fn invalid_jobs_are_rejected() {
+    let (app, token) = app("invalid", &["a"]).await;
+
+    for payload in [
+        json!({"target": "not a url"}),
+        json!({"target": "http://x", "concurrency": 0}),
+        json!({"target": "http://x", "speed": -1}),
+        json!({"target": "http://x", "q": "status:abc"}),
+        json!({"target": "http://x", "ids": ["missing"]}),
+    ] {
+        let (status, body) = call(&app, "POST", "/api/webhooks/invalid/replay-jobs", &token, Some(payload.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{payload}: {body}");
+    }
+
+    let (status, _) = call(&app, "GET", "/api/replay-jobs/missing", &token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
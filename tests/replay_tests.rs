@@ -9,6 +9,7 @@ use std::sync::Arc;
 use tokio::sync::{broadcast, Mutex};
 
 use webhook_tester::replay::{replay_request, MOCK_FORWARD_REQUEST};
+use webhook_tester::auth::{generate_token, hash_token};
 use webhook_tester::models::{StoredRequest, WebhookEvent};
 use webhook_tester::{AppState};
 use webhook_tester::utils::new_for_tests;
@@ -24,6 +25,7 @@ fn test_stored_request() -> StoredRequest {
         body: "hello world".into(),
         query: "".into(),
         created_at: "2025-01-01T00:00:00Z".into(),
+        ..Default::default()
     }
 }
 
@@ -43,7 +45,7 @@ async fn replay_fails_when_target_missing() {
     let (tx, _rx) = broadcast::channel::<WebhookEvent>(100);
     let db = new_for_tests().await;
 
-    let state = Arc::new(AppState { db, tx });
+    let state = Arc::new(AppState::new(db, tx).unwrap());
     let app = test_app(state);
 
     let req = axum::http::Request::builder()
@@ -64,7 +66,7 @@ async fn replay_fails_when_request_not_found() {
     let (tx, _rx) = broadcast::channel::<WebhookEvent>(100);
     let db = new_for_tests().await;
 
-    let state = Arc::new(AppState { db, tx });
+    let state = Arc::new(AppState::new(db, tx).unwrap());
     let app = test_app(state);
 
     let req = axum::http::Request::builder()
@@ -87,14 +89,17 @@ async fn replay_sends_request_successfully() {
 
     let (tx, _rx) = broadcast::channel::<WebhookEvent>(100);
     let db = new_for_tests().await;
+    let token = generate_token();
+    db.create_webhook("wh-1", Some(&hash_token(&token))).await.unwrap();
     db.store_request(&test_stored_request()).await.unwrap();
 
-    let state = Arc::new(AppState { db, tx });
+    let state = Arc::new(AppState::new(db, tx).unwrap());
     let app = test_app(state);
 
     let captured: Arc<Mutex<Option<(String, String)>>> = Arc::new(Mutex::new(None));
     let captured_clone = captured.clone();
 
+    #[allow(clippy::type_complexity)]
     let mock_fn: Box<
         dyn Fn(&str, &StoredRequest) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send>>
         + Send
@@ -117,6 +122,7 @@ async fn replay_sends_request_successfully() {
         .method("POST")
         .uri("/replay/req-1")
         .header("content-type", "application/json")
+        .header("authorization", format!("Bearer {token}"))
         .body(Body::from(json!({ "target": "http://mock.url" }).to_string()))
         .unwrap();
 
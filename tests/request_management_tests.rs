@@ -0,0 +1,94 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    routing::{delete, put},
+    Router,
+};
+use chrono::{Duration, Utc};
+use tower::ServiceExt;
+
+use webhook_tester::models::StoredRequest;
+use webhook_tester::routes::api::{clear_requests, delete_request, pin_request, unpin_request};
+use common::{register_webhook, test_state};
+
+fn stored(id: &str, created_at: String) -> StoredRequest {
+    StoredRequest {
+        id: id.into(),
+        webhook_id: "managed".into(),
+        method: "POST".into(),
+        headers: "{}".into(),
+        body: id.into(),
+        query: "{}".into(),
+        created_at,
+        ..Default::default()
+    }
+}
+
+fn request(method: &str, uri: &str, token: &str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("authorization", format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn requests_can_be_deleted_pinned_and_cleared() {
+    let state = test_state().await;
+    let token = register_webhook(&state, "managed").await;
+    for id in ["a", "b", "c"] {
+        state.store_request(&stored(id, Utc::now().to_rfc3339())).await.unwrap();
+    }
+
+    let app = Router::new()
+        .route("/api/webhooks/:id/requests", delete(clear_requests))
+        .route("/api/requests/:req_id", delete(delete_request))
+        .route("/api/requests/:req_id/pin", put(pin_request).delete(unpin_request))
+        .with_state(state.clone());
+
+    let response = app.clone().oneshot(request("DELETE", "/api/requests/a", &token)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = app.clone().oneshot(request("PUT", "/api/requests/b/pin", &token)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(state.db.get_request("b").await.unwrap().pinned);
+
+    let response = app
+        .clone()
+        .oneshot(request("DELETE", "/api/webhooks/managed/requests", &token))
+        .await
+        .unwrap();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&bytes[..], br#"{"deleted":1}"#);
+
+    let remaining: Vec<String> = state.get_requests("managed").await.into_iter().map(|r| r.id).collect();
+    assert_eq!(remaining, vec!["b".to_string()]);
+
+    let response = app.oneshot(request("DELETE", "/api/requests/missing", &token)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn retention_purges_only_old_unpinned_requests() {
+    let state = test_state().await;
+    register_webhook(&state, "managed").await;
+
+    let old = (Utc::now() - Duration::hours(72)).to_rfc3339();
+    state.store_request(&stored("old", old.clone())).await.unwrap();
+    state.store_request(&StoredRequest { pinned: true, ..stored("old-pinned", old) }).await.unwrap();
+    state.store_request(&stored("fresh", Utc::now().to_rfc3339())).await.unwrap();
+
+    let purged = state
+        .db
+        .purge_requests_before(Utc::now() - Duration::hours(48))
+        .await
+        .unwrap();
+    assert_eq!(purged, 1);
+
+    let mut remaining: Vec<String> = state.get_requests("managed").await.into_iter().map(|r| r.id).collect();
+    remaining.sort();
+    assert_eq!(remaining, vec!["fresh".to_string(), "old-pinned".to_string()]);
+}
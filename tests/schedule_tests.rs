@@ -0,0 +1,155 @@
+mod common;
+
+use axum::{
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use std::sync::Mutex;
+
+use webhook_tester::models::StoredRequest;
+use webhook_tester::replay::{ForwardRequestFn, MOCK_FORWARD_REQUEST};
+use webhook_tester::schedules::{create_schedule, delete_schedule, get_schedule, list_schedules, run_due};
+use webhook_tester::AppState;
+use common::{call, register_webhook, test_state};
+
+/// Target URL and signature header of every replay the mock received
+static SENT: Mutex<Vec<(String, Option<String>)>> = Mutex::new(Vec::new());
+
+fn install_mock() {
+    let mock: Box<ForwardRequestFn> = Box::new(|url: &str, req: &StoredRequest| {
+        let signature = req.header_map().get("x-webhook-signature").cloned();
+        SENT.lock().unwrap().push((url.to_string(), signature));
+        Box::pin(async { Ok(()) })
+    });
+    MOCK_FORWARD_REQUEST.set(mock).ok();
+}
+
+fn sent_to(target: &str) -> Vec<Option<String>> {
+    SENT.lock()
+        .unwrap()
+        .iter()
+        .filter(|(url, _)| url == target)
+        .map(|(_, signature)| signature.clone())
+        .collect()
+}
+
+async fn app(webhook_id: &str) -> (Router, std::sync::Arc<AppState>, String) {
+    install_mock();
+    let state = test_state().await;
+    let token = register_webhook(&state, webhook_id).await;
+    state
+        .store_request(&StoredRequest {
+            id: format!("{webhook_id}-req"),
+            webhook_id: webhook_id.into(),
+            method: "POST".into(),
+            headers: "{}".into(),
+            body: r#"{"ping":true}"#.into(),
+            query: "{}".into(),
+            created_at: (Utc::now() - Duration::days(30)).to_rfc3339(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let app = Router::new()
+        .route("/api/requests/:req_id/schedules", post(create_schedule))
+        .route("/api/webhooks/:id/schedules", get(list_schedules))
+        .route("/api/schedules/:schedule_id", get(get_schedule).delete(delete_schedule))
+        .with_state(state.clone());
+    (app, state, token)
+}
+
+#[tokio::test]
+async fn one_off_and_recurring_schedules_fire_when_due() {
+    let (app, state, token) = app("sched").await;
+    let once = "http://consumer.local/once";
+    let nightly = "http://consumer.local/nightly";
+    let now = Utc::now();
+
+    let (status, one_off) = call(&app, "POST", "/api/requests/sched-req/schedules", &token, Some(json!({
+        "target": once,
+        "at": (now + Duration::hours(1)).to_rfc3339(),
+        "signing": {"scheme": "hmac-sha256-hex", "secret": "s3cret"}
+    })))
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{one_off}");
+    // The signing secret is never echoed back
+    assert!(!one_off.to_string().contains("s3cret"));
+
+    let (status, recurring) = call(&app, "POST", "/api/requests/sched-req/schedules", &token, Some(json!({
+        "target": nightly,
+        "cron": "0 2 * * *"
+    })))
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{recurring}");
+    assert!(recurring["next_run_at"].as_str().unwrap().ends_with("T02:00:00.000Z"));
+
+    let (_, listed) = call(&app, "GET", "/api/webhooks/sched/schedules", &token, None).await;
+    assert_eq!(listed.as_array().unwrap().len(), 2);
+
+    // Nothing is due yet
+    assert_eq!(run_due(&state, now).await.unwrap(), 0);
+
+    let later = now + Duration::days(1) + Duration::hours(1);
+    assert_eq!(run_due(&state, later).await.unwrap(), 2);
+    // Both moved on, so the same sweep doesn't fire them again
+    assert_eq!(run_due(&state, later).await.unwrap(), 0);
+
+    let signatures = sent_to(once);
+    assert_eq!(signatures.len(), 1);
+    assert!(signatures[0].is_some());
+    assert_eq!(sent_to(nightly), vec![None]);
+
+    let (_, one_off) = call(&app, "GET", &format!("/api/schedules/{}", one_off["id"].as_str().unwrap()), &token, None).await;
+    assert_eq!(one_off["runs"], 1);
+    assert!(one_off["next_run_at"].is_null());
+    assert!(one_off["last_error"].is_null());
+
+    let (_, recurring) = call(&app, "GET", &format!("/api/schedules/{}", recurring["id"].as_str().unwrap()), &token, None).await;
+    assert_eq!(recurring["runs"], 1);
+    let next = chrono::DateTime::parse_from_rfc3339(recurring["next_run_at"].as_str().unwrap()).unwrap();
+    assert!(next > later);
+}
+
+#[tokio::test]
+async fn schedules_are_validated_and_cancelable() {
+    let (app, state, token) = app("cancel").await;
+    let future = (Utc::now() + Duration::hours(1)).to_rfc3339();
+
+    for payload in [
+        json!({"target": "http://x"}),
+        json!({"target": "http://x", "at": future, "cron": "* * * * *"}),
+        json!({"target": "http://x", "at": "2001-01-01T00:00:00Z"}),
+        json!({"target": "http://x", "cron": "every night"}),
+        json!({"target": "nowhere", "at": future}),
+    ] {
+        let (status, body) = call(&app, "POST", "/api/requests/cancel-req/schedules", &token, Some(payload.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{payload}: {body}");
+    }
+    let (status, _) = call(&app, "POST", "/api/requests/missing/schedules", &token, Some(json!({"target": "http://x", "at": future}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, schedule) = call(&app, "POST", "/api/requests/cancel-req/schedules", &token, Some(json!({"target": "http://x", "cron": "*/5 * * * *"}))).await;
+    let uri = format!("/api/schedules/{}", schedule["id"].as_str().unwrap());
+
+    // Retention spares a request while it has a schedule
+    assert_eq!(state.db.purge_requests_before(Utc::now()).await.unwrap(), 0);
+
+    let (status, _) = call(&app, "DELETE", &uri, &token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = call(&app, "GET", &uri, &token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(state.db.purge_requests_before(Utc::now()).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn deleting_the_request_removes_its_schedules() {
+    let (app, state, token) = app("cascade").await;
+    let (_, schedule) = call(&app, "POST", "/api/requests/cascade-req/schedules", &token, Some(json!({"target": "http://x", "cron": "0 0 * * * *"}))).await;
+
+    state.db.delete_request("cascade-req").await.unwrap();
+    assert!(state.db.get_schedule(schedule["id"].as_str().unwrap()).await.unwrap().is_none());
+}
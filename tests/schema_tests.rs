@@ -0,0 +1,183 @@
+mod common;
+
+use axum::http::{HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use webhook_tester::schema::{PayloadSchema, SchemaVerdict};
+use webhook_tester::testing::{TestServer, TestWebhook};
+use common::call_server;
+
+fn order_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["id", "amount"],
+        "properties": {
+            "id": {"type": "integer"},
+            "amount": {"type": "number", "minimum": 0}
+        }
+    })
+}
+
+async fn configure(server: &TestServer, webhook: &TestWebhook, config: Value) -> (StatusCode, Value) {
+    call_server(server, webhook, "POST", &format!("/webhook/{}/config", webhook.id), Some(config)).await
+}
+
+#[test]
+fn single_schema_reports_json_pointer_paths() {
+    let schema = PayloadSchema { schema: Some(order_schema()), ..Default::default() };
+    assert!(schema.check().is_ok());
+
+    let headers = HeaderMap::new();
+    assert_eq!(schema.validate(&headers, br#"{"id":1,"amount":9.5}"#), SchemaVerdict::Valid);
+
+    let verdict = schema.validate(&headers, br#"{"id":"one","amount":-1}"#);
+    assert_eq!(verdict.status(), "invalid");
+    let mut paths: Vec<String> = verdict.errors().into_iter().map(|e| e.path).collect();
+    paths.sort();
+    assert_eq!(paths, vec!["/amount", "/id"]);
+
+    let verdict = schema.validate(&headers, b"not json");
+    assert!(verdict.is_invalid());
+    assert!(verdict.errors()[0].message.contains("not JSON"));
+}
+
+#[test]
+fn event_keyed_schemas_pick_by_body_field_or_header() {
+    let by_body = PayloadSchema {
+        schemas: [("order.created".to_string(), order_schema())].into(),
+        event_field: Some("data.type".into()),
+        ..Default::default()
+    };
+    let headers = HeaderMap::new();
+    assert_eq!(by_body.validate(&headers, br#"{"data":{"type":"order.created"},"id":1,"amount":2}"#), SchemaVerdict::Valid);
+    assert!(by_body.validate(&headers, br#"{"data":{"type":"order.created"},"id":1}"#).is_invalid());
+
+    let unmatched = by_body.validate(&headers, br#"{"data":{"type":"order.deleted"}}"#);
+    assert_eq!(unmatched.status(), "unmatched");
+    assert!(unmatched.errors()[0].message.contains("order.deleted"));
+
+    let by_header = PayloadSchema {
+        schema: Some(json!({"type": "object"})),
+        schemas: [("push".to_string(), json!({"required": ["ref"]}))].into(),
+        event_field: Some("header:X-GitHub-Event".into()),
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert("x-github-event", HeaderValue::from_static("push"));
+    assert!(by_header.validate(&headers, br#"{"zen":"hi"}"#).is_invalid());
+    // Event types without their own schema fall back to the shared one
+    headers.insert("x-github-event", HeaderValue::from_static("ping"));
+    assert_eq!(by_header.validate(&headers, br#"{"zen":"hi"}"#), SchemaVerdict::Valid);
+}
+
+#[test]
+fn check_rejects_broken_configurations() {
+    assert!(PayloadSchema::default().check().is_err());
+
+    let missing_field = PayloadSchema { schemas: [("a".to_string(), json!({}))].into(), ..Default::default() };
+    assert!(missing_field.check().unwrap_err().contains("event_field"));
+
+    let bad = PayloadSchema { schema: Some(json!({"type": "nonsense"})), ..Default::default() };
+    assert!(bad.check().unwrap_err().starts_with("Invalid schema"));
+}
+
+#[tokio::test]
+async fn captured_requests_record_their_schema_verdict() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+
+    let (status, body) = configure(&server, &webhook, json!({"payload_schema": {"schema": {"type": "nonsense"}}})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+
+    let (status, body) = configure(&server, &webhook, json!({"payload_schema": {"schema": order_schema()}})).await;
+    assert!(status.is_success(), "{body}");
+
+    let client = reqwest::Client::new();
+    let response = client.post(&webhook.url).json(&json!({"id": 1, "amount": 3})).send().await.unwrap();
+    assert!(response.status().is_success());
+    let response = client.post(&webhook.url).json(&json!({"id": 2})).send().await.unwrap();
+    assert!(response.status().is_success(), "invalid payloads are only flagged by default");
+
+    let valid = server.next_request(Duration::from_secs(5), |req| req.body.contains("\"amount\"")).await.unwrap();
+    assert_eq!(valid.schema_status.as_deref(), Some("valid"));
+    assert!(valid.schema_errors.is_none());
+
+    let invalid = server.next_request(Duration::from_secs(5), |_| true).await.unwrap();
+    assert_eq!(invalid.schema_status.as_deref(), Some("invalid"));
+    let errors = invalid.schema_error_list();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.contains("amount"), "{errors:?}");
+
+    let (status, found) = call_server(&server, &webhook, "GET", &format!("/api/webhooks/{}/search?q=schema:invalid", webhook.id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let ids: Vec<&str> = found.as_array().unwrap().iter().map(|r| r["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec![invalid.id.as_str()]);
+}
+
+#[tokio::test]
+async fn invalid_payloads_can_be_rejected() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let config = json!({"payload_schema": {"schema": order_schema()}, "reject_invalid_payloads": true});
+    assert!(configure(&server, &webhook, config).await.0.is_success());
+
+    let response = reqwest::Client::new().post(&webhook.url).json(&json!({"id": "x"})).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "Payload does not match its schema");
+    assert!(!body["errors"].as_array().unwrap().is_empty());
+
+    let stored = server.next_request(Duration::from_secs(5), |_| true).await.unwrap();
+    assert_eq!(stored.response_status, Some(400));
+    assert_eq!(stored.response_source.as_deref(), Some("schema"));
+}
+
+#[tokio::test]
+async fn rejected_payloads_do_not_advance_the_response_sequence() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let config = json!({
+        "payload_schema": {"schema": order_schema()},
+        "reject_invalid_payloads": true,
+        "response_sequence": [{"status_code": 201}, {"status_code": 202}]
+    });
+    assert!(configure(&server, &webhook, config).await.0.is_success());
+
+    let client = reqwest::Client::new();
+    let rejected = client.post(&webhook.url).json(&json!({"id": "x"})).send().await.unwrap();
+    assert_eq!(rejected.status(), StatusCode::BAD_REQUEST);
+
+    let valid = json!({"id": 1, "amount": 5});
+    let first = client.post(&webhook.url).json(&valid).send().await.unwrap();
+    assert_eq!(first.status(), StatusCode::CREATED);
+    let second = client.post(&webhook.url).json(&valid).send().await.unwrap();
+    assert_eq!(second.status(), StatusCode::ACCEPTED);
+}
+
+#[tokio::test]
+async fn validate_endpoint_checks_a_sample_payload() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let path = format!("/api/webhooks/{}/schema/validate", webhook.id);
+
+    let (status, _) = call_server(&server, &webhook, "POST", &path, Some(json!({"id": 1}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    assert!(configure(&server, &webhook, json!({"payload_schema": {"schema": order_schema()}})).await.0.is_success());
+
+    let (status, result) = call_server(&server, &webhook, "POST", &path, Some(json!({"id": 1, "amount": 1}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result["status"], "valid");
+
+    let (_, result) = call_server(&server, &webhook, "POST", &path, Some(json!({"id": 1, "amount": -5}))).await;
+    assert_eq!(result["status"], "invalid");
+    assert_eq!(result["errors"][0]["path"], "/amount");
+
+    // A saved schema replaces the compiled one
+    let stricter = json!({"type": "object", "required": ["currency"]});
+    assert!(configure(&server, &webhook, json!({"payload_schema": {"schema": stricter}})).await.0.is_success());
+    let (_, result) = call_server(&server, &webhook, "POST", &path, Some(json!({"id": 1, "amount": 1}))).await;
+    assert_eq!(result["status"], "invalid");
+    assert!(result["errors"][0]["message"].as_str().unwrap().contains("currency"), "{result}");
+}
@@ -0,0 +1,200 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    routing::get,
+    Router,
+};
+use chrono::{Duration, TimeZone, Utc};
+use tower::ServiceExt;
+
+use webhook_tester::models::{Delivery, StoredRequest};
+use webhook_tester::routes::api::search_requests;
+use webhook_tester::search::{SearchQuery, StatusFilter};
+use common::{register_webhook, test_state};
+
+fn stored(id: &str, method: &str, headers: &str, body: &str, status: u16, age_hours: i64) -> StoredRequest {
+    StoredRequest {
+        id: id.into(),
+        webhook_id: "search".into(),
+        method: method.into(),
+        headers: headers.into(),
+        body: body.into(),
+        query: "{}".into(),
+        created_at: (Utc::now() - Duration::hours(age_hours)).to_rfc3339(),
+        response_status: Some(status),
+        ..Default::default()
+    }
+}
+
+async fn search(app: &Router, token: &str, q: &str) -> (StatusCode, serde_json::Value) {
+    let uri = format!("/api/webhooks/search/search?q={}", urlencode(q));
+    let request = Request::builder().uri(uri).header("authorization", format!("Bearer {token}"));
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+fn urlencode(q: &str) -> String {
+    serde_urlencoded::to_string([("", q)]).unwrap()[1..].to_string()
+}
+
+fn ids(results: &serde_json::Value) -> Vec<&str> {
+    results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["id"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn query_syntax_parses_filters_and_text() {
+    let now = Utc.with_ymd_and_hms(2025, 3, 10, 12, 0, 0).unwrap();
+    let query = SearchQuery::parse_at(
+        r#"method:post header:X-GitHub-Event=push json:data.id=7 status:5xx after:24h before:2025-03-10 "payment failed" "method:literal" https://example.com"#,
+        now,
+    )
+    .unwrap();
+
+    assert_eq!(query.method.as_deref(), Some("POST"));
+    assert_eq!(query.headers, vec![("x-github-event".to_string(), "push".to_string())]);
+    assert_eq!(query.json, vec![("$.data.id".to_string(), "7".to_string())]);
+    assert_eq!(query.status, Some(StatusFilter::Class(5)));
+    assert_eq!(query.after, Some(now - Duration::hours(24)));
+    assert_eq!(query.before, Some(Utc.with_ymd_and_hms(2025, 3, 10, 0, 0, 0).unwrap()));
+    assert_eq!(query.text, vec!["payment failed", "method:literal", "https://example.com"]);
+
+    assert!(SearchQuery::parse_at("status:abc", now).is_err());
+    assert!(SearchQuery::parse_at("after:yesterday", now).is_err());
+    assert!(SearchQuery::parse_at("json:noequals", now).is_err());
+    for path in ["$.a[x]", "$..a", "$x", "$.a[", "a.", "$.a[#-]"] {
+        assert!(SearchQuery::parse_at(&format!("json:{path}=1"), now).is_err(), "{path}");
+    }
+    let query = SearchQuery::parse_at("json:items[#-1].id=1 json:$.a.b[0]=2 json:[2]=3", now).unwrap();
+    let paths: Vec<&str> = query.json.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(paths, ["$.items[#-1].id", "$.a.b[0]", "$[2]"]);
+    assert!(SearchQuery::parse_at("\"unterminated", now).is_err());
+}
+
+#[tokio::test]
+async fn search_combines_full_text_and_structured_filters() {
+    let state = test_state().await;
+    let token = register_webhook(&state, "search").await;
+
+    let github = r#"{"x-github-event":"push","content-type":"application/json"}"#;
+    let stripe = r#"{"stripe-signature":"t=1,v1=abc","content-type":"application/json"}"#;
+    for req in [
+        stored("order", "POST", stripe, r#"{"data":{"order_id":12345,"status":"paid"}}"#, 200, 30),
+        stored("order-str", "POST", stripe, r#"{"data":{"order_id":"12345"}}"#, 500, 1),
+        stored("push", "POST", github, r#"{"ref":"refs/heads/main","pusher":"octocat"}"#, 200, 2),
+        stored("plain", "PUT", "{}", "payment failed for customer", 503, 3),
+    ] {
+        state.store_request(&req).await.unwrap();
+    }
+
+    let app = Router::new()
+        .route("/api/webhooks/:id/search", get(search_requests))
+        .with_state(state.clone());
+
+    let (status, results) = search(&app, &token, "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&results), vec!["order-str", "push", "plain", "order"]);
+
+    let (_, results) = search(&app, &token, "octocat").await;
+    assert_eq!(ids(&results), vec!["push"]);
+
+    // Headers are indexed too
+    let (_, results) = search(&app, &token, "stripe").await;
+    assert_eq!(ids(&results), vec!["order-str", "order"]);
+
+    let (_, results) = search(&app, &token, r#""payment failed""#).await;
+    assert_eq!(ids(&results), vec!["plain"]);
+
+    let (_, results) = search(&app, &token, "method:put").await;
+    assert_eq!(ids(&results), vec!["plain"]);
+
+    let (_, results) = search(&app, &token, "header:X-GitHub-Event=push").await;
+    assert_eq!(ids(&results), vec!["push"]);
+
+    // Numbers and strings with the same spelling both match
+    let (_, results) = search(&app, &token, "json:data.order_id=12345").await;
+    assert_eq!(ids(&results), vec!["order-str", "order"]);
+
+    let (_, results) = search(&app, &token, "json:data.order_id=12345 after:24h").await;
+    assert_eq!(ids(&results), vec!["order-str"]);
+
+    let (_, results) = search(&app, &token, "status:5xx").await;
+    assert_eq!(ids(&results), vec!["order-str", "plain"]);
+
+    let (_, results) = search(&app, &token, "status:503 before:2h").await;
+    assert_eq!(ids(&results), vec!["plain"]);
+
+    let (status, results) = search(&app, &token, "status:9xx").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(results["error"].is_string());
+
+    let (status, _) = search(&app, &token, "json:$.a[x]=1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let delivery = Delivery {
+        id: "relayed".into(),
+        request_id: "order".into(),
+        target: "http://localhost:8080/hooks".into(),
+        status_code: Some(200),
+        response_headers: None,
+        response_body: None,
+        error: None,
+        duration_ms: None,
+        created_at: Utc::now().to_rfc3339(),
+    };
+    state.db.store_delivery(&delivery).await.unwrap();
+    let (_, results) = search(&app, &token, "delivered:no").await;
+    assert_eq!(ids(&results), vec!["order-str", "push", "plain"]);
+    let (_, results) = search(&app, &token, "delivered:yes").await;
+    assert_eq!(ids(&results), vec!["order"]);
+    let (status, _) = search(&app, &token, "delivered:maybe").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Deleted requests leave the index with them
+    state.db.delete_request("push").await.unwrap();
+    let (_, results) = search(&app, &token, "octocat").await;
+    assert_eq!(ids(&results), Vec::<&str>::new());
+}
+
+#[tokio::test]
+async fn rowid_keyed_index_is_rebuilt_and_survives_vacuum() {
+    let state = test_state().await;
+    register_webhook(&state, "search").await;
+    let pool = &state.db.pool;
+
+    for (id, body) in [("first", "alpha"), ("second", "bravo"), ("third", "charlie")] {
+        state.store_request(&stored(id, "POST", "{}", body, 200, 1)).await.unwrap();
+    }
+    // The index as it was first created, keyed on the requests' implicit rowid
+    for statement in [
+        "DROP TRIGGER requests_fts_insert",
+        "DROP TRIGGER requests_fts_delete",
+        "DROP TRIGGER requests_fts_update",
+        "DROP TABLE requests_fts",
+        "CREATE VIRTUAL TABLE requests_fts USING fts5(body, headers)",
+        "INSERT INTO requests_fts (rowid, body, headers) SELECT rowid, body, headers FROM requests",
+    ] {
+        sqlx::query(statement).execute(pool).await.unwrap();
+    }
+
+    webhook_tester::db::run_migrations(pool).await.unwrap();
+    state.db.delete_request("first").await.unwrap();
+    sqlx::query("VACUUM").execute(pool).await.unwrap();
+
+    let found = state.db.search_requests("search", &"charlie".parse().unwrap(), 10).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, "third");
+    assert!(state.db.search_requests("search", &"alpha".parse().unwrap(), 10).await.unwrap().is_empty());
+}
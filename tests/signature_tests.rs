@@ -0,0 +1,206 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, Request, StatusCode},
+};
+use tower::ServiceExt;
+
+use webhook_tester::models::WebhookConfig;
+use webhook_tester::routes::webhook::{set_custom_response, webhook_handler};
+use webhook_tester::signature::{verify, SignatureProvider, Verdict};
+use common::{register_webhook, test_state};
+
+const BODY: &[u8] = br#"{"action":"opened"}"#;
+const NOW: i64 = 1_700_000_000;
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    map
+}
+
+#[test]
+fn github_signature_is_verified() {
+    let h = headers(&[(
+        "x-hub-signature-256",
+        "sha256=5ccdd8275f57d608741ad8390e42e8696ebfcd85607d9c2ef890769dda8f7568",
+    )]);
+
+    let verdict = verify(SignatureProvider::Github, "It's a Secret to Everybody", &h, BODY, "", NOW);
+    assert_eq!(verdict, Verdict::Valid);
+
+    let verdict = verify(SignatureProvider::Github, "wrong", &h, BODY, "", NOW);
+    assert_eq!(verdict.status(), "invalid");
+
+    let verdict = verify(SignatureProvider::Github, "secret", &HeaderMap::new(), BODY, "", NOW);
+    assert_eq!(verdict.status(), "missing");
+}
+
+#[test]
+fn stripe_signature_respects_timestamp_tolerance() {
+    let h = headers(&[(
+        "stripe-signature",
+        "t=1700000000,v1=05b82fecc44b706d9d261c030899aa32f52a887e2a55a448d784fab853f42fb3",
+    )]);
+
+    assert!(verify(SignatureProvider::Stripe, "whsec_test", &h, BODY, "", NOW + 10).is_valid());
+
+    let stale = verify(SignatureProvider::Stripe, "whsec_test", &h, BODY, "", NOW + 3600);
+    assert_eq!(stale.status(), "invalid");
+    assert!(stale.reason().contains("tolerance"));
+}
+
+#[test]
+fn slack_and_shopify_signatures_are_verified() {
+    let slack = headers(&[
+        ("x-slack-signature", "v0=c71f92627014d5161c902a6cddb4f1638b6cb0794abb4dd4f2fec80c9730301f"),
+        ("x-slack-request-timestamp", "1700000000"),
+    ]);
+    assert!(verify(SignatureProvider::Slack, "slacksecret", &slack, BODY, "", NOW).is_valid());
+
+    let shopify = headers(&[("x-shopify-hmac-sha256", "AjX71GbR9AH00MNrRF3DQC2fi8B0k8z/E3jjomaelRA=")]);
+    assert!(verify(SignatureProvider::Shopify, "shopsecret", &shopify, BODY, "", NOW).is_valid());
+}
+
+#[test]
+fn twilio_signature_covers_url_and_sorted_params() {
+    let h = headers(&[("x-twilio-signature", "FPRKSLdMhHR8Y35nLQB3MgMsr7k=")]);
+    let form = b"To=%2B15551234567&From=%2B15557654321&Body=hi";
+
+    let verdict = verify(SignatureProvider::Twilio, "twiliotoken", &h, form, "https://example.com/webhook/tw", NOW);
+    assert!(verdict.is_valid());
+
+    let verdict = verify(SignatureProvider::Twilio, "twiliotoken", &h, form, "https://example.com/other", NOW);
+    assert_eq!(verdict.status(), "invalid");
+}
+
+#[test]
+fn standard_webhooks_signature_accepts_svix_headers() {
+    let h = headers(&[
+        ("svix-id", "msg_1"),
+        ("svix-timestamp", "1700000000"),
+        ("svix-signature", "v1,bogus v1,QYGv8DU8M4E0d7WHwspwSppXVAWlfrfUPz+K7fo1Z20="),
+    ]);
+
+    let verdict = verify(
+        SignatureProvider::StandardWebhooks,
+        "whsec_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw",
+        &h,
+        BODY,
+        "",
+        NOW,
+    );
+    assert!(verdict.is_valid());
+}
+
+#[tokio::test]
+async fn webhook_stores_verdict_and_rejects_invalid_signature() {
+    let state = test_state().await;
+    register_webhook(&state, "signed").await;
+
+    state.db
+        .set_response_config(&WebhookConfig {
+            webhook_id: "signed".into(),
+            signing_provider: Some("github".into()),
+            signing_secret: Some("It's a Secret to Everybody".into()),
+            reject_invalid_signatures: Some(true),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let app = axum::Router::new()
+        .route("/webhook/:id", axum::routing::post(webhook_handler))
+        .with_state(state.clone());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/signed")
+                .method("POST")
+                .header("x-hub-signature-256", "sha256=deadbeef")
+                .body(Body::from(BODY))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let stored = state.get_requests("signed").await.pop().unwrap();
+    assert_eq!(stored.signature_status.as_deref(), Some("invalid"));
+    assert_eq!(stored.response_status, Some(401));
+    assert_eq!(stored.response_source.as_deref(), Some("signature"));
+}
+
+#[tokio::test]
+async fn rejected_signatures_do_not_advance_the_response_sequence() {
+    let state = test_state().await;
+    register_webhook(&state, "scripted").await;
+
+    state.db
+        .set_response_config(&WebhookConfig {
+            webhook_id: "scripted".into(),
+            response_sequence: Some(r#"[{"status_code":201},{"status_code":202}]"#.into()),
+            signing_provider: Some("github".into()),
+            signing_secret: Some("It's a Secret to Everybody".into()),
+            reject_invalid_signatures: Some(true),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let app = axum::Router::new()
+        .route("/webhook/:id", axum::routing::post(webhook_handler))
+        .with_state(state.clone());
+
+    let send = |signature: &'static str| {
+        app.clone().oneshot(
+            Request::builder()
+                .uri("/webhook/scripted")
+                .method("POST")
+                .header("x-hub-signature-256", signature)
+                .body(Body::from(BODY))
+                .unwrap(),
+        )
+    };
+
+    assert_eq!(send("sha256=deadbeef").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    let valid = "sha256=5ccdd8275f57d608741ad8390e42e8696ebfcd85607d9c2ef890769dda8f7568";
+    assert_eq!(send(valid).await.unwrap().status(), StatusCode::CREATED);
+    assert_eq!(send(valid).await.unwrap().status(), StatusCode::ACCEPTED);
+}
+
+#[tokio::test]
+async fn saving_config_without_the_secret_keeps_it() {
+    let state = test_state().await;
+    let token = state.create_webhook("kept").await.unwrap();
+
+    let app = axum::Router::new()
+        .route("/webhook/:id/config", axum::routing::post(set_custom_response))
+        .with_state(state.clone());
+
+    let save = |config: serde_json::Value| {
+        app.clone().oneshot(
+            Request::builder()
+                .uri("/webhook/kept/config")
+                .method("POST")
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {token}"))
+                .body(Body::from(config.to_string()))
+                .unwrap(),
+        )
+    };
+
+    save(serde_json::json!({"signing_provider": "github", "signing_secret": "s3cret"})).await.unwrap();
+    save(serde_json::json!({"signing_provider": "github", "status_code": 204})).await.unwrap();
+    let config = state.db.get_response_config("kept").await.unwrap();
+    assert_eq!(config.signing_secret.as_deref(), Some("s3cret"));
+    assert_eq!(config.status_code, Some(204));
+
+    save(serde_json::json!({"signing_provider": "github", "signing_secret": ""})).await.unwrap();
+    assert_eq!(state.db.get_response_config("kept").await.unwrap().signing_secret, None);
+}
@@ -0,0 +1,73 @@
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+use webhook_tester::testing::TestServer;
+
+#[tokio::test]
+async fn captures_requests_and_serves_the_api() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+    let client = reqwest::Client::new();
+
+    // Sent before anyone waits for it
+    let response = client.post(&webhook.url).body(r#"{"type":"invoice.paid"}"#).send().await.unwrap();
+    assert!(response.status().is_success());
+    client.post(&webhook.url).body("second").send().await.unwrap();
+
+    let req = server
+        .next_request(Duration::from_secs(5), |req| req.webhook_id == webhook.id && req.body == "second")
+        .await
+        .unwrap();
+    assert_eq!(req.body, "second");
+    let req = server.next_request(Duration::from_secs(5), |_| true).await.unwrap();
+    assert_eq!(req.body, r#"{"type":"invoice.paid"}"#);
+    assert_eq!(req.response_status, Some(200));
+
+    // Each request is returned once
+    let err = server.next_request(Duration::from_millis(100), |_| true).await.unwrap_err();
+    assert!(err.to_string().starts_with("No matching request arrived"), "{err}");
+
+    let requests = format!("{}/api/webhooks/{}/requests", server.url(), webhook.id);
+    let response = client.get(&requests).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    let listed: Vec<Value> = client.get(&requests).bearer_auth(&webhook.token).send().await.unwrap().json().await.unwrap();
+    assert_eq!(listed.len(), 2);
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn waits_for_requests_still_to_come_and_keeps_the_live_feed() {
+    let server = TestServer::start().await.unwrap();
+    let webhook = server.create_webhook().await.unwrap();
+
+    let socket_url = format!("{}/ws/{}?token={}", server.url().replace("http://", "ws://"), webhook.id, webhook.token);
+    let (mut socket, _) = tokio_tungstenite::connect_async(socket_url.into_client_request().unwrap()).await.unwrap();
+    let greeting = socket.next().await.unwrap().unwrap();
+    assert!(greeting.to_text().unwrap().contains("connected"));
+
+    let url = webhook.url.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        reqwest::Client::new().post(url).body("late").send().await.unwrap();
+    });
+    let req = server.next_request(Duration::from_secs(5), |req| req.body == "late").await.unwrap();
+    assert_eq!(req.webhook_id, webhook.id);
+
+    // Dashboards still get every request while the test server listens in
+    let Message::Text(pushed) = socket.next().await.unwrap().unwrap() else { panic!("expected text") };
+    let pushed: Value = serde_json::from_str(&pushed).unwrap();
+    assert_eq!(pushed["id"], req.id);
+}
+
+#[tokio::test]
+async fn shutdown_stops_the_listener() {
+    let server = TestServer::start().await.unwrap();
+    let url = format!("{}/new", server.url());
+    assert!(reqwest::get(&url).await.unwrap().status().is_success());
+
+    server.shutdown().await.unwrap();
+    assert!(reqwest::get(&url).await.is_err());
+}
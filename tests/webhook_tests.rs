@@ -6,13 +6,16 @@ use axum::{
 };
 use tower::ServiceExt;
 
-use webhook_tester::routes::webhook::webhook_handler;
+use webhook_tester::auth::hash_token;
+use webhook_tester::routes::webhook::{reset_response_sequence, set_custom_response, webhook_handler, WEBHOOK_TOKEN_HEADER};
 use webhook_tester::models::WebhookConfig;
-use common::test_state;
+use webhook_tester::settings::UnknownWebhookPolicy;
+use common::{register_webhook, test_state};
 
 #[tokio::test]
 async fn webhook_stores_request_and_returns_default_response() {
     let state = test_state().await;
+    register_webhook(&state, "test123").await;
 
     let app = axum::Router::new()
         .route("/webhook/:id", axum::routing::post(webhook_handler))
@@ -44,6 +47,7 @@ async fn webhook_stores_request_and_returns_default_response() {
 #[tokio::test]
 async fn webhook_respects_custom_response() {
     let state = test_state().await;
+    register_webhook(&state, "abc").await;
 
     state.db
         .set_response_config(&WebhookConfig {
@@ -52,6 +56,7 @@ async fn webhook_respects_custom_response() {
             response_body: Some("Created".into()),
             content_type: Some("text/plain".into()),
             forward_url: None,
+            ..Default::default()
         })
         .await
         .unwrap();
@@ -73,3 +78,283 @@ async fn webhook_respects_custom_response() {
 
     assert_eq!(response.status(), StatusCode::CREATED);
 }
+
+#[tokio::test]
+async fn webhook_plays_response_sequence_then_sticks_on_last() {
+    let state = test_state().await;
+    let token = register_webhook(&state, "seq").await;
+
+    let app = axum::Router::new()
+        .route("/webhook/:id", axum::routing::post(webhook_handler))
+        .route("/webhook/:id/config", axum::routing::post(set_custom_response))
+        .route("/webhook/:id/config/reset", axum::routing::post(reset_response_sequence))
+        .with_state(state);
+
+    let config = serde_json::json!({
+        "response_sequence": [
+            { "status_code": 503 },
+            { "status_code": 503 },
+            { "status_code": 200, "response_body": "recovered" }
+        ]
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/seq/config")
+                .method("POST")
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {token}"))
+                .body(Body::from(config.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut statuses = Vec::new();
+    for _ in 0..4 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/webhook/seq")
+                    .method("POST")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        statuses.push(response.status().as_u16());
+    }
+    assert_eq!(statuses, vec![503, 503, 200, 200]);
+
+    app.clone()
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/seq/config/reset")
+                .method("POST")
+                .header("authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/seq")
+                .method("POST")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn webhook_response_sequence_can_loop() {
+    let state = test_state().await;
+    register_webhook(&state, "alt").await;
+
+    state.db
+        .set_response_config(&WebhookConfig {
+            webhook_id: "alt".into(),
+            status_code: Some(200),
+            response_body: None,
+            content_type: None,
+            forward_url: None,
+            response_sequence: Some(r#"[{"status_code":200},{"status_code":500}]"#.into()),
+            sequence_mode: Some("loop".into()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let app = axum::Router::new()
+        .route("/webhook/:id", axum::routing::post(webhook_handler))
+        .with_state(state);
+
+    let mut statuses = Vec::new();
+    for _ in 0..4 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/webhook/alt")
+                    .method("POST")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        statuses.push(response.status().as_u16());
+    }
+    assert_eq!(statuses, vec![200, 500, 200, 500]);
+}
+
+#[tokio::test]
+async fn webhook_config_rejects_unknown_sequence_mode() {
+    let state = test_state().await;
+    let token = register_webhook(&state, "mode").await;
+
+    let app = axum::Router::new()
+        .route("/webhook/:id/config", axum::routing::post(set_custom_response))
+        .with_state(state.clone());
+
+    let config = serde_json::json!({
+        "response_sequence": [{ "status_code": 200 }],
+        "sequence_mode": "bounce"
+    });
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/mode/config")
+                .method("POST")
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {token}"))
+                .body(Body::from(config.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(state.db.get_response_config("mode").await.unwrap().webhook_id.is_empty());
+}
+
+#[tokio::test]
+async fn webhook_records_sent_response_with_request() {
+    let state = test_state().await;
+    register_webhook(&state, "rec").await;
+
+    state.db
+        .set_response_config(&WebhookConfig {
+            webhook_id: "rec".into(),
+            status_code: Some(202),
+            response_body: Some("queued".into()),
+            content_type: Some("text/plain".into()),
+            forward_url: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let app = axum::Router::new()
+        .route("/webhook/:id", axum::routing::post(webhook_handler))
+        .with_state(state.clone());
+    let mut events = state.tx.subscribe();
+
+    app.oneshot(
+        Request::builder()
+            .uri("/webhook/rec")
+            .method("POST")
+            .body(Body::from("payload"))
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let stored = state.get_requests("rec").await.pop().unwrap();
+
+    assert_eq!(stored.response_status, Some(202));
+    assert_eq!(stored.response_body.as_deref(), Some("queued"));
+    assert_eq!(stored.response_source.as_deref(), Some("config"));
+    assert_eq!(stored.config_version, Some(1));
+    assert!(stored.response_headers.unwrap().contains("text/plain"));
+    assert!(stored.latency_ms.is_some());
+
+    let event = events.recv().await.unwrap();
+    assert!(event.response_headers.unwrap().contains("text/plain"));
+    assert_eq!(event.config_version, Some(1));
+    assert_eq!(event.latency_ms, stored.latency_ms);
+
+    // Every save is a new version
+    let mut config = state.db.get_response_config("rec").await.unwrap();
+    config.status_code = Some(200);
+    state.db.set_response_config(&config).await.unwrap();
+    assert_eq!(state.db.get_response_config("rec").await.unwrap().version, Some(2));
+}
+
+#[tokio::test]
+async fn webhook_rejects_unknown_id_by_default() {
+    let state = test_state().await;
+
+    let app = axum::Router::new()
+        .route("/webhook/:id", axum::routing::post(webhook_handler))
+        .with_state(state.clone());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/never-created")
+                .method("POST")
+                .body(Body::from("lost"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(state.get_requests("never-created").await.is_empty());
+}
+
+#[tokio::test]
+async fn webhook_auto_creates_unknown_id_when_configured() {
+    let mut state = (*test_state().await).clone();
+    state.settings.unknown_webhooks = UnknownWebhookPolicy::AutoCreate;
+    let state = std::sync::Arc::new(state);
+
+    let app = axum::Router::new()
+        .route("/webhook/:id", axum::routing::post(webhook_handler))
+        .with_state(state.clone());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/on-the-fly")
+                .method("POST")
+                .body(Body::from("kept"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let token = response.headers()[WEBHOOK_TOKEN_HEADER].to_str().unwrap();
+    let expected = state.db.get_token_hash("on-the-fly").await.unwrap().flatten();
+    assert_eq!(expected, Some(hash_token(token)));
+    assert_eq!(state.get_requests("on-the-fly").await.len(), 1);
+
+    // Only the first request is told the token
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/webhook/on-the-fly")
+                .method("POST")
+                .body(Body::from("again"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(WEBHOOK_TOKEN_HEADER).is_none());
+    assert_eq!(state.get_requests("on-the-fly").await.len(), 2);
+}
+
+#[tokio::test]
+async fn concurrent_first_requests_claim_a_webhook_once() {
+    let mut state = (*test_state().await).clone();
+    state.settings.unknown_webhooks = UnknownWebhookPolicy::AutoCreate;
+    let state = std::sync::Arc::new(state);
+
+    let claims = futures_util::future::join_all((0..8).map(|_| state.claim_webhook("racy"))).await;
+    let tokens: Vec<String> = claims.into_iter().filter_map(|claim| claim.unwrap().1).collect();
+    assert_eq!(tokens.len(), 1);
+    let expected = state.db.get_token_hash("racy").await.unwrap().flatten();
+    assert_eq!(expected, Some(hash_token(&tokens[0])));
+}
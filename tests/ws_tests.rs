@@ -1,3 +1,5 @@
+mod common;
+
 use axum::{
     routing::get,
     Router,
@@ -6,12 +8,9 @@ use futures_util::StreamExt;
 use tokio::time::{timeout, Duration};
 use tokio_tungstenite::tungstenite::Message;
 
-use webhook_tester::routes::ws::{ws_handler, broadcast_to_clients};
-use webhook_tester::models::StoredRequest;
-
-fn test_app() -> Router {
-    Router::new().route("/ws/:id", get(ws_handler))
-}
+use webhook_tester::routes::ws::{ws_handler, broadcast_event, broadcast_to_clients};
+use webhook_tester::models::{DashboardEvent, StoredRequest};
+use common::{register_webhook, test_state};
 
 fn test_stored_request(webhook_id: &str, id: &str, body: &str) -> StoredRequest {
     StoredRequest {
@@ -22,16 +21,22 @@ fn test_stored_request(webhook_id: &str, id: &str, body: &str) -> StoredRequest
         body: body.to_string(),
         query: "".to_string(),
         created_at: "2025-01-01T00:00:00Z".to_string(),
+        ..Default::default()
     }
 }
 
-async fn spawn_app() -> String {
+/// Serve the socket route for one webhook, returning its URL with the token attached
+async fn spawn_app(webhook_id: &str) -> String {
     let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
         .await
         .unwrap();
 
     let addr = listener.local_addr().unwrap();
-    let app = test_app();
+    let state = test_state().await;
+    let token = register_webhook(&state, webhook_id).await;
+    let app = Router::new()
+        .route("/ws/:id", get(ws_handler))
+        .with_state(state);
 
     tokio::spawn(async move {
         axum::serve(listener, app)
@@ -39,14 +44,13 @@ async fn spawn_app() -> String {
             .unwrap();
     });
 
-    format!("ws://{}", addr)
+    format!("ws://{addr}/ws/{webhook_id}?token={token}")
 }
 
 
 #[tokio::test]
 async fn websocket_sends_connected_message() {
-    let base = spawn_app().await;
-    let url = format!("{}/ws/test-webhook", base);
+    let url = spawn_app("test-webhook").await;
 
     let (mut ws, _) = tokio_tungstenite::connect_async(url)
         .await
@@ -68,8 +72,7 @@ async fn websocket_sends_connected_message() {
 
 #[tokio::test]
 async fn broadcast_reaches_connected_client() {
-    let base = spawn_app().await;
-    let url = format!("{}/ws/abc123", base);
+    let url = spawn_app("abc123").await;
 
     let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
 
@@ -101,3 +104,27 @@ async fn broadcast_with_no_clients_does_not_panic() {
 
     broadcast_to_clients("non-existent", &req).await;
 }
+
+#[tokio::test]
+async fn dashboard_events_reach_connected_client() {
+    let url = spawn_app("events-wh").await;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    let _ = ws.next().await;
+
+    let event = DashboardEvent::RequestDeleted { request_id: "req-9".into() };
+    broadcast_event("events-wh", &event).await;
+
+    let msg = timeout(Duration::from_secs(1), ws.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    let Message::Text(text) = msg else {
+        panic!("expected text message");
+    };
+
+    assert!(text.contains("\"event\":\"request_deleted\""));
+    assert_eq!(serde_json::from_str::<DashboardEvent>(&text).unwrap(), event);
+}